@@ -0,0 +1,693 @@
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::process;
+
+use solver::prelude::*;
+use solver::DataField;
+use solver::differential_equation::{AnisotropicPhaseFieldEquation, FitzHughNagumoEquation,
+                                    GrayScottEquation, PhaseFieldEquation, ThermalConduction};
+use solver::{csv, npy};
+use solver::colormap::{FieldRenderer, GifWriter};
+use solver::geometry::{Bitmap, CellType, Mask, Rectangle, Shape};
+use solver::source::RegionSource;
+use solver::precondition::{ConstantPreCondition, FieldPreCondition, ShapePreCondition};
+use solver::vtk::{self, TimeSeries};
+
+use scenario::{Equation, Format, RegionShape, Scenario};
+
+pub const USAGE: &'static str = "Usage: rusty_solver --headless [options]
+
+Options:
+    --scenario <file>                Scenario file to load, see scenario.rs for the format
+    --equation <thermal|phasefield|dendrite|gray_scott|fitzhugh_nagumo>
+                                     Equation to solve without a scenario file (default: phasefield)
+    --steps <n>                      Number of steps to solve (default: 1000)
+    --size <nx>x<ny>                 Grid size including the boundary (default: 200x200)
+    --delta-t <dt>                   Time step (default: 0.1)
+    --h <h>                          Grid spacing (default: 1.0)
+    --snapshot-every <n>             Write a snapshot every n steps, 0 disables them (default: 100)
+    --output <dir>                   Directory for the snapshots (default: output)
+    --format <png|gif|vtk|vti|npy|csv>
+                                     File format of the snapshots (default: png)
+    --checkpoint-every <n>           Write a checkpoint every n steps, 0 disables them (default: 0)
+    --resume <file>                  Resume the run from a checkpoint
+
+All options except --equation override the values of the scenario file.";
+
+#[derive(Default)]
+pub struct Options {
+    pub scenario: Option<PathBuf>,
+    pub equation: Option<String>,
+    pub dimensions: Option<(usize, usize)>,
+    pub steps: Option<usize>,
+    pub delta_t: Option<f32>,
+    pub h: Option<f32>,
+    pub snapshot_interval: Option<usize>,
+    pub output: Option<PathBuf>,
+    pub format: Option<Format>,
+    pub checkpoint_interval: Option<usize>,
+    pub resume: Option<PathBuf>,
+}
+
+impl Options {
+    pub fn parse<I>(args: I) -> Result<Options, String>
+        where I: IntoIterator<Item = String>
+    {
+        let mut options = Options::default();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let value = match args.next() {
+                Some(value) => value,
+                None => return Err(format!("Missing value for {}", arg)),
+            };
+
+            match &arg[..] {
+                "--scenario" => options.scenario = Some(PathBuf::from(value)),
+                "--equation" => {
+                    if Equation::from_name(&value).is_none() {
+                        return Err(format!("Unknown equation {}", value));
+                    }
+                    options.equation = Some(value);
+                }
+                "--steps" => options.steps = Some(parse_value(&arg, &value)?),
+                "--size" => options.dimensions = Some(parse_size(&value)?),
+                "--delta-t" => options.delta_t = Some(parse_value(&arg, &value)?),
+                "--h" => options.h = Some(parse_value(&arg, &value)?),
+                "--snapshot-every" => options.snapshot_interval = Some(parse_value(&arg, &value)?),
+                "--output" => options.output = Some(PathBuf::from(value)),
+                "--format" => {
+                    options.format = match Format::from_name(&value) {
+                        Some(format) => Some(format),
+                        None => return Err(format!("Unknown format {}", value)),
+                    };
+                }
+                "--checkpoint-every" => {
+                    options.checkpoint_interval = Some(parse_value(&arg, &value)?)
+                }
+                "--resume" => options.resume = Some(PathBuf::from(value)),
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        if options.scenario.is_some() && options.equation.is_some() {
+            return Err("--equation can't be combined with --scenario".to_string());
+        }
+
+        Ok(options)
+    }
+
+    /// Loads the scenario file or creates the built-in scenario of the
+    /// equation, and applies the remaining options to it.
+    pub fn scenario(&self) -> Result<Scenario, String> {
+        let mut scenario = match self.scenario {
+            Some(ref path) => Scenario::load(path)?,
+            None => {
+                let name = self.equation.as_ref().map_or("phasefield", |name| &name[..]);
+                let equation = Equation::from_name(name).unwrap();
+                Scenario::new(equation, self.dimensions.unwrap_or((200, 200)))
+            }
+        };
+
+        if let Some(dimensions) = self.dimensions {
+            scenario.dimensions = dimensions;
+        }
+        if let Some(steps) = self.steps {
+            scenario.steps = steps;
+        }
+        if let Some(delta_t) = self.delta_t {
+            scenario.delta_t = delta_t;
+        }
+        if let Some(h) = self.h {
+            scenario.h = h;
+        }
+        if let Some(snapshot_interval) = self.snapshot_interval {
+            scenario.output.interval = snapshot_interval;
+        }
+        if let Some(ref output) = self.output {
+            scenario.output.directory = output.clone();
+        }
+        if let Some(format) = self.format {
+            scenario.output.format = format;
+        }
+        if let Some(checkpoint_interval) = self.checkpoint_interval {
+            scenario.output.checkpoint_interval = checkpoint_interval;
+        }
+
+        scenario.validate()?;
+        Ok(scenario)
+    }
+}
+
+fn parse_value<T: ::std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value {} for {}", value, arg))
+}
+
+fn parse_size(value: &str) -> Result<(usize, usize), String> {
+    let mut parts = value.split('x');
+    match (parts.next().map(str::parse), parts.next().map(str::parse), parts.next()) {
+        (Some(Ok(nx)), Some(Ok(ny)), None) if nx >= 3 && ny >= 3 => Ok((nx, ny)),
+        _ => Err(format!("Invalid size {}, expected <nx>x<ny> with at least 3x3 cells", value)),
+    }
+}
+
+pub fn run(options: Options) {
+    let scenario = match options.scenario() {
+        Ok(scenario) => scenario,
+        Err(message) => exit_with_error(&message),
+    };
+    let resume = options.resume.as_deref();
+
+    if scenario.output.interval > 0 || scenario.output.checkpoint_interval > 0 {
+        if let Err(error) = fs::create_dir_all(&scenario.output.directory) {
+            exit_with_error(&format!("Couldn't create {}: {}",
+                                     scenario.output.directory.display(),
+                                     error));
+        }
+    }
+
+    let boundaries = &scenario.boundaries;
+    let new_boundary_conditions = || {
+        BoundaryConditions::new(boundaries.up.boxed(),
+                                boundaries.down.boxed(),
+                                boundaries.left.boxed(),
+                                boundaries.right.boxed())
+    };
+    let boundary_conditions = new_boundary_conditions();
+
+    match scenario.equation {
+        Equation::ThermalConduction => {
+            let solver = Solver::with_time_integration(ThermalConduction,
+                                                       boundary_conditions,
+                                                       scenario.dimensions,
+                                                       scenario.delta_t,
+                                                       scenario.h,
+                                                       scenario.time_integration);
+            simulate_single(solver, &scenario, resume);
+        }
+        Equation::PhaseField(ref equation) => {
+            let equation = PhaseFieldEquation { ..*equation };
+            let solver = Solver::with_time_integration(equation,
+                                                       boundary_conditions,
+                                                       scenario.dimensions,
+                                                       scenario.delta_t,
+                                                       scenario.h,
+                                                       scenario.time_integration);
+            simulate_single(solver, &scenario, resume);
+        }
+        Equation::Dendrite(ref equation) => {
+            let equation = AnisotropicPhaseFieldEquation { ..*equation };
+            let solver = Solver::with_time_integration(equation,
+                                                       boundary_conditions,
+                                                       scenario.dimensions,
+                                                       scenario.delta_t,
+                                                       scenario.h,
+                                                       scenario.time_integration);
+            simulate_single(solver, &scenario, resume);
+        }
+        Equation::GrayScott(ref equation) => {
+            let equation = GrayScottEquation { ..*equation };
+            let initial_conditions = equation.reference_conditions(scenario.dimensions);
+            let solver = CoupledSolver::with_time_integration(equation,
+                                                              vec![boundary_conditions,
+                                                                   new_boundary_conditions()],
+                                                              scenario.dimensions,
+                                                              scenario.delta_t,
+                                                              scenario.h,
+                                                              scenario.time_integration);
+            simulate_coupled(solver, &initial_conditions, &scenario, resume);
+        }
+        Equation::FitzHughNagumo(ref equation) => {
+            let equation = FitzHughNagumoEquation { ..*equation };
+            let initial_conditions = equation.reference_conditions(scenario.dimensions);
+            let solver = CoupledSolver::with_time_integration(equation,
+                                                              vec![boundary_conditions,
+                                                                   new_boundary_conditions()],
+                                                              scenario.dimensions,
+                                                              scenario.delta_t,
+                                                              scenario.h,
+                                                              scenario.time_integration);
+            simulate_coupled(solver, &initial_conditions, &scenario, resume);
+        }
+    }
+}
+
+/// Sets up a scenario of an equation with a single field and runs it.
+fn simulate_single<D, BU, BD, BL, BR>(mut solver: Solver<f32, D, BU, BD, BL, BR>,
+                                      scenario: &Scenario,
+                                      resume: Option<&Path>)
+    where D: DifferentialEquation<f32> + Sync + Send + 'static,
+          BU: BoundaryCondition<f32> + Sync + Send + 'static,
+          BD: BoundaryCondition<f32> + Sync + Send + 'static,
+          BL: BoundaryCondition<f32> + Sync + Send + 'static,
+          BR: BoundaryCondition<f32> + Sync + Send + 'static
+{
+    solver.execute_precondition(&ConstantPreCondition(scenario.initial_value));
+    if let Some(ref path) = scenario.initial_file {
+        let field = read_field(path).unwrap_or_else(|error| {
+            exit_with_error(&format!("Couldn't read {}: {}", path.display(), error))
+        });
+        if field.dimensions != scenario.dimensions {
+            exit_with_error(&format!("{} contains a {}x{} field, but the grid is {}x{}",
+                                     path.display(),
+                                     field.dimensions.0,
+                                     field.dimensions.1,
+                                     scenario.dimensions.0,
+                                     scenario.dimensions.1));
+        }
+        solver.execute_precondition(&FieldPreCondition(field));
+    }
+    for region in &scenario.initial_regions {
+        let shape = load_shape(&region.shape);
+        for (coord, cell) in solver.get_field_mut().iter_mut() {
+            if shape.contains(coord) {
+                *cell = region.value;
+            }
+        }
+    }
+
+    let mut mask = Mask::new(scenario.dimensions);
+    for obstacle in &scenario.obstacles {
+        mask.fill(&*load_shape(&obstacle.shape), CellType::Insulated);
+    }
+    for source in &scenario.sources {
+        mask.fill(&*load_shape(&source.shape), CellType::FixedValue(source.value));
+    }
+    solver.mask = Some(mask);
+
+    for source_term in &scenario.source_terms {
+        solver.add_source(RegionSource {
+            shape: load_shape(&source_term.shape),
+            strength: source_term.value,
+        });
+    }
+
+    simulate(solver, scenario, resume);
+}
+
+/// Sets up a scenario of an equation with several fields, which starts from
+/// the initial conditions of the equation, and runs it. The initial regions
+/// set the field that gets written.
+fn simulate_coupled<E>(mut solver: CoupledSolver<f32, E>,
+                       initial_conditions: &[(&str, ShapePreCondition<Rectangle, f32>)],
+                       scenario: &Scenario,
+                       resume: Option<&Path>)
+    where E: CoupledEquation<f32> + Sync + Send + 'static
+{
+    for &(name, ref condition) in initial_conditions {
+        solver.execute_precondition(name, condition);
+    }
+    let field_name = scenario.equation.field_name();
+    for region in &scenario.initial_regions {
+        let shape = load_shape(&region.shape);
+        for (coord, cell) in solver.get_field_mut(field_name).unwrap().iter_mut() {
+            if shape.contains(coord) {
+                *cell = region.value;
+            }
+        }
+    }
+
+    simulate(solver, scenario, resume);
+}
+
+/// The parts of `Solver` and `CoupledSolver` that `simulate` needs.
+trait Simulation: Sized {
+    fn steps(&self) -> usize;
+
+    fn time(&self) -> f32;
+
+    fn h(&self) -> f32;
+
+    /// Returns the fields along with their names. A single field is named
+    /// `name`.
+    fn named_fields<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a DataField<f32>)>;
+
+    fn solve_next_frame(&mut self);
+
+    fn add_observer<F>(&mut self, interval: usize, observer: F)
+        where F: FnMut(&Self) + Send + 'static;
+
+    fn save_checkpoint(&self, writer: &mut BufWriter<File>) -> io::Result<()>;
+
+    fn load_checkpoint(&mut self, reader: &mut BufReader<File>) -> io::Result<()>;
+}
+
+impl<D, BU, BD, BL, BR> Simulation for Solver<f32, D, BU, BD, BL, BR>
+    where D: DifferentialEquation<f32> + Sync + Send,
+          BU: BoundaryCondition<f32> + Sync + Send,
+          BD: BoundaryCondition<f32> + Sync + Send,
+          BL: BoundaryCondition<f32> + Sync + Send,
+          BR: BoundaryCondition<f32> + Sync + Send
+{
+    fn steps(&self) -> usize {
+        Solver::steps(self)
+    }
+
+    fn time(&self) -> f32 {
+        Solver::time(self)
+    }
+
+    fn h(&self) -> f32 {
+        Solver::h(self)
+    }
+
+    fn named_fields<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a DataField<f32>)> {
+        vec![(name, self.get_field())]
+    }
+
+    fn solve_next_frame(&mut self) {
+        Solver::solve_next_frame(self)
+    }
+
+    fn add_observer<F>(&mut self, interval: usize, observer: F)
+        where F: FnMut(&Self) + Send + 'static
+    {
+        Solver::add_observer(self, interval, observer)
+    }
+
+    fn save_checkpoint(&self, writer: &mut BufWriter<File>) -> io::Result<()> {
+        Solver::save_checkpoint(self, writer)
+    }
+
+    fn load_checkpoint(&mut self, reader: &mut BufReader<File>) -> io::Result<()> {
+        Solver::load_checkpoint(self, reader)
+    }
+}
+
+impl<E> Simulation for CoupledSolver<f32, E>
+    where E: CoupledEquation<f32> + Sync + Send
+{
+    fn steps(&self) -> usize {
+        CoupledSolver::steps(self)
+    }
+
+    fn time(&self) -> f32 {
+        CoupledSolver::time(self)
+    }
+
+    fn h(&self) -> f32 {
+        CoupledSolver::h(self)
+    }
+
+    fn named_fields<'a>(&'a self, _: &'a str) -> Vec<(&'a str, &'a DataField<f32>)> {
+        self.field_names()
+            .iter()
+            .map(|name| &name[..])
+            .zip(self.get_fields())
+            .collect()
+    }
+
+    fn solve_next_frame(&mut self) {
+        CoupledSolver::solve_next_frame(self)
+    }
+
+    fn add_observer<F>(&mut self, interval: usize, observer: F)
+        where F: FnMut(&Self) + Send + 'static
+    {
+        CoupledSolver::add_observer(self, interval, observer)
+    }
+
+    fn save_checkpoint(&self, writer: &mut BufWriter<File>) -> io::Result<()> {
+        CoupledSolver::save_checkpoint(self, writer)
+    }
+
+    fn load_checkpoint(&mut self, reader: &mut BufReader<File>) -> io::Result<()> {
+        CoupledSolver::load_checkpoint(self, reader)
+    }
+}
+
+/// Resumes the solver from a checkpoint if requested, and runs it for the
+/// steps of the scenario while writing the progress, the snapshots and the
+/// checkpoints.
+fn simulate<S: Simulation>(mut solver: S, scenario: &Scenario, resume: Option<&Path>) {
+    if let Some(path) = resume {
+        let loaded = File::open(path)
+                         .and_then(|file| solver.load_checkpoint(&mut BufReader::new(file)));
+        if let Err(error) = loaded {
+            exit_with_error(&format!("Couldn't resume from {}: {}", path.display(), error));
+        }
+    }
+
+    let total_steps = scenario.steps;
+
+    let progress_interval = if total_steps >= 100 {
+        total_steps / 100
+    } else {
+        1
+    };
+    solver.add_observer(progress_interval, move |solver| {
+        let percentage = 100 * solver.steps() / total_steps;
+        print!("\rStep {}/{} ({}%), t = {}",
+               solver.steps(),
+               total_steps,
+               percentage,
+               solver.time());
+        io::stdout().flush().ok();
+    });
+
+    let output = &scenario.output;
+    if output.interval > 0 {
+        let mut snapshots = Snapshots::new(scenario);
+        let mut write = move |solver: &S| {
+            let fields = solver.named_fields(snapshots.field_name);
+            snapshots.write(solver.steps(), solver.time(), solver.h(), &fields);
+        };
+        write(&solver);
+        solver.add_observer(output.interval, write);
+    }
+
+    if output.checkpoint_interval > 0 {
+        let path = output.directory.join(format!("{}.checkpoint", output.name));
+        solver.add_observer(output.checkpoint_interval, move |solver| {
+            write_checkpoint(&path, |writer| solver.save_checkpoint(writer))
+        });
+    }
+
+    while solver.steps() < total_steps {
+        solver.solve_next_frame();
+    }
+    println!("");
+}
+
+fn load_shape(shape: &RegionShape) -> Box<dyn Shape + Sync + Send> {
+    match *shape {
+        RegionShape::Rectangle(ref rectangle) => Box::new(rectangle.clone()),
+        RegionShape::Circle(ref circle) => Box::new(circle.clone()),
+        RegionShape::Polygon(ref polygon) => Box::new(polygon.clone()),
+        RegionShape::Bitmap(ref path, position) => {
+            let bitmap = File::open(path)
+                             .and_then(|file| Bitmap::read_png(BufReader::new(file), position));
+            match bitmap {
+                Ok(bitmap) => Box::new(bitmap),
+                Err(error) => {
+                    exit_with_error(&format!("Couldn't read {}: {}", path.display(), error))
+                }
+            }
+        }
+    }
+}
+
+fn read_field(path: &Path) -> io::Result<DataField<f32>> {
+    let file = File::open(path)?;
+    if path.extension().is_some_and(|extension| extension == "csv") {
+        csv::read(BufReader::new(file))
+    } else {
+        npy::read(&mut BufReader::new(file))
+    }
+}
+
+struct Snapshots {
+    directory: PathBuf,
+    name: String,
+    field_name: &'static str,
+    format: Format,
+    renderer: FieldRenderer<f32>,
+    gif: Option<GifWriter<BufWriter<File>>>,
+    time_series: TimeSeries,
+}
+
+impl Snapshots {
+    fn new(scenario: &Scenario) -> Self {
+        let output = &scenario.output;
+        Snapshots {
+            directory: output.directory.clone(),
+            name: output.name.clone(),
+            field_name: scenario.equation.field_name(),
+            format: output.format,
+            renderer: FieldRenderer {
+                color_map: output.color_map.clone(),
+                value_range: output.value_range,
+                color_bar: output.color_bar,
+            },
+            gif: None,
+            time_series: TimeSeries::new(),
+        }
+    }
+
+    /// Writes the field named `field_name`, or all of the fields for the VTK
+    /// formats. GIF snapshots are added as frames to a single animation,
+    /// while the VTK image data snapshots are added to a `.pvd` time series,
+    /// so ParaView can load them all at once.
+    fn write(&mut self, steps: usize, time: f32, h: f32, fields: &[(&str, &DataField<f32>)]) {
+        let file_name = format!("{}_{:06}.{}", self.name, steps, self.format.extension());
+        let path = match self.format {
+            Format::Gif => self.directory.join(format!("{}.gif", self.name)),
+            _ => self.directory.join(&file_name),
+        };
+        let field = fields.iter()
+                          .find(|&&(name, _)| name == self.field_name)
+                          .map(|&(_, field)| field)
+                          .expect("The field to write is missing");
+
+        let result = match self.format {
+            Format::Png => {
+                File::create(&path).and_then(|file| {
+                    let mut writer = BufWriter::new(file);
+                    self.renderer.render(field).write_png(&mut writer)?;
+                    writer.flush()
+                })
+            }
+            Format::Gif => {
+                let image = self.renderer.render(field);
+                let size = (image.width, image.height);
+                let gif = match self.gif.take() {
+                    Some(gif) => Ok(gif),
+                    None => {
+                        File::create(&path)
+                            .and_then(|file| GifWriter::new(BufWriter::new(file), size, 10))
+                    }
+                };
+                gif.and_then(|mut gif| {
+                    let result = gif.add_frame(&image);
+                    self.gif = Some(gif);
+                    result
+                })
+            }
+            Format::Vtk => {
+                File::create(&path)
+                    .and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+                        vtk::write_legacy(&mut writer, fields, h, time)?;
+                        writer.flush()
+                    })
+            }
+            Format::Vti => {
+                self.time_series.add(time, &file_name);
+                let series_path = self.directory.join(format!("{}.pvd", self.name));
+                File::create(&path)
+                    .and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+                        vtk::write_vti(&mut writer, fields, h, time)?;
+                        writer.flush()
+                    })
+                    .and_then(|_| File::create(&series_path))
+                    .and_then(|file| self.time_series.write_to(&mut BufWriter::new(file)))
+            }
+            Format::Npy => {
+                File::create(&path)
+                    .and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+                        npy::write(&mut writer, field)?;
+                        writer.flush()
+                    })
+            }
+            Format::Csv => {
+                File::create(&path)
+                    .and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+                        csv::write(&mut writer, field)?;
+                        writer.flush()
+                    })
+            }
+        };
+
+        if let Err(error) = result {
+            exit_with_error(&format!("Couldn't write {}: {}", path.display(), error));
+        }
+    }
+}
+
+/// Writes the checkpoint with `save` to a temporary file first, so that a
+/// crash while writing doesn't destroy the previous checkpoint.
+fn write_checkpoint<F>(path: &Path, save: F)
+    where F: FnOnce(&mut BufWriter<File>) -> io::Result<()>
+{
+    let temporary_path = path.with_extension("checkpoint.tmp");
+    let result = File::create(&temporary_path)
+                     .and_then(|file| {
+                         let mut writer = BufWriter::new(file);
+                         save(&mut writer)?;
+                         writer.flush()
+                     })
+                     .and_then(|_| fs::rename(&temporary_path, path));
+    if let Err(error) = result {
+        exit_with_error(&format!("Couldn't write {}: {}", path.display(), error));
+    }
+}
+
+pub fn exit_with_error(message: &str) -> ! {
+    writeln!(io::stderr(), "{}", message).ok();
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use scenario::{Equation, Scenario};
+    use super::Options;
+
+    fn scenario(args: &[&str]) -> Result<Scenario, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+            .and_then(|options| options.scenario())
+    }
+
+    #[test]
+    fn uses_the_phasefield_scenario_by_default() {
+        let scenario = scenario(&[]).unwrap_or_else(|message| panic!("{}", message));
+        assert!(match scenario.equation {
+            Equation::PhaseField(_) => true,
+            _ => false,
+        });
+        assert_eq!(scenario.dimensions, (200, 200));
+        assert_eq!(scenario.steps, 1000);
+        assert_eq!(scenario.output.interval, 100);
+        assert_eq!(scenario.output.directory, PathBuf::from("output"));
+    }
+
+    #[test]
+    fn applies_all_options() {
+        let scenario = scenario(&["--equation", "thermal", "--steps", "20", "--size", "30x40",
+                                  "--delta-t", "0.5", "--h", "2", "--snapshot-every", "0",
+                                  "--output", "frames"])
+            .unwrap_or_else(|message| panic!("{}", message));
+        assert!(match scenario.equation {
+            Equation::ThermalConduction => true,
+            _ => false,
+        });
+        assert_eq!(scenario.steps, 20);
+        assert_eq!(scenario.dimensions, (30, 40));
+        assert_eq!(scenario.delta_t, 0.5);
+        assert_eq!(scenario.h, 2.0);
+        assert_eq!(scenario.output.interval, 0);
+        assert_eq!(scenario.output.directory, PathBuf::from("frames"));
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let invalid: &[&[&str]] = &[&["--steps"],
+                                    &["--steps", "-1"],
+                                    &["--delta-t", "fast"],
+                                    &["--equation", "wave"],
+                                    &["--size", "2x10"],
+                                    &["--size", "10"],
+                                    &["--size", "10x10x10"],
+                                    &["--colors", "12"],
+                                    &["--scenario", "a.toml", "--equation", "thermal"]];
+        for args in invalid {
+            assert!(Options::parse(args.iter().map(|arg| arg.to_string())).is_err(),
+                    "{:?} got accepted",
+                    args);
+        }
+    }
+}
@@ -4,11 +4,15 @@ extern crate graphics;
 extern crate glutin_window;
 extern crate opengl_graphics;
 extern crate image;
-extern crate palette;
+extern crate toml;
 
+mod headless;
+mod scenario;
 mod switchable_boundary_condition;
 mod utils;
 
+use std::env;
+
 use piston::window::WindowSettings;
 use piston::event_loop::*;
 use piston::input::*;
@@ -22,6 +26,21 @@ mod phasefield_app;
 use phasefield_app::PhaseFieldApp as App;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--headless") {
+        if args.iter().any(|arg| arg == "--help") {
+            println!("{}", headless::USAGE);
+            return;
+        }
+        match headless::Options::parse(args.into_iter().filter(|arg| arg != "--headless")) {
+            Ok(options) => headless::run(options),
+            Err(message) => {
+                headless::exit_with_error(&format!("{}\n\n{}", message, headless::USAGE))
+            }
+        }
+        return;
+    }
+
     let opengl = OpenGL::V2_1;
 
     let dimensions = (200, 200);
@@ -13,11 +13,12 @@ use super::graphics;
 
 pub struct PhaseFieldApp {
     gl: GlGraphics,
-    solver: Solver<PhaseFieldEquation,
-                   SwitchableBoundaryCondition<Dirichlet, Neumann>,
-                   SwitchableBoundaryCondition<Dirichlet, Neumann>,
-                   SwitchableBoundaryCondition<Dirichlet, Neumann>,
-                   Dirichlet>,
+    solver: Solver<f32,
+                   PhaseFieldEquation<f32>,
+                   SwitchableBoundaryCondition<f32, Dirichlet<f32>, Neumann>,
+                   SwitchableBoundaryCondition<f32, Dirichlet<f32>, Neumann>,
+                   SwitchableBoundaryCondition<f32, Dirichlet<f32>, Neumann>,
+                   Dirichlet<f32>>,
     mouse_coord: (f64, f64),
     left_mouse_down: bool,
     right_mouse_down: bool,
@@ -48,7 +49,7 @@ impl PhaseFieldApp {
 
         PhaseFieldApp {
             gl: GlGraphics::new(opengl),
-            solver: solver,
+            solver,
             mouse_coord: (0.0, 0.0),
             left_mouse_down: false,
             right_mouse_down: false,
@@ -0,0 +1,1158 @@
+//! Scenario files describe a complete simulation in TOML, so that it can be
+//! changed without recompiling:
+//!
+//! ```toml
+//! [equation]
+//! type = "phasefield"           # "dendrite" for the anisotropic phase field, or "thermal"
+//! t = 0.9                       # the phase field parameters are optional
+//!                               # dendrite also takes anisotropy, symmetry and orientation
+//!                               # "gray_scott" takes du, dv, feed and kill, and
+//!                               # "fitzhugh_nagumo" takes a, b, epsilon, du, dv and current
+//!
+//! [grid]
+//! size = [200, 200]             # including the boundary
+//! h = 1.0
+//!
+//! [time]
+//! delta_t = 0.1
+//! steps = 1000
+//! integration = "forward_euler" # heun, midpoint, rk4, backward_euler, crank_nicolson
+//! linear_solver = "cg"          # jacobi, gauss_seidel or cg for the implicit schemes
+//!
+//! [boundaries]                  # sides default to Neumann, and apply to all fields
+//! up = { type = "dirichlet", value = 0.0 }
+//! down = "neumann"                # or "periodic" on both sides of an axis
+//! left = { type = "flux", flux = -0.5 } # outward flux -k dT/dn, conductivity defaults to 1
+//! right = { type = "robin", coefficient = 0.2, ambient = 20.0 } # -k dT/dn = 0.2 (T - 20)
+//!
+//! [initial]                     # gray_scott and fitzhugh_nagumo start from their reference
+//! value = 0.0                   # conditions and only take regions, which set the field
+//! file = "initial.npy"          # that gets written, optional .npy or .csv file, relative
+//!                               # to the scenario
+//!
+//! [[initial.regions]]           # set once before the first step
+//! position = [95, 95]
+//! size = [10, 10]
+//! value = 1.0
+//!
+//! [[obstacles]]                 # insulated walls, nothing flows through them
+//! position = [20, 20]
+//! size = [10, 40]
+//!
+//! [[obstacles]]                 # all regions can have a shape, rectangle is the default
+//! shape = "circle"
+//! center = [100.0, 40.0]
+//! radius = 8.0
+//!
+//! [[obstacles]]
+//! shape = "polygon"
+//! vertices = [[40.0, 150.0], [70.0, 180.0], [40.0, 180.0]]
+//!
+//! [[obstacles]]
+//! shape = "bitmap"              # the dark pixels, relative to the scenario
+//! file = "walls.png"
+//! position = [120, 60]
+//!
+//! [[sources]]                   # fixed values
+//! position = [150, 150]
+//! size = [4, 4]
+//! value = 10.0
+//!
+//! [[source_terms]]              # added to the rate of change, negative strengths are sinks
+//! shape = "circle"
+//! center = [50.0, 100.0]
+//! radius = 3.0
+//! strength = 2.5
+//!
+//! [output]
+//! directory = "output"
+//! name = "phasefield"
+//! every = 100                   # 0 disables the snapshots
+//! format = "png"                # gif, or "vtk" and "vti" for ParaView, "npy" or "csv"
+//! colormap = "greyscale"        # temperature, viridis, inferno or diverging
+//! range = [0.0, 1.0]            # values at both ends of the colormap, or "automatic"
+//! color_bar = false
+//! checkpoint_every = 0          # 0 disables the checkpoints
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use toml::{Parser, Value};
+
+use solver::prelude::*;
+use solver::boundary_condition::{BoxedBoundaryCondition, Dirichlet, Flux, Neumann, Periodic,
+                                 Robin};
+use solver::colormap::{ColorMap, ValueRange};
+use solver::differential_equation::{AnisotropicPhaseFieldEquation, FitzHughNagumoEquation,
+                                    GrayScottEquation, PhaseFieldEquation};
+use solver::geometry::{Circle, Polygon, Rectangle};
+
+use utils::temperature_color_map;
+
+pub enum Equation {
+    ThermalConduction,
+    PhaseField(PhaseFieldEquation<f32>),
+    Dendrite(AnisotropicPhaseFieldEquation<f32>),
+    GrayScott(GrayScottEquation<f32>),
+    FitzHughNagumo(FitzHughNagumoEquation<f32>),
+}
+
+impl Equation {
+    pub fn from_name(name: &str) -> Option<Equation> {
+        match name {
+            "thermal" => Some(Equation::ThermalConduction),
+            "phasefield" => {
+                Some(Equation::PhaseField(PhaseFieldEquation {
+                    t: 0.9,
+                    gamma: 1.0,
+                    tau: 2.0,
+                    epsilon: 5.0,
+                    tm: 1.0,
+                    la: 0.5,
+                }))
+            }
+            "dendrite" => {
+                Some(Equation::Dendrite(AnisotropicPhaseFieldEquation {
+                    t: 0.5,
+                    gamma: 1.0,
+                    tau: 2.0,
+                    epsilon: 5.0,
+                    tm: 1.0,
+                    la: 0.5,
+                    anisotropy: 0.05,
+                    symmetry: 4,
+                    orientation: 0.0,
+                }))
+            }
+            "gray_scott" => Some(Equation::GrayScott(GrayScottEquation::new())),
+            "fitzhugh_nagumo" => Some(Equation::FitzHughNagumo(FitzHughNagumoEquation::new())),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Equation::ThermalConduction => "thermal",
+            Equation::PhaseField(_) => "phasefield",
+            Equation::Dendrite(_) => "dendrite",
+            Equation::GrayScott(_) => "gray_scott",
+            Equation::FitzHughNagumo(_) => "fitzhugh_nagumo",
+        }
+    }
+
+    /// The name of the field that gets written. The coupled equations write
+    /// all of their fields to the VTK formats.
+    pub fn field_name(&self) -> &'static str {
+        match *self {
+            Equation::ThermalConduction => "temperature",
+            Equation::PhaseField(_) | Equation::Dendrite(_) => "phi",
+            Equation::GrayScott(_) => "v",
+            Equation::FitzHughNagumo(_) => "u",
+        }
+    }
+
+    /// Whether the equation is solved by the coupled solver, which only
+    /// supports the explicit time integrations and neither obstacles nor
+    /// sources.
+    pub fn is_coupled(&self) -> bool {
+        matches!(*self, Equation::GrayScott(_) | Equation::FitzHughNagumo(_))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Boundary {
+    Dirichlet(f32),
+    Neumann,
+    Periodic,
+    Flux {
+        flux: f32,
+        conductivity: f32,
+    },
+    Robin {
+        conductivity: f32,
+        coefficient: f32,
+        ambient: f32,
+    },
+}
+
+impl Boundary {
+    pub fn is_periodic(&self) -> bool {
+        matches!(*self, Boundary::Periodic)
+    }
+
+    pub fn boxed(&self) -> BoxedBoundaryCondition<f32> {
+        match *self {
+            Boundary::Dirichlet(value) => Box::new(Dirichlet(value)),
+            Boundary::Neumann => Box::new(Neumann),
+            Boundary::Periodic => Box::new(Periodic),
+            Boundary::Flux { flux, conductivity } => Box::new(Flux::new(flux, conductivity)),
+            Boundary::Robin { conductivity, coefficient, ambient } => {
+                Box::new(Robin::new(conductivity, coefficient, ambient))
+            }
+        }
+    }
+}
+
+pub struct Boundaries {
+    pub up: Boundary,
+    pub down: Boundary,
+    pub left: Boundary,
+    pub right: Boundary,
+}
+
+pub enum RegionShape {
+    Rectangle(Rectangle),
+    Circle(Circle),
+    Polygon(Polygon),
+    /// A PNG image whose dark pixels belong to the region, with its top left
+    /// pixel at the position.
+    Bitmap(PathBuf, (usize, usize)),
+}
+
+pub struct Region {
+    pub shape: RegionShape,
+    /// The value or the strength, depending on the kind of region
+    pub value: f32,
+}
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Png,
+    Vtk,
+    Vti,
+    Gif,
+    Npy,
+    Csv,
+}
+
+impl Format {
+    const ALL: [Format; 6] = [Format::Png,
+                              Format::Vtk,
+                              Format::Vti,
+                              Format::Gif,
+                              Format::Npy,
+                              Format::Csv];
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        Format::ALL.iter().cloned().find(|format| format.extension() == name)
+    }
+
+    /// Lists the names of all formats, like `"png", "vtk" or "csv"`.
+    pub fn names() -> String {
+        let names = Format::ALL
+                        .iter()
+                        .map(|format| format!("\"{}\"", format.extension()))
+                        .collect::<Vec<_>>();
+        let (last, others) = names.split_last().unwrap();
+        format!("{} or {}", others.join(", "), last)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Format::Png => "png",
+            Format::Vtk => "vtk",
+            Format::Vti => "vti",
+            Format::Gif => "gif",
+            Format::Npy => "npy",
+            Format::Csv => "csv",
+        }
+    }
+}
+
+pub struct Output {
+    pub directory: PathBuf,
+    pub name: String,
+    pub interval: usize,
+    pub format: Format,
+    pub color_map: ColorMap,
+    pub value_range: ValueRange<f32>,
+    pub color_bar: bool,
+    pub checkpoint_interval: usize,
+}
+
+pub struct Scenario {
+    pub equation: Equation,
+    pub dimensions: (usize, usize),
+    pub h: f32,
+    pub delta_t: f32,
+    pub steps: usize,
+    pub time_integration: TimeIntegration<f32>,
+    pub boundaries: Boundaries,
+    pub initial_value: f32,
+    pub initial_file: Option<PathBuf>,
+    pub initial_regions: Vec<Region>,
+    pub obstacles: Vec<Region>,
+    pub sources: Vec<Region>,
+    pub source_terms: Vec<Region>,
+    pub output: Output,
+}
+
+impl Scenario {
+    /// Creates the built-in scenario for the equation. The phase field starts
+    /// with a solid seed in the middle of the field.
+    pub fn new(equation: Equation, dimensions: (usize, usize)) -> Self {
+        let (nx, ny) = dimensions;
+        let (boundaries, initial_regions, (color_map, value_range)) = match equation {
+            Equation::ThermalConduction => {
+                let boundaries = Boundaries {
+                    up: Boundary::Dirichlet(0.0),
+                    down: Boundary::Dirichlet(10.0),
+                    left: Boundary::Dirichlet(5.0),
+                    right: Boundary::Neumann,
+                };
+                (boundaries,
+                 Vec::new(),
+                 (temperature_color_map(), ValueRange::Fixed(0.0, 30.0)))
+            }
+            Equation::PhaseField(_) |
+            Equation::Dendrite(_) => {
+                let boundaries = Boundaries {
+                    up: Boundary::Dirichlet(0.0),
+                    down: Boundary::Dirichlet(0.0),
+                    left: Boundary::Dirichlet(0.0),
+                    right: Boundary::Dirichlet(0.0),
+                };
+                let seed = Region {
+                    shape: RegionShape::Rectangle(Rectangle {
+                        position: ((nx / 2).saturating_sub(5), (ny / 2).saturating_sub(5)),
+                        size: (10.min(nx), 10.min(ny)),
+                    }),
+                    value: 1.0,
+                };
+                (boundaries, vec![seed], (ColorMap::greyscale(), ValueRange::Fixed(0.0, 1.0)))
+            }
+            Equation::GrayScott(_) => {
+                let boundaries = Boundaries {
+                    up: Boundary::Periodic,
+                    down: Boundary::Periodic,
+                    left: Boundary::Periodic,
+                    right: Boundary::Periodic,
+                };
+                (boundaries, Vec::new(), (ColorMap::viridis(), ValueRange::Fixed(0.0, 0.5)))
+            }
+            Equation::FitzHughNagumo(_) => {
+                let boundaries = Boundaries {
+                    up: Boundary::Neumann,
+                    down: Boundary::Neumann,
+                    left: Boundary::Neumann,
+                    right: Boundary::Neumann,
+                };
+                (boundaries, Vec::new(), (ColorMap::diverging(), ValueRange::Fixed(-2.0, 2.0)))
+            }
+        };
+
+        let name = equation.name().to_string();
+
+        Scenario {
+            equation,
+            dimensions,
+            h: 1.0,
+            delta_t: 0.1,
+            steps: 1000,
+            time_integration: TimeIntegration::ForwardEuler,
+            boundaries,
+            initial_value: 0.0,
+            initial_file: None,
+            initial_regions,
+            obstacles: Vec::new(),
+            sources: Vec::new(),
+            source_terms: Vec::new(),
+            output: Output {
+                directory: PathBuf::from("output"),
+                name,
+                interval: 100,
+                format: Format::Png,
+                color_map,
+                value_range,
+                color_bar: false,
+                checkpoint_interval: 0,
+            },
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scenario, String> {
+        let path = path.as_ref();
+        let mut source = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut source))
+            .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+
+        let mut scenario = Scenario::parse(&source)
+                               .map_err(|error| format!("{}: {}", path.display(), error))?;
+        if let Some(directory) = path.parent() {
+            if let Some(file) = scenario.initial_file.take() {
+                scenario.initial_file = Some(directory.join(file));
+            }
+            let regions = scenario.initial_regions
+                                  .iter_mut()
+                                  .chain(&mut scenario.obstacles)
+                                  .chain(&mut scenario.sources)
+                                  .chain(&mut scenario.source_terms);
+            for region in regions {
+                if let RegionShape::Bitmap(ref mut file, _) = region.shape {
+                    *file = directory.join(&*file);
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    pub fn parse(source: &str) -> Result<Scenario, String> {
+        let mut parser = Parser::new(source);
+        let root = match parser.parse() {
+            Some(root) => root,
+            None => {
+                let messages = parser.errors
+                                     .iter()
+                                     .map(|error| {
+                                         let (line, column) = parser.to_linecol(error.lo);
+                                         format!("line {}, column {}: {}",
+                                                 line + 1,
+                                                 column + 1,
+                                                 error.desc)
+                                     })
+                                     .collect::<Vec<_>>();
+                return Err(messages.join("\n"));
+            }
+        };
+        let root = Section {
+            path: String::new(),
+            table: &root,
+        };
+        root.check_keys(&["equation", "grid", "time", "boundaries", "initial", "obstacles",
+                          "sources", "source_terms", "output"])?;
+
+        let equation = parse_equation(&root.section("equation")?
+                                           .ok_or("Missing section [equation]")?)?;
+
+        let grid = root.section("grid")?.ok_or("Missing section [grid]")?;
+        grid.check_keys(&["size", "h"])?;
+        let dimensions = grid.pair("size")?.ok_or("Missing key grid.size")?;
+
+        let mut scenario = Scenario::new(equation, dimensions);
+        scenario.h = grid.positive("h")?.unwrap_or(scenario.h);
+
+        if let Some(time) = root.section("time")? {
+            time.check_keys(&["delta_t", "steps", "integration", "linear_solver"])?;
+            scenario.delta_t = time.positive("delta_t")?.unwrap_or(scenario.delta_t);
+            scenario.steps = time.integer("steps")?.unwrap_or(scenario.steps);
+            scenario.time_integration = parse_time_integration(&time)?;
+        }
+
+        if let Some(boundaries) = root.section("boundaries")? {
+            boundaries.check_keys(&["up", "down", "left", "right"])?;
+            scenario.boundaries = Boundaries {
+                up: parse_boundary(&boundaries, "up")?,
+                down: parse_boundary(&boundaries, "down")?,
+                left: parse_boundary(&boundaries, "left")?,
+                right: parse_boundary(&boundaries, "right")?,
+            };
+        }
+
+        scenario.initial_regions.clear();
+        if let Some(initial) = root.section("initial")? {
+            if scenario.equation.is_coupled() {
+                initial.check_keys(&["regions"])?;
+            } else {
+                initial.check_keys(&["value", "file", "regions"])?;
+            }
+            scenario.initial_value = initial.float("value")?.unwrap_or(scenario.initial_value);
+            if let Some(file) = initial.string("file")? {
+                if !file.ends_with(".npy") && !file.ends_with(".csv") {
+                    return Err(format!("initial.file \"{}\" should be a .npy or .csv file", file));
+                }
+                scenario.initial_file = Some(PathBuf::from(file));
+            }
+            scenario.initial_regions = parse_regions(&initial, "regions", Some("value"))?;
+        }
+        scenario.obstacles = parse_regions(&root, "obstacles", None)?;
+        scenario.sources = parse_regions(&root, "sources", Some("value"))?;
+        scenario.source_terms = parse_regions(&root, "source_terms", Some("strength"))?;
+
+        if let Some(output) = root.section("output")? {
+            output.check_keys(&["directory",
+                                "name",
+                                "every",
+                                "format",
+                                "colormap",
+                                "range",
+                                "color_bar",
+                                "checkpoint_every"])?;
+            if let Some(directory) = output.string("directory")? {
+                scenario.output.directory = PathBuf::from(directory);
+            }
+            if let Some(name) = output.string("name")? {
+                scenario.output.name = name.to_string();
+            }
+            scenario.output.interval = output.integer("every")?
+                                             .unwrap_or(scenario.output.interval);
+            if let Some(format) = output.string("format")? {
+                scenario.output.format = match Format::from_name(format) {
+                    Some(format) => format,
+                    None => {
+                        return Err(format!("Unknown output.format \"{}\", expected {}",
+                                           format,
+                                           Format::names()))
+                    }
+                };
+            }
+            scenario.output.color_map = match output.string("colormap")? {
+                Some("temperature") => temperature_color_map(),
+                Some("greyscale") => ColorMap::greyscale(),
+                Some("viridis") => ColorMap::viridis(),
+                Some("inferno") => ColorMap::inferno(),
+                Some("diverging") => ColorMap::diverging(),
+                Some(other) => {
+                    return Err(format!("Unknown output.colormap \"{}\", expected \"temperature\", \
+                                        \"greyscale\", \"viridis\", \"inferno\" or \"diverging\"",
+                                       other))
+                }
+                None => scenario.output.color_map.clone(),
+            };
+            scenario.output.value_range = match output.table.get("range") {
+                Some(&Value::String(ref range)) if range == "automatic" => ValueRange::Automatic,
+                _ => {
+                    match output.float_pair("range")? {
+                        Some((min, max)) if min < max => ValueRange::Fixed(min, max),
+                        Some(_) => {
+                            return Err("output.range should be an increasing pair".to_string())
+                        }
+                        None => scenario.output.value_range,
+                    }
+                }
+            };
+            scenario.output.color_bar = output.boolean("color_bar")?
+                                              .unwrap_or(scenario.output.color_bar);
+            scenario.output.checkpoint_interval = output.integer("checkpoint_every")?
+                                                        .unwrap_or(scenario.output
+                                                                           .checkpoint_interval);
+        }
+
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks that the grid is large enough and that all regions lie inside
+    /// of it. Obstacles need an additional cell on every side, as their walls
+    /// copy the values of the neighbouring cells.
+    pub fn validate(&self) -> Result<(), String> {
+        let (nx, ny) = self.dimensions;
+        if nx < 3 || ny < 3 {
+            return Err(format!("The grid size {}x{} is too small, it needs to be at least 3x3",
+                               nx,
+                               ny));
+        }
+
+        if self.equation.is_coupled() {
+            let name = self.equation.name();
+            if self.time_integration.implicitness().is_some() {
+                return Err(format!("The {} equation only supports the explicit time \
+                                    integrations",
+                                   name));
+            }
+            if !self.obstacles.is_empty() || !self.sources.is_empty() ||
+               !self.source_terms.is_empty() {
+                return Err(format!("The {} equation doesn't support obstacles and sources",
+                                   name));
+            }
+        }
+
+        let boundaries = &self.boundaries;
+        for &(first, second, axis) in &[(boundaries.left, boundaries.right, "left and right"),
+                                        (boundaries.up, boundaries.down, "up and down")] {
+            if first.is_periodic() != second.is_periodic() {
+                return Err(format!("The {} boundaries need to be periodic together", axis));
+            }
+        }
+
+        let regions = self.initial_regions
+                          .iter()
+                          .map(|region| ("initial.regions", region))
+                          .chain(self.obstacles.iter().map(|region| ("obstacles", region)))
+                          .chain(self.sources.iter().map(|region| ("sources", region)))
+                          .chain(self.source_terms.iter().map(|region| ("source_terms", region)));
+
+        // The other shapes get clipped to the grid
+        for (name, region) in regions {
+            if let RegionShape::Rectangle(ref rectangle) = region.shape {
+                let (x, y) = rectangle.position;
+                let (width, height) = rectangle.size;
+                if width == 0 || height == 0 || x + width > nx || y + height > ny {
+                    return Err(format!("The {} entry at {:?} with size {:?} doesn't fit into \
+                                        the {}x{} grid",
+                                       name,
+                                       rectangle.position,
+                                       rectangle.size,
+                                       nx,
+                                       ny));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_equation(equation: &Section) -> Result<Equation, String> {
+    let name = equation.string("type")?.ok_or("Missing key equation.type")?;
+    match Equation::from_name(name) {
+        Some(Equation::ThermalConduction) => {
+            equation.check_keys(&["type"])?;
+            Ok(Equation::ThermalConduction)
+        }
+        Some(Equation::PhaseField(defaults)) => {
+            equation.check_keys(&["type", "t", "gamma", "tau", "epsilon", "tm", "la"])?;
+            Ok(Equation::PhaseField(PhaseFieldEquation {
+                t: equation.float("t")?.unwrap_or(defaults.t),
+                gamma: equation.positive("gamma")?.unwrap_or(defaults.gamma),
+                tau: equation.positive("tau")?.unwrap_or(defaults.tau),
+                epsilon: equation.positive("epsilon")?.unwrap_or(defaults.epsilon),
+                tm: equation.positive("tm")?.unwrap_or(defaults.tm),
+                la: equation.float("la")?.unwrap_or(defaults.la),
+            }))
+        }
+        Some(Equation::Dendrite(defaults)) => {
+            equation.check_keys(&["type", "t", "gamma", "tau", "epsilon", "tm", "la",
+                                  "anisotropy", "symmetry", "orientation"])?;
+            let symmetry = equation.integer("symmetry")?.unwrap_or(defaults.symmetry);
+            if symmetry == 0 {
+                return Err("equation.symmetry should be positive".to_string());
+            }
+            Ok(Equation::Dendrite(AnisotropicPhaseFieldEquation {
+                t: equation.float("t")?.unwrap_or(defaults.t),
+                gamma: equation.positive("gamma")?.unwrap_or(defaults.gamma),
+                tau: equation.positive("tau")?.unwrap_or(defaults.tau),
+                epsilon: equation.positive("epsilon")?.unwrap_or(defaults.epsilon),
+                tm: equation.positive("tm")?.unwrap_or(defaults.tm),
+                la: equation.float("la")?.unwrap_or(defaults.la),
+                anisotropy: equation.float("anisotropy")?.unwrap_or(defaults.anisotropy),
+                symmetry,
+                orientation: equation.float("orientation")?.unwrap_or(defaults.orientation),
+            }))
+        }
+        Some(Equation::GrayScott(defaults)) => {
+            equation.check_keys(&["type", "du", "dv", "feed", "kill"])?;
+            Ok(Equation::GrayScott(GrayScottEquation {
+                du: equation.positive("du")?.unwrap_or(defaults.du),
+                dv: equation.positive("dv")?.unwrap_or(defaults.dv),
+                feed: equation.positive("feed")?.unwrap_or(defaults.feed),
+                kill: equation.positive("kill")?.unwrap_or(defaults.kill),
+            }))
+        }
+        Some(Equation::FitzHughNagumo(defaults)) => {
+            equation.check_keys(&["type", "a", "b", "epsilon", "du", "dv", "current"])?;
+            let dv = equation.float("dv")?.unwrap_or(defaults.dv);
+            if dv < 0.0 {
+                return Err(format!("equation.dv should not be negative, found {}", dv));
+            }
+            Ok(Equation::FitzHughNagumo(FitzHughNagumoEquation {
+                a: equation.float("a")?.unwrap_or(defaults.a),
+                b: equation.float("b")?.unwrap_or(defaults.b),
+                epsilon: equation.positive("epsilon")?.unwrap_or(defaults.epsilon),
+                du: equation.positive("du")?.unwrap_or(defaults.du),
+                dv,
+                current: equation.float("current")?.unwrap_or(defaults.current),
+            }))
+        }
+        None => {
+            Err(format!("Unknown equation.type \"{}\", expected \"thermal\", \"phasefield\", \
+                         \"dendrite\", \"gray_scott\" or \"fitzhugh_nagumo\"",
+                        name))
+        }
+    }
+}
+
+fn parse_time_integration(time: &Section) -> Result<TimeIntegration<f32>, String> {
+    let linear_solver = match time.string("linear_solver")? {
+        Some("jacobi") => LinearSolver::new(LinearSolverMethod::Jacobi),
+        Some("gauss_seidel") => LinearSolver::new(LinearSolverMethod::GaussSeidel),
+        Some("cg") | None => LinearSolver::new(LinearSolverMethod::ConjugateGradient),
+        Some(other) => {
+            return Err(format!("Unknown time.linear_solver \"{}\", expected \"jacobi\", \
+                                \"gauss_seidel\" or \"cg\"",
+                               other))
+        }
+    };
+
+    match time.string("integration")? {
+        Some("forward_euler") | None => Ok(TimeIntegration::ForwardEuler),
+        Some("heun") => Ok(TimeIntegration::Heun),
+        Some("midpoint") => Ok(TimeIntegration::Midpoint),
+        Some("rk4") => Ok(TimeIntegration::RungeKutta4),
+        Some("backward_euler") => Ok(TimeIntegration::BackwardEuler(linear_solver)),
+        Some("crank_nicolson") => Ok(TimeIntegration::CrankNicolson(linear_solver)),
+        Some(other) => {
+            Err(format!("Unknown time.integration \"{}\", expected \"forward_euler\", \"heun\", \
+                         \"midpoint\", \"rk4\", \"backward_euler\" or \"crank_nicolson\"",
+                        other))
+        }
+    }
+}
+
+fn parse_boundary(boundaries: &Section, side: &str) -> Result<Boundary, String> {
+    let path = boundaries.key_path(side);
+    let boundary = match boundaries.table.get(side) {
+        None => return Ok(Boundary::Neumann),
+        Some(&Value::String(ref kind)) if kind == "neumann" => return Ok(Boundary::Neumann),
+        Some(&Value::String(ref kind)) if kind == "periodic" => return Ok(Boundary::Periodic),
+        Some(&Value::Table(ref table)) => {
+            Section {
+                path: path.clone(),
+                table,
+            }
+        }
+        Some(_) => {
+            return Err(format!("{} should be \"neumann\", \"periodic\" or a table like \
+                                {{ type = \"dirichlet\", value = 1.0 }}",
+                               path))
+        }
+    };
+
+    match boundary.string("type")? {
+        Some("dirichlet") => {
+            boundary.check_keys(&["type", "value"])?;
+            let value = boundary.float("value")?
+                                .ok_or_else(|| format!("Missing key {}.value", path))?;
+            Ok(Boundary::Dirichlet(value))
+        }
+        Some("neumann") => {
+            boundary.check_keys(&["type"])?;
+            Ok(Boundary::Neumann)
+        }
+        Some("periodic") => {
+            boundary.check_keys(&["type"])?;
+            Ok(Boundary::Periodic)
+        }
+        Some("flux") => {
+            boundary.check_keys(&["type", "flux", "conductivity"])?;
+            let flux = boundary.float("flux")?
+                               .ok_or_else(|| format!("Missing key {}.flux", path))?;
+            Ok(Boundary::Flux {
+                flux,
+                conductivity: boundary.positive("conductivity")?.unwrap_or(1.0),
+            })
+        }
+        Some("robin") => {
+            boundary.check_keys(&["type", "conductivity", "coefficient", "ambient"])?;
+            let coefficient = boundary.float("coefficient")?
+                                      .ok_or_else(|| format!("Missing key {}.coefficient", path))?;
+            if coefficient < 0.0 {
+                return Err(format!("{}.coefficient should not be negative, found {}",
+                                   path,
+                                   coefficient));
+            }
+            let ambient = boundary.float("ambient")?
+                                  .ok_or_else(|| format!("Missing key {}.ambient", path))?;
+            Ok(Boundary::Robin {
+                conductivity: boundary.positive("conductivity")?.unwrap_or(1.0),
+                coefficient,
+                ambient,
+            })
+        }
+        Some(other) => {
+            Err(format!("Unknown {}.type \"{}\", expected \"dirichlet\", \"neumann\", \
+                         \"periodic\", \"flux\" or \"robin\"",
+                        path,
+                        other))
+        }
+        None => Err(format!("Missing key {}.type", path)),
+    }
+}
+
+/// Parses an array of regions, which have a value under `value_key` if it is
+/// given.
+fn parse_regions(parent: &Section,
+                 key: &str,
+                 value_key: Option<&'static str>)
+                 -> Result<Vec<Region>, String> {
+    let path = parent.key_path(key);
+    let entries = match parent.table.get(key) {
+        None => return Ok(Vec::new()),
+        Some(&Value::Array(ref entries)) => entries,
+        Some(value) => {
+            return Err(format!("{} should be an array of tables, found {}",
+                               path,
+                               value.type_str()))
+        }
+    };
+
+    let mut regions = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let path = format!("{}[{}]", path, index);
+        let region = match *entry {
+            Value::Table(ref table) => {
+                Section {
+                    path: path.clone(),
+                    table,
+                }
+            }
+            _ => return Err(format!("{} should be a table, found {}", path, entry.type_str())),
+        };
+
+        let missing = |key| format!("Missing key {}.{}", path, key);
+        let (shape, mut keys) = match region.string("shape")?.unwrap_or("rectangle") {
+            "rectangle" => {
+                let rectangle = Rectangle {
+                    position: region.pair("position")?.ok_or_else(|| missing("position"))?,
+                    size: region.pair("size")?.ok_or_else(|| missing("size"))?,
+                };
+                (RegionShape::Rectangle(rectangle), vec!["position", "size"])
+            }
+            "circle" => {
+                let circle = Circle {
+                    center: region.point("center")?.ok_or_else(|| missing("center"))?,
+                    radius: region.positive("radius")?.ok_or_else(|| missing("radius"))? as f64,
+                };
+                (RegionShape::Circle(circle), vec!["center", "radius"])
+            }
+            "polygon" => {
+                let vertices = region.points("vertices")?.ok_or_else(|| missing("vertices"))?;
+                if vertices.len() < 3 {
+                    return Err(format!("{}.vertices should contain at least 3 points", path));
+                }
+                (RegionShape::Polygon(Polygon { vertices }), vec!["vertices"])
+            }
+            "bitmap" => {
+                let file = region.string("file")?.ok_or_else(|| missing("file"))?;
+                let position = region.pair("position")?.ok_or_else(|| missing("position"))?;
+                (RegionShape::Bitmap(PathBuf::from(file), position), vec!["file", "position"])
+            }
+            shape => {
+                return Err(format!("Unknown shape \"{}\" in {}.shape, expected rectangle, \
+                                    circle, polygon or bitmap",
+                                   shape,
+                                   path))
+            }
+        };
+
+        keys.push("shape");
+        let value = match value_key {
+            Some(value_key) => {
+                keys.push(value_key);
+                region.float(value_key)?.ok_or_else(|| missing(value_key))?
+            }
+            None => 0.0,
+        };
+        region.check_keys(&keys)?;
+
+        regions.push(Region {
+            shape,
+            value,
+        });
+    }
+
+    Ok(regions)
+}
+
+/// A table of the scenario file along with its path, which is used to point
+/// to the offending key in error messages.
+struct Section<'a> {
+    path: String,
+    table: &'a BTreeMap<String, Value>,
+}
+
+impl<'a> Section<'a> {
+    fn key_path(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), String> {
+        for key in self.table.keys() {
+            if !allowed.contains(&&key[..]) {
+                return Err(format!("Unknown key {}, expected one of: {}",
+                                   self.key_path(key),
+                                   allowed.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    fn section(&self, key: &str) -> Result<Option<Section<'a>>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Table(ref table)) => {
+                Ok(Some(Section {
+                    path: self.key_path(key),
+                    table,
+                }))
+            }
+            Some(value) => {
+                Err(format!("{} should be a table, found {}",
+                            self.key_path(key),
+                            value.type_str()))
+            }
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<Option<&'a str>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::String(ref value)) => Ok(Some(value)),
+            Some(value) => {
+                Err(format!("{} should be a string, found {}",
+                            self.key_path(key),
+                            value.type_str()))
+            }
+        }
+    }
+
+    fn float(&self, key: &str) -> Result<Option<f32>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Float(value)) => Ok(Some(value as f32)),
+            Some(&Value::Integer(value)) => Ok(Some(value as f32)),
+            Some(value) => {
+                Err(format!("{} should be a number, found {}",
+                            self.key_path(key),
+                            value.type_str()))
+            }
+        }
+    }
+
+    fn boolean(&self, key: &str) -> Result<Option<bool>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Boolean(value)) => Ok(Some(value)),
+            Some(value) => {
+                Err(format!("{} should be true or false, found {}",
+                            self.key_path(key),
+                            value.type_str()))
+            }
+        }
+    }
+
+    fn positive(&self, key: &str) -> Result<Option<f32>, String> {
+        match self.float(key)? {
+            Some(value) if value <= 0.0 => {
+                Err(format!("{} should be positive, found {}", self.key_path(key), value))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn integer(&self, key: &str) -> Result<Option<usize>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Integer(value)) if value >= 0 => Ok(Some(value as usize)),
+            Some(value) => {
+                Err(format!("{} should be a non-negative integer, found {}",
+                            self.key_path(key),
+                            value))
+            }
+        }
+    }
+
+    fn float_pair(&self, key: &str) -> Result<Option<(f32, f32)>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Array(ref values)) if values.len() == 2 => {
+                let number = |value: &Value| {
+                    match *value {
+                        Value::Float(value) => Some(value as f32),
+                        Value::Integer(value) => Some(value as f32),
+                        _ => None,
+                    }
+                };
+                match (number(&values[0]), number(&values[1])) {
+                    (Some(first), Some(second)) => Ok(Some((first, second))),
+                    _ => Err(format!("{} should contain two numbers", self.key_path(key))),
+                }
+            }
+            Some(value) => {
+                Err(format!("{} should be \"automatic\" or an array like [min, max], found {}",
+                            self.key_path(key),
+                            value))
+            }
+        }
+    }
+
+    fn point(&self, key: &str) -> Result<Option<(f64, f64)>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(value) => {
+                point(value).map(Some).ok_or_else(|| {
+                    format!("{} should be an array like [x, y], found {}",
+                            self.key_path(key),
+                            value)
+                })
+            }
+        }
+    }
+
+    fn points(&self, key: &str) -> Result<Option<Vec<(f64, f64)>>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Array(ref values)) => {
+                values.iter()
+                      .map(point)
+                      .collect::<Option<Vec<_>>>()
+                      .map(Some)
+                      .ok_or_else(|| {
+                          format!("{} should only contain arrays like [x, y]",
+                                  self.key_path(key))
+                      })
+            }
+            Some(value) => {
+                Err(format!("{} should be an array like [[x, y], ...], found {}",
+                            self.key_path(key),
+                            value))
+            }
+        }
+    }
+
+    fn pair(&self, key: &str) -> Result<Option<(usize, usize)>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(&Value::Array(ref values)) if values.len() == 2 => {
+                match (&values[0], &values[1]) {
+                    (&Value::Integer(x), &Value::Integer(y)) if x >= 0 && y >= 0 => {
+                        Ok(Some((x as usize, y as usize)))
+                    }
+                    _ => {
+                        Err(format!("{} should contain two non-negative integers",
+                                    self.key_path(key)))
+                    }
+                }
+            }
+            Some(value) => {
+                Err(format!("{} should be an array like [x, y], found {}",
+                            self.key_path(key),
+                            value))
+            }
+        }
+    }
+}
+
+fn point(value: &Value) -> Option<(f64, f64)> {
+    let number = |value: &Value| {
+        match *value {
+            Value::Float(value) => Some(value),
+            Value::Integer(value) => Some(value as f64),
+            _ => None,
+        }
+    };
+    match *value {
+        Value::Array(ref values) if values.len() == 2 => {
+            match (number(&values[0]), number(&values[1])) {
+                (Some(x), Some(y)) => Some((x, y)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solver::colormap::ValueRange;
+    use super::Scenario;
+
+    const MINIMAL: &'static str = "[equation]\ntype = \"thermal\"\n[grid]\nsize = [20, 20]\n";
+
+    fn parse_error(source: &str) -> String {
+        match Scenario::parse(source) {
+            Ok(_) => panic!("The scenario got accepted:\n{}", source),
+            Err(message) => message,
+        }
+    }
+
+    #[test]
+    fn parses_the_scenario_files() {
+        let sources = [include_str!("../scenarios/phasefield.toml"),
+                       include_str!("../scenarios/thermal_conduction.toml"),
+                       include_str!("../scenarios/gray_scott.toml")];
+        for source in &sources {
+            if let Err(message) = Scenario::parse(source) {
+                panic!("{}", message);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let message = parse_error(&format!("{}[outptu]\nevery = 10\n", MINIMAL));
+        assert!(message.starts_with("Unknown key outptu"), "{}", message);
+
+        let message = parse_error(&format!("{}[time]\ndelta = 0.1\n", MINIMAL));
+        assert!(message.starts_with("Unknown key time.delta"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_rectangles_outside_of_the_grid() {
+        let message = parse_error(&format!("{}[[sources]]\nposition = [18, 5]\nsize = [4, 4]\n\
+                                            value = 1.0\n",
+                                           MINIMAL));
+        assert!(message.contains("doesn't fit"), "{}", message);
+
+        let message = parse_error(&format!("{}[[obstacles]]\nposition = [5, 17]\nsize = [4, 4]\n",
+                                           MINIMAL));
+        assert!(message.contains("doesn't fit"), "{}", message);
+
+        let message = parse_error(&format!("{}[[obstacles]]\nposition = [5, 5]\nsize = [0, 4]\n",
+                                           MINIMAL));
+        assert!(message.contains("doesn't fit"), "{}", message);
+
+        let source = format!("{}[[obstacles]]\nposition = [0, 0]\nsize = [20, 20]\n", MINIMAL);
+        assert!(Scenario::parse(&source).is_ok());
+    }
+
+    #[test]
+    fn lists_all_formats_for_unknown_ones() {
+        let message = parse_error(&format!("{}[output]\nformat = \"jpg\"\n", MINIMAL));
+        assert_eq!(message,
+                   "Unknown output.format \"jpg\", expected \"png\", \"vtk\", \"vti\", \"gif\", \
+                    \"npy\" or \"csv\"");
+    }
+
+    #[test]
+    fn parses_the_value_range() {
+        let source = format!("{}[output]\nrange = \"automatic\"\n", MINIMAL);
+        let scenario = Scenario::parse(&source).unwrap_or_else(|message| panic!("{}", message));
+        assert!(match scenario.output.value_range {
+            ValueRange::Automatic => true,
+            _ => false,
+        });
+
+        let source = format!("{}[output]\nrange = [-1.0, 2.5]\n", MINIMAL);
+        let scenario = Scenario::parse(&source).unwrap_or_else(|message| panic!("{}", message));
+        assert!(match scenario.output.value_range {
+            ValueRange::Fixed(min, max) => min == -1.0 && max == 2.5,
+            _ => false,
+        });
+
+        parse_error(&format!("{}[output]\nrange = [1.0, 1.0]\n", MINIMAL));
+        parse_error(&format!("{}[output]\nrange = \"auto\"\n", MINIMAL));
+    }
+
+    #[test]
+    fn rejects_one_sided_periodic_boundaries() {
+        let message = parse_error(&format!("{}[boundaries]\nleft = \"periodic\"\n", MINIMAL));
+        assert_eq!(message, "The left and right boundaries need to be periodic together");
+
+        let source = format!("{}[boundaries]\nup = \"periodic\"\ndown = \"periodic\"\n",
+                             MINIMAL);
+        assert!(Scenario::parse(&source).is_ok());
+    }
+
+    #[test]
+    fn rejects_what_the_coupled_solver_does_not_support() {
+        let coupled = "[equation]\ntype = \"gray_scott\"\n[grid]\nsize = [20, 20]\n";
+        let message = parse_error(&format!("{}[[obstacles]]\nposition = [5, 5]\nsize = [4, 4]\n",
+                                           coupled));
+        assert_eq!(message, "The gray_scott equation doesn't support obstacles and sources");
+
+        let message = parse_error(&format!("{}[time]\nintegration = \"crank_nicolson\"\n",
+                                           coupled));
+        assert!(message.contains("only supports the explicit"), "{}", message);
+
+        let message = parse_error(&format!("{}[initial]\nvalue = 0.5\n", coupled));
+        assert!(message.starts_with("Unknown key initial.value"), "{}", message);
+    }
+}
@@ -1,23 +1,30 @@
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use solver::checkpoint;
 use solver::prelude::*;
 
-pub struct SwitchableBoundaryCondition<F, S>
-    where F: BoundaryCondition,
-          S: BoundaryCondition
+pub struct SwitchableBoundaryCondition<T, F, S>
+    where T: Float,
+          F: BoundaryCondition<T>,
+          S: BoundaryCondition<T>
 {
     first: F,
     second: S,
     use_second: bool,
+    _scalar: PhantomData<T>,
 }
 
-impl<F, S> SwitchableBoundaryCondition<F, S>
-    where F: BoundaryCondition,
-          S: BoundaryCondition
+impl<T, F, S> SwitchableBoundaryCondition<T, F, S>
+    where T: Float,
+          F: BoundaryCondition<T>,
+          S: BoundaryCondition<T>
 {
     pub fn new(first: F, second: S) -> Self {
         SwitchableBoundaryCondition {
-            first: first,
-            second: second,
+            first,
+            second,
             use_second: false,
+            _scalar: PhantomData,
         }
     }
 
@@ -26,15 +33,40 @@ impl<F, S> SwitchableBoundaryCondition<F, S>
     }
 }
 
-impl<F, S> BoundaryCondition for SwitchableBoundaryCondition<F, S>
-    where F: BoundaryCondition,
-          S: BoundaryCondition
+impl<T, F, S> BoundaryCondition<T> for SwitchableBoundaryCondition<T, F, S>
+    where T: Float,
+          F: BoundaryCondition<T>,
+          S: BoundaryCondition<T>
 {
-    fn calculate_boundary(&self, other: f32) -> f32 {
+    fn calculate_boundary(&self, other: T, point: BoundaryPoint<T>) -> T {
         if self.use_second {
-            self.second.calculate_boundary(other)
+            self.second.calculate_boundary(other, point)
         } else {
-            self.first.calculate_boundary(other)
+            self.first.calculate_boundary(other, point)
         }
     }
+
+    fn is_periodic(&self) -> bool {
+        if self.use_second {
+            self.second.is_periodic()
+        } else {
+            self.first.is_periodic()
+        }
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Switchable")?;
+        writer.write_all(&[self.use_second as u8])?;
+        self.first.save_state(writer)?;
+        self.second.save_state(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Switchable")?;
+        let mut use_second = [0];
+        reader.read_exact(&mut use_second)?;
+        self.use_second = use_second[0] != 0;
+        self.first.load_state(reader)?;
+        self.second.load_state(reader)
+    }
 }
@@ -2,21 +2,24 @@ use solver::prelude::*;
 use solver::differential_equation::ThermalConduction;
 use solver::precondition::ConstantPreCondition;
 use solver::boundary_condition::{Neumann, Dirichlet};
+use solver::geometry::{CellType, Mask, Rectangle};
+use solver::source::RegionSource;
 use switchable_boundary_condition::SwitchableBoundaryCondition;
 
 use piston::input::*;
 use opengl_graphics::{GlGraphics, OpenGL, Texture};
 use graphics::Transformed;
 
-use utils::{draw_cube, draw_neumann_rectangle, to_temperature_image};
+use utils::{draw_cube, to_temperature_image};
 use super::graphics;
 
 pub struct ThermalConductionApp {
     gl: GlGraphics,
-    solver: Solver<ThermalConduction,
-                   SwitchableBoundaryCondition<Dirichlet, Neumann>,
-                   SwitchableBoundaryCondition<Dirichlet, Neumann>,
-                   SwitchableBoundaryCondition<Dirichlet, Neumann>,
+    solver: Solver<f32,
+                   ThermalConduction,
+                   SwitchableBoundaryCondition<f32, Dirichlet<f32>, Neumann>,
+                   SwitchableBoundaryCondition<f32, Dirichlet<f32>, Neumann>,
+                   SwitchableBoundaryCondition<f32, Dirichlet<f32>, Neumann>,
                    Neumann>,
     mouse_coord: (f64, f64),
     mouse_down: bool,
@@ -24,6 +27,11 @@ pub struct ThermalConductionApp {
     pipe_open: bool,
 }
 
+const PIPE: Rectangle = Rectangle {
+    position: (160, 60),
+    size: (10, 10),
+};
+
 impl ThermalConductionApp {
     pub fn new(opengl: OpenGL, dimensions: (usize, usize)) -> Self {
         let ref precondition = ConstantPreCondition(0.0);
@@ -39,9 +47,33 @@ impl ThermalConductionApp {
 
         solver.execute_precondition(precondition);
 
+        let mut mask = Mask::new(dimensions);
+        let walls = [((100, 100), (50, 10)),
+                     ((100, 120), (70, 10)),
+                     ((90, 100), (10, 30)),
+                     ((170, 60), (10, 70)),
+                     ((150, 60), (10, 50))];
+        for &(position, size) in &walls {
+            let wall = Rectangle {
+                position,
+                size,
+            };
+            mask.fill(&wall, CellType::Insulated);
+        }
+        mask.fill(&PIPE, CellType::Insulated);
+        solver.mask = Some(mask);
+
+        solver.add_source(RegionSource {
+            shape: Rectangle {
+                position: (100, 110),
+                size: (10, 10),
+            },
+            strength: 100.0,
+        });
+
         ThermalConductionApp {
             gl: GlGraphics::new(opengl),
-            solver: solver,
+            solver,
             mouse_coord: (0.0, 0.0),
             mouse_down: false,
             window_scale: 5.0,
@@ -85,16 +117,6 @@ impl ThermalConductionApp {
         };
         draw_cube(self.solver.get_field_mut(), cube_coord, cube_temperature);
 
-        draw_cube(self.solver.get_field_mut(), (105, 115), 1000.0);
-        draw_neumann_rectangle(self.solver.get_field_mut(), (100, 100), (50, 10));
-        draw_neumann_rectangle(self.solver.get_field_mut(), (100, 120), (70, 10));
-        draw_neumann_rectangle(self.solver.get_field_mut(), (90, 100), (10, 30));
-        draw_neumann_rectangle(self.solver.get_field_mut(), (170, 60), (10, 70));
-        draw_neumann_rectangle(self.solver.get_field_mut(), (150, 60), (10, 50));
-        if !self.pipe_open {
-            draw_neumann_rectangle(self.solver.get_field_mut(), (160, 60), (10, 10));
-        }
-
         self.solver.solve_next_frame();
     }
 
@@ -111,6 +133,14 @@ impl ThermalConductionApp {
             }
             Key::Space => {
                 self.pipe_open = !self.pipe_open;
+                let cell_type = if self.pipe_open {
+                    CellType::Fluid
+                } else {
+                    CellType::Insulated
+                };
+                if let Some(ref mut mask) = self.solver.mask {
+                    mask.fill(&PIPE, cell_type);
+                }
             }
             _ => {}
         }
@@ -1,11 +1,9 @@
-use solver::prelude::*;
-use solver::boundary_condition::Neumann;
 use solver::DataField;
+use solver::colormap::{ColorMap, FieldRenderer, RgbImage, ValueRange};
 
 use image::RgbaImage;
-use palette::{Gradient, Hsv, Rgb, RgbHue};
 
-pub fn draw_cube(field: &mut DataField, (x, y): (isize, isize), temperature: f32) {
+pub fn draw_cube(field: &mut DataField<f32>, (x, y): (isize, isize), temperature: f32) {
     for y in y - 5..y + 5 {
         for x in x - 5..x + 5 {
             if y >= 0 && x >= 0 {
@@ -16,67 +14,31 @@ pub fn draw_cube(field: &mut DataField, (x, y): (isize, isize), temperature: f32
     }
 }
 
-pub fn draw_neumann_rectangle(field: &mut DataField,
-                          (x, y): (usize, usize),
-                          (width, height): (usize, usize)) {
-    let neumann = Neumann;
-
-    let (x_end, y_end) = (x + width - 1, y + height - 1);
-
-    for y in y..y + height {
-        // Left
-        let other = field[(x - 1, y)];
-        let value = neumann.calculate_boundary(other);
-        field.set((x, y), value);
-
-        // Right
-        let other = field[(x_end + 1, y)];
-        let value = neumann.calculate_boundary(other);
-        field.set((x_end, y), value);
-    }
-
-    for x in x..x + width {
-        // Up
-        let other = field[(x, y - 1)];
-        let value = neumann.calculate_boundary(other);
-        field.set((x, y), value);
-
-        // Down
-        let other = field[(x, y_end + 1)];
-        let value = neumann.calculate_boundary(other);
-        field.set((x, y_end), value);
-    }
+/// The blue to red gradient of the thermal conduction app.
+pub fn temperature_color_map() -> ColorMap {
+    ColorMap::new(&[[0, 56, 204], [143, 204, 172], [255, 219, 0], [255, 146, 0], [255, 0, 0]])
 }
 
-pub fn to_temperature_image(field: &DataField) -> RgbaImage {
-    let (nx, ny) = field.dimensions;
-    let mut image = RgbaImage::new(nx as u32, ny as u32);
-
-    let red: Rgb<_> = Hsv::new(RgbHue::from_radians(0.0), 1.0, 1.0).into();
-    let orange: Rgb<_> = Hsv::new(RgbHue::from_radians(0.6), 1.0, 1.0).into();
-    let yellow: Rgb<_> = Hsv::new(RgbHue::from_radians(0.9), 1.0, 1.0).into();
-    let greenish: Rgb<_> = Hsv::new(RgbHue::from_radians(2.6), 0.3, 0.8).into();
-    let blue: Rgb<_> = Hsv::new(RgbHue::from_radians(3.9), 1.0, 0.8).into();
-
-    let gradient = Gradient::new(vec![blue, greenish, yellow, orange, red]);
-
-    for (x, y, pixel) in image.enumerate_pixels_mut() {
-        let value = field[(x as usize, y as usize)] / 30.0;
-        pixel.data = gradient.get(value).to_pixel();
-    }
+pub fn to_temperature_image(field: &DataField<f32>) -> RgbaImage {
+    let mut renderer = FieldRenderer::new(temperature_color_map());
+    renderer.value_range = ValueRange::Fixed(0.0, 30.0);
+    to_rgba_image(&renderer.render(field))
+}
 
-    image
+pub fn to_greyscale_image(field: &DataField<f32>) -> RgbaImage {
+    let mut renderer = FieldRenderer::new(ColorMap::greyscale());
+    renderer.value_range = ValueRange::Fixed(0.0, 1.0);
+    to_rgba_image(&renderer.render(field))
 }
 
-pub fn to_greyscale_image(field: &DataField) -> RgbaImage {
-    let (nx, ny) = field.dimensions;
-    let mut image = RgbaImage::new(nx as u32, ny as u32);
+fn to_rgba_image(image: &RgbImage) -> RgbaImage {
+    let mut rgba_image = RgbaImage::new(image.width as u32, image.height as u32);
 
-    for (x, y, pixel) in image.enumerate_pixels_mut() {
-        let value = field[(x as usize, y as usize)];
-        let brightness = (255.0 * value) as u8;
-        pixel.data = [brightness, brightness, brightness, 0xFF];
+    for (x, y, pixel) in rgba_image.enumerate_pixels_mut() {
+        let index = 3 * (x as usize + image.width * y as usize);
+        let rgb = &image.pixels[index..index + 3];
+        pixel.data = [rgb[0], rgb[1], rgb[2], 0xFF];
     }
 
-    image
-}
\ No newline at end of file
+    rgba_image
+}
@@ -11,8 +11,8 @@ use solver::boundary_condition::Dirichlet;
 
 #[bench]
 fn bench_solving(b: &mut Bencher) {
-    let ref precondition = ConstantPreCondition(0.0);
-    let equation = PhaseFieldEquation {
+    let precondition = &ConstantPreCondition(0.0);
+    let equation = PhaseFieldEquation::<f32> {
         t: 0.9,
         gamma: 1.0,
         tau: 2.0,
@@ -32,3 +32,29 @@ fn bench_solving(b: &mut Bencher) {
 
     b.iter(|| solver.solve_next_frame());
 }
+
+#[bench]
+fn bench_solving_3d(b: &mut Bencher) {
+    let precondition = &ConstantPreCondition(0.0);
+    let equation = PhaseFieldEquation::<f32> {
+        t: 0.9,
+        gamma: 1.0,
+        tau: 2.0,
+        epsilon: 5.0,
+        tm: 1.0,
+        la: 0.5,
+    };
+
+    let boundary_conditions = BoundaryConditions3D::new(Dirichlet(0.0),
+                                                        Dirichlet(0.0),
+                                                        Dirichlet(0.0),
+                                                        Dirichlet(0.0),
+                                                        Dirichlet(0.0),
+                                                        Dirichlet(0.0));
+
+    let mut solver = Solver3D::new(equation, boundary_conditions, (300, 300, 300), 0.1, 1.0);
+
+    solver.execute_precondition(precondition);
+
+    b.iter(|| solver.solve_next_frame());
+}
@@ -0,0 +1,44 @@
+use std::io::{self, Read, Write};
+use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
+use boundary_condition::History;
+
+/// Mur's first order absorbing boundary for waves travelling with the given
+/// speed. Waves that hit the boundary head-on leave the field without getting
+/// reflected, while waves at an angle get reflected partially. It needs the
+/// previous time level of a second order equation and falls back to a zero
+/// gradient without it.
+pub struct Absorbing<T>(pub T) where T: Float;
+
+impl<T> BoundaryCondition<T> for Absorbing<T>
+    where T: Float
+{
+    fn calculate_boundary(&self, other: T, _: BoundaryPoint<T>) -> T {
+        other
+    }
+
+    fn calculate_boundary_with_history(&self,
+                                       other: T,
+                                       history: History<T>,
+                                       point: BoundaryPoint<T>)
+                                       -> T {
+        // Discretizes the one-way wave equation du/dt + speed du/dn = 0 for
+        // waves leaving along the outward normal n
+        let travelled = self.0 * history.delta_t;
+        let distance = point.distance();
+        history.other + (travelled - distance) / (travelled + distance) * (other - history.ghost)
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Absorbing")?;
+        self.0.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Absorbing")?;
+        self.0 = T::read_from(reader)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use std::marker::PhantomData;
+use BoundaryCondition;
+use BoundaryPoint;
+use boundary_condition::{is_periodic_pair, Side};
+use DataField3D;
+use Float;
+
+pub struct BoundaryConditions3D<T, BU, BD, BL, BR, BF, BB>
+    where T: Float,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>,
+          BF: BoundaryCondition<T>,
+          BB: BoundaryCondition<T>
+{
+    pub up: BU,
+    pub down: BD,
+    pub left: BL,
+    pub right: BR,
+    pub front: BF,
+    pub back: BB,
+    _scalar: PhantomData<T>,
+}
+
+impl<T, BU, BD, BL, BR, BF, BB> BoundaryConditions3D<T, BU, BD, BL, BR, BF, BB>
+    where T: Float,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>,
+          BF: BoundaryCondition<T>,
+          BB: BoundaryCondition<T>
+{
+    /// Panics if only one side of an axis is periodic.
+    pub fn new(up: BU, down: BD, left: BL, right: BR, front: BF, back: BB) -> Self {
+        is_periodic_pair(&up, &down);
+        is_periodic_pair(&left, &right);
+        is_periodic_pair(&front, &back);
+        BoundaryConditions3D {
+            up,
+            down,
+            left,
+            right,
+            front,
+            back,
+            _scalar: PhantomData,
+        }
+    }
+
+    /// Fills the ghost cells of the volume with the boundary values at the
+    /// given simulation time. Every side only covers the cells between the
+    /// ghost cells of the other axes. The edges and then the corners are
+    /// filled afterwards. Along periodic axes they are copied from the
+    /// opposite side, otherwise they are the mean of the adjacent ghost cells
+    /// that are further inside.
+    pub fn calculate_boundaries(&self, field: &mut DataField3D<T>, time: T, h: T) {
+        let (nx, ny, nz) = field.dimensions;
+        let (end_x, end_y, end_z) = (nx - 1, ny - 1, nz - 1);
+        let (end_other_x, end_other_y, end_other_z) = (nx - 2, ny - 2, nz - 2);
+        let periodic = [is_periodic_pair(&self.left, &self.right),
+                        is_periodic_pair(&self.up, &self.down),
+                        is_periodic_pair(&self.front, &self.back)];
+        let point = |coord, side| {
+            BoundaryPoint {
+                coord,
+                side,
+                time,
+                h,
+                layer: 0,
+            }
+        };
+
+        for z in 1..end_z {
+            for y in 1..end_y {
+                if periodic[0] {
+                    field[(0, y, z)] = field[(end_other_x, y, z)];
+                    field[(end_x, y, z)] = field[(1, y, z)];
+                } else {
+                    // Left
+                    let other = field[(1, y, z)];
+                    let coord = (0, y, z);
+                    field[coord] = self.left.calculate_boundary(other, point(coord, Side::Left));
+
+                    // Right
+                    let other = field[(end_other_x, y, z)];
+                    let coord = (end_x, y, z);
+                    field[coord] = self.right.calculate_boundary(other, point(coord, Side::Right));
+                }
+            }
+        }
+
+        for z in 1..end_z {
+            for x in 1..end_x {
+                if periodic[1] {
+                    field[(x, 0, z)] = field[(x, end_other_y, z)];
+                    field[(x, end_y, z)] = field[(x, 1, z)];
+                } else {
+                    // Up
+                    let other = field[(x, 1, z)];
+                    let coord = (x, 0, z);
+                    field[coord] = self.up.calculate_boundary(other, point(coord, Side::Up));
+
+                    // Down
+                    let other = field[(x, end_other_y, z)];
+                    let coord = (x, end_y, z);
+                    field[coord] = self.down.calculate_boundary(other, point(coord, Side::Down));
+                }
+            }
+        }
+
+        for y in 1..end_y {
+            for x in 1..end_x {
+                if periodic[2] {
+                    field[(x, y, 0)] = field[(x, y, end_other_z)];
+                    field[(x, y, end_z)] = field[(x, y, 1)];
+                } else {
+                    // Front
+                    let other = field[(x, y, 1)];
+                    let coord = (x, y, 0);
+                    field[coord] = self.front.calculate_boundary(other, point(coord, Side::Front));
+
+                    // Back
+                    let other = field[(x, y, end_other_z)];
+                    let coord = (x, y, end_z);
+                    field[coord] = self.back.calculate_boundary(other, point(coord, Side::Back));
+                }
+            }
+        }
+
+        // Edges are outside of two sides and corners outside of three, so
+        // the edges need to be known before the corners
+        let dimensions = [nx, ny, nz];
+        let is_ghost = |coord: [usize; 3], axis: usize| {
+            coord[axis] == 0 || coord[axis] == dimensions[axis] - 1
+        };
+        for &ghost_axes in &[2, 3] {
+            for z in 0..nz {
+                for y in 0..ny {
+                    // Rows between the sides only have ghost cells at both ends
+                    let step = if is_ghost([0, y, z], 1) || is_ghost([0, y, z], 2) {
+                        1
+                    } else {
+                        end_x
+                    };
+                    for x in (0..nx).step_by(step) {
+                        let coord = [x, y, z];
+                        if (0..3).filter(|&axis| is_ghost(coord, axis)).count() != ghost_axes {
+                            continue;
+                        }
+
+                        let mut source = coord;
+                        for axis in 0..3 {
+                            if periodic[axis] && is_ghost(coord, axis) {
+                                source[axis] = if coord[axis] == 0 {
+                                    dimensions[axis] - 2
+                                } else {
+                                    1
+                                };
+                            }
+                        }
+
+                        let mut outside = [0; 3];
+                        let mut outside_count = 0;
+                        for axis in 0..3 {
+                            if is_ghost(source, axis) {
+                                outside[outside_count] = axis;
+                                outside_count += 1;
+                            }
+                        }
+                        let value = if outside_count < 2 {
+                            field[(source[0], source[1], source[2])]
+                        } else {
+                            let mut sum = T::zero();
+                            for &axis in &outside[..outside_count] {
+                                let mut neighbour = source;
+                                neighbour[axis] = if source[axis] == 0 {
+                                    1
+                                } else {
+                                    dimensions[axis] - 2
+                                };
+                                sum = sum + field[(neighbour[0], neighbour[1], neighbour[2])];
+                            }
+                            sum / T::from_f64(outside_count as f64)
+                        };
+                        field[(x, y, z)] = value;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,27 @@
+use std::io::{self, Read, Write};
 use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
 
-pub struct Dirichlet(pub f32);
+pub struct Dirichlet<T>(pub T) where T: Float;
 
-impl BoundaryCondition for Dirichlet {
-    fn calculate_boundary(&self, _: f32) -> f32 {
+impl<T> BoundaryCondition<T> for Dirichlet<T>
+    where T: Float
+{
+    fn calculate_boundary(&self, _: T, _: BoundaryPoint<T>) -> T {
         let &Dirichlet(value) = self;
         value
     }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Dirichlet")?;
+        self.0.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Dirichlet")?;
+        self.0 = T::read_from(reader)?;
+        Ok(())
+    }
 }
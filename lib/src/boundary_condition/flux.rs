@@ -0,0 +1,47 @@
+use std::io::{self, Read, Write};
+use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
+
+/// Prescribes the flux `q = -k dT/dn` leaving the field through the
+/// boundary, where `n` is the outward normal. A negative flux flows into the
+/// field and a flux of zero is the same as `Neumann`.
+pub struct Flux<T>
+    where T: Float
+{
+    pub flux: T,
+    pub conductivity: T,
+}
+
+impl<T> Flux<T>
+    where T: Float
+{
+    pub fn new(flux: T, conductivity: T) -> Self {
+        Flux {
+            flux,
+            conductivity,
+        }
+    }
+}
+
+impl<T> BoundaryCondition<T> for Flux<T>
+    where T: Float
+{
+    fn calculate_boundary(&self, other: T, point: BoundaryPoint<T>) -> T {
+        other - self.flux * point.distance() / self.conductivity
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Flux")?;
+        self.flux.write_to(writer)?;
+        self.conductivity.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Flux")?;
+        self.flux = T::read_from(reader)?;
+        self.conductivity = T::read_from(reader)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{self, Read, Write};
+use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
+use velocity::Velocity;
+
+/// An open boundary of an advection, through which the flow enters or leaves
+/// the field depending on the velocity at the boundary. Where it flows in,
+/// the ghost cells hold the `inflow` value, which gets carried into the
+/// field. Where it flows out, the gradient is zero, so that the values leave
+/// the field without getting reflected.
+pub struct InflowOutflow<T, V>
+    where T: Float,
+          V: Velocity<T>
+{
+    pub inflow: T,
+    pub velocity: V,
+}
+
+impl<T, V> BoundaryCondition<T> for InflowOutflow<T, V>
+    where T: Float,
+          V: Velocity<T>
+{
+    fn calculate_boundary(&self, other: T, point: BoundaryPoint<T>) -> T {
+        // The velocity is taken at the inner cell next to the boundary
+        let (x, y, _) = point.coord;
+        let (normal_x, normal_y, _) = point.side.normal();
+        let depth = point.layer as isize + 1;
+        let inner = ((x as isize - normal_x * depth) as usize,
+                     (y as isize - normal_y * depth) as usize);
+        let (u, v) = self.velocity.at(inner);
+        let outward = u * T::from_f64(normal_x as f64) + v * T::from_f64(normal_y as f64);
+
+        if outward < T::zero() {
+            self.inflow
+        } else {
+            other
+        }
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "InflowOutflow")?;
+        self.inflow.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "InflowOutflow")?;
+        self.inflow = T::read_from(reader)?;
+        Ok(())
+    }
+}
@@ -1,65 +1,498 @@
+mod absorbing;
+mod boundary_conditions_3d;
 mod dirichlet;
+mod flux;
+mod inflow_outflow;
 mod neumann;
+mod periodic;
+mod profile;
+mod robin;
 
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
 use DataField;
+use Float;
 
+pub use self::absorbing::Absorbing;
+pub use self::boundary_conditions_3d::BoundaryConditions3D;
 pub use self::dirichlet::Dirichlet;
+pub use self::flux::Flux;
+pub use self::inflow_outflow::InflowOutflow;
 pub use self::neumann::Neumann;
+pub use self::periodic::Periodic;
+pub use self::profile::Profile;
+pub use self::robin::Robin;
 
-pub trait BoundaryCondition {
-    fn calculate_boundary(&self, other: f32) -> f32;
+/// The side of the field a boundary belongs to. `Up` is the side at `y = 0`
+/// and `Front` the side at `z = 0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Up,
+    Down,
+    Left,
+    Right,
+    Front,
+    Back,
 }
 
-pub struct BoundaryConditions<BU, BD, BL, BR>
-    where BU: BoundaryCondition,
-          BD: BoundaryCondition,
-          BL: BoundaryCondition,
-          BR: BoundaryCondition
+impl Side {
+    /// Returns the unit normal pointing out of the field.
+    pub fn normal(self) -> (isize, isize, isize) {
+        match self {
+            Side::Up => (0, -1, 0),
+            Side::Down => (0, 1, 0),
+            Side::Left => (-1, 0, 0),
+            Side::Right => (1, 0, 0),
+            Side::Front => (0, 0, -1),
+            Side::Back => (0, 0, 1),
+        }
+    }
+}
+
+/// The ghost cell a boundary condition gets evaluated for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundaryPoint<T>
+    where T: Float
+{
+    /// The coordinate of the ghost cell. The `z` coordinate is always zero
+    /// for 2D fields.
+    pub coord: (usize, usize, usize),
+    pub side: Side,
+    /// The simulation time the boundary values are calculated for.
+    pub time: T,
+    /// The grid spacing.
+    pub h: T,
+    /// The ghost layer of the cell, counted outwards from 0 for the layer
+    /// next to the inner cells.
+    pub layer: usize,
+}
+
+impl<T> BoundaryPoint<T>
+    where T: Float
+{
+    /// The distance between the ghost cell and the inner cell it mirrors,
+    /// whose value the boundary condition gets as `other`.
+    pub fn distance(&self) -> T {
+        self.h * T::from_f64((2 * self.layer + 1) as f64)
+    }
+}
+
+/// The values of a ghost cell and of the cell mirroring it at the previous
+/// time level, which the `Solver` keeps for equations that are second order
+/// in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct History<T>
+    where T: Float
+{
+    pub ghost: T,
+    pub other: T,
+    /// The time step from the previous to the current time level.
+    pub delta_t: T,
+}
+
+pub trait BoundaryCondition<T>
+    where T: Float
+{
+    /// Returns the value of the ghost cell at `point`, where `other` is the
+    /// value of the cell inside the field that mirrors it at the boundary.
+    /// For the first ghost layer, that's the adjacent cell.
+    fn calculate_boundary(&self, other: T, point: BoundaryPoint<T>) -> T;
+
+    /// Like `calculate_boundary`, but with the values of the previous time
+    /// level, which boundary conditions that depend on the rate of change
+    /// need. It's only called when the solver keeps that level.
+    fn calculate_boundary_with_history(&self,
+                                       other: T,
+                                       _history: History<T>,
+                                       point: BoundaryPoint<T>)
+                                       -> T {
+        self.calculate_boundary(other, point)
+    }
+
+    /// Periodic boundary conditions don't calculate their ghost cells, but
+    /// copy them from the opposite side of the field, which needs to be
+    /// periodic as well.
+    fn is_periodic(&self) -> bool {
+        false
+    }
+
+    /// Writes the parameters of the boundary condition into a checkpoint.
+    /// The built-in boundary conditions start with an identifier written by
+    /// `checkpoint::write_id`, so that their state can't be loaded into a
+    /// different boundary condition.
+    fn save_state(&self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Restores the parameters written by `save_state`.
+    fn load_state(&mut self, _reader: &mut dyn Read) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T, B> BoundaryCondition<T> for Box<B>
+    where T: Float,
+          B: BoundaryCondition<T> + ?Sized
+{
+    fn calculate_boundary(&self, other: T, point: BoundaryPoint<T>) -> T {
+        (**self).calculate_boundary(other, point)
+    }
+
+    fn calculate_boundary_with_history(&self,
+                                       other: T,
+                                       history: History<T>,
+                                       point: BoundaryPoint<T>)
+                                       -> T {
+        (**self).calculate_boundary_with_history(other, history, point)
+    }
+
+    fn is_periodic(&self) -> bool {
+        (**self).is_periodic()
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        (**self).save_state(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        (**self).load_state(reader)
+    }
+}
+
+pub type BoxedBoundaryCondition<T> = Box<dyn BoundaryCondition<T> + Sync + Send>;
+
+pub type BoxedBoundaryConditions<T> = BoundaryConditions<T,
+                                                         BoxedBoundaryCondition<T>,
+                                                         BoxedBoundaryCondition<T>,
+                                                         BoxedBoundaryCondition<T>,
+                                                         BoxedBoundaryCondition<T>>;
+
+pub struct BoundaryConditions<T, BU, BD, BL, BR>
+    where T: Float,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>
 {
     pub up: BU,
     pub down: BD,
     pub left: BL,
     pub right: BR,
+    _scalar: PhantomData<T>,
 }
 
-impl<BU, BD, BL, BR> BoundaryConditions<BU, BD, BL, BR>
-    where BU: BoundaryCondition,
-          BD: BoundaryCondition,
-          BL: BoundaryCondition,
-          BR: BoundaryCondition
+impl<T, BU, BD, BL, BR> BoundaryConditions<T, BU, BD, BL, BR>
+    where T: Float,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>
 {
+    /// Panics if only one side of an axis is periodic.
     pub fn new(up: BU, down: BD, left: BL, right: BR) -> Self {
+        is_periodic_pair(&up, &down);
+        is_periodic_pair(&left, &right);
         BoundaryConditions {
-            up: up,
-            down: down,
-            left: left,
-            right: right,
+            up,
+            down,
+            left,
+            right,
+            _scalar: PhantomData,
         }
     }
 
-    pub fn calculate_boundaries(&self, field: &mut DataField) {
+    pub fn boxed(self) -> BoxedBoundaryConditions<T>
+        where BU: Sync + Send + 'static,
+              BD: Sync + Send + 'static,
+              BL: Sync + Send + 'static,
+              BR: Sync + Send + 'static
+    {
+        BoundaryConditions::new(Box::new(self.up),
+                                Box::new(self.down),
+                                Box::new(self.left),
+                                Box::new(self.right))
+    }
+
+    /// Fills the ghost layers of the field with the boundary values at the
+    /// given simulation time. Every side covers the rows or columns between
+    /// the ghost layers of the other axis. Each ghost cell is calculated
+    /// from the inner cell it mirrors at the boundary, so that a Neumann
+    /// condition stays symmetric for stencils reaching further than one
+    /// cell. The corners are filled last. Along a periodic axis
+    /// they are copied from the opposite side, otherwise they are the mean of
+    /// the adjacent ghost cells of both sides.
+    pub fn calculate_boundaries(&self, field: &mut DataField<T>, time: T, h: T) {
+        self.calculate(field, None, time, h);
+    }
+
+    /// Like `calculate_boundaries`, but passes the values of `previous`, the
+    /// field at the previous time level `delta_t` earlier, to the boundary
+    /// conditions.
+    pub fn calculate_boundaries_with_history(&self,
+                                             field: &mut DataField<T>,
+                                             previous: &DataField<T>,
+                                             delta_t: T,
+                                             time: T,
+                                             h: T) {
+        self.calculate(field, Some((previous, delta_t)), time, h);
+    }
+
+    fn calculate(&self,
+                 field: &mut DataField<T>,
+                 history: Option<(&DataField<T>, T)>,
+                 time: T,
+                 h: T) {
         let (nx, ny) = field.dimensions;
-        let (end_x, end_y) = (nx - 1, ny - 1);
-        let (end_other_x, end_other_y) = (nx - 2, ny - 2);
+        let layers = field.ghost_layers();
+        let (period_x, period_y) = (nx - 2 * layers, ny - 2 * layers);
+        let periodic_x = is_periodic_pair(&self.left, &self.right);
+        let periodic_y = is_periodic_pair(&self.up, &self.down);
+        assert!((!periodic_x || period_x >= layers) && (!periodic_y || period_y >= layers),
+                "A periodic axis needs at least as many cells as there are ghost layers");
+        let point = |(x, y), side, layer| {
+            BoundaryPoint {
+                coord: (x, y, 0),
+                side,
+                time,
+                h,
+                layer,
+            }
+        };
+
+        for y in layers..ny - layers {
+            for layer in 0..layers {
+                let (left, right) = (layers - 1 - layer, nx - layers + layer);
+                if periodic_x {
+                    field[(left, y)] = field[(left + period_x, y)];
+                    field[(right, y)] = field[(right - period_x, y)];
+                } else {
+                    // Left
+                    let left_point = point((left, y), Side::Left, layer);
+                    field[(left, y)] = boundary_value(&self.left,
+                                                      field,
+                                                      history,
+                                                      (layers + layer, y),
+                                                      left_point);
+
+                    // Right
+                    let right_point = point((right, y), Side::Right, layer);
+                    field[(right, y)] = boundary_value(&self.right,
+                                                       field,
+                                                       history,
+                                                       (nx - 1 - layers - layer, y),
+                                                       right_point);
+                }
+            }
+        }
+
+        for x in layers..nx - layers {
+            for layer in 0..layers {
+                let (up, down) = (layers - 1 - layer, ny - layers + layer);
+                if periodic_y {
+                    field[(x, up)] = field[(x, up + period_y)];
+                    field[(x, down)] = field[(x, down - period_y)];
+                } else {
+                    // Up
+                    let up_point = point((x, up), Side::Up, layer);
+                    field[(x, up)] = boundary_value(&self.up,
+                                                    field,
+                                                    history,
+                                                    (x, layers + layer),
+                                                    up_point);
+
+                    // Down
+                    let down_point = point((x, down), Side::Down, layer);
+                    field[(x, down)] = boundary_value(&self.down,
+                                                      field,
+                                                      history,
+                                                      (x, ny - 1 - layers - layer),
+                                                      down_point);
+                }
+            }
+        }
 
-        for y in 0..ny {
-            // Left
-            let other = field[(1, y)];
-            field[(0, y)] = self.left.calculate_boundary(other);
+        // Corners
+        let inner = |c, n| if c < layers { layers } else { n - 1 - layers };
+        let wrap = |c, period| if c < layers { c + period } else { c - period };
+        for y in (0..layers).chain(ny - layers..ny) {
+            for x in (0..layers).chain(nx - layers..nx) {
+                field[(x, y)] = if periodic_x {
+                    field[(wrap(x, period_x), y)]
+                } else if periodic_y {
+                    field[(x, wrap(y, period_y))]
+                } else {
+                    (field[(x, inner(y, ny))] + field[(inner(x, nx), y)]) / T::from_f64(2.0)
+                };
+            }
+        }
+    }
+}
 
-            // Right
-            let other = field[(end_other_x, y)];
-            field[(end_x, y)] = self.right.calculate_boundary(other);
+/// Evaluates the boundary condition for the ghost cell at `point`, which
+/// mirrors the inner cell at `other`.
+fn boundary_value<T, B>(condition: &B,
+                        field: &DataField<T>,
+                        history: Option<(&DataField<T>, T)>,
+                        other: (usize, usize),
+                        point: BoundaryPoint<T>)
+                        -> T
+    where T: Float,
+          B: BoundaryCondition<T>
+{
+    match history {
+        Some((previous, delta_t)) => {
+            let (x, y, _) = point.coord;
+            let history = History {
+                ghost: previous[(x, y)],
+                other: previous[other],
+                delta_t,
+            };
+            condition.calculate_boundary_with_history(field[other], history, point)
         }
+        None => condition.calculate_boundary(field[other], point),
+    }
+}
+
+/// Returns whether both boundary conditions of an axis are periodic.
+fn is_periodic_pair<T, A, B>(first: &A, second: &B) -> bool
+    where T: Float,
+          A: BoundaryCondition<T>,
+          B: BoundaryCondition<T>
+{
+    let periodic = first.is_periodic();
+    assert!(periodic == second.is_periodic(),
+            "Periodic boundary conditions need a periodic boundary condition on the opposite \
+             side");
+    periodic
+}
 
-        for x in 0..nx {
-            // Up
-            let other = field[(x, 1)];
-            field[(x, 0)] = self.up.calculate_boundary(other);
+#[cfg(test)]
+mod tests {
+    use DataField;
+    use super::*;
 
-            // Down
-            let other = field[(x, end_other_y)];
-            field[(x, end_y)] = self.down.calculate_boundary(other);
+    /// A field with asymmetric inner values, where `transposed` swaps the
+    /// axes of both the dimensions and the values.
+    fn field((nx, ny): (usize, usize), layers: usize, transposed: bool) -> DataField<f64> {
+        let dimensions = if transposed { (ny, nx) } else { (nx, ny) };
+        let mut field = DataField::with_ghost_layers(dimensions, layers);
+        for ((x, y), cell) in field.iter_inner_mut() {
+            let (x, y) = if transposed { (y, x) } else { (x, y) };
+            *cell = (1.3 * x as f64 + 0.7 * y as f64).sin() + 0.1 * (x * y) as f64;
         }
+        field
+    }
+
+    /// Checks that swapping the axes of the field and the boundary conditions
+    /// swaps the axes of all the ghost cells, including the corners, so that
+    /// the corners don't depend on which axis gets processed first.
+    fn assert_transposes(original: BoxedBoundaryConditions<f64>,
+                         transposed: BoxedBoundaryConditions<f64>) {
+        for &layers in &[1, 2] {
+            let mut field_original = field((9, 7), layers, false);
+            let mut field_transposed = field((9, 7), layers, true);
+            original.calculate_boundaries(&mut field_original, 0.0, 1.0);
+            transposed.calculate_boundaries(&mut field_transposed, 0.0, 1.0);
+
+            for y in 0..7 {
+                for x in 0..9 {
+                    assert_eq!(field_original[(x, y)],
+                               field_transposed[(y, x)],
+                               "Cell {:?} with {} ghost layers",
+                               (x, y),
+                               layers);
+                }
+            }
+
+            let mut recalculated = field((9, 7), layers, false);
+            recalculated.copy_from(&field_original);
+            original.calculate_boundaries(&mut recalculated, 0.0, 1.0);
+            for y in 0..7 {
+                for x in 0..9 {
+                    assert_eq!(recalculated[(x, y)], field_original[(x, y)]);
+                }
+            }
+        }
+    }
+
+    fn encode(point: BoundaryPoint<f64>) -> f64 {
+        let (x, y, z) = point.coord;
+        x as f64 + 10.0 * y as f64 + 100.0 * z as f64 + 1000.0 * point.side as usize as f64 +
+        1e4 * point.time + 1e5 * point.h
+    }
+
+    #[test]
+    fn passes_the_ghost_cell_and_the_time() {
+        let boundary_conditions = BoundaryConditions::new(Profile(encode),
+                                                          Profile(encode),
+                                                          Profile(encode),
+                                                          Profile(encode));
+        let mut field = DataField::new((5, 4));
+        boundary_conditions.calculate_boundaries(&mut field, 0.5, 2.0);
+
+        for y in 0..4 {
+            for x in 0..5 {
+                let side = match (x, y) {
+                    (0, 0) | (0, 3) | (4, 0) | (4, 3) => continue,
+                    (_, 0) => Side::Up,
+                    (_, 3) => Side::Down,
+                    (0, _) => Side::Left,
+                    (4, _) => Side::Right,
+                    _ => {
+                        assert_eq!(field[(x, y)], 0.0);
+                        continue;
+                    }
+                };
+                let point = BoundaryPoint {
+                    coord: (x, y, 0),
+                    time: 0.5,
+                    h: 2.0,
+                    side,
+                    layer: 0,
+                };
+                assert_eq!(field[(x, y)], encode(point));
+            }
+        }
+    }
+
+    #[test]
+    fn corners_do_not_depend_on_the_order_of_the_axes() {
+        assert_transposes(BoundaryConditions::new(Dirichlet(1.0),
+                                                  Dirichlet(2.0),
+                                                  Dirichlet(3.0),
+                                                  Neumann)
+                              .boxed(),
+                          BoundaryConditions::new(Dirichlet(3.0),
+                                                  Neumann,
+                                                  Dirichlet(1.0),
+                                                  Dirichlet(2.0))
+                              .boxed());
+    }
+
+    #[test]
+    fn periodic_corners_do_not_depend_on_the_order_of_the_axes() {
+        assert_transposes(BoundaryConditions::new(Dirichlet(1.0), Neumann, Periodic, Periodic)
+                              .boxed(),
+                          BoundaryConditions::new(Periodic, Periodic, Dirichlet(1.0), Neumann)
+                              .boxed());
+        assert_transposes(BoundaryConditions::new(Periodic, Periodic, Periodic, Periodic).boxed(),
+                          BoundaryConditions::new(Periodic, Periodic, Periodic, Periodic).boxed());
+    }
+
+    #[test]
+    #[should_panic(expected = "opposite side")]
+    fn one_sided_periodic_fails_at_construction() {
+        BoundaryConditions::<f64, _, _, _, _>::new(Periodic, Neumann, Neumann, Neumann);
+    }
+
+    #[test]
+    #[should_panic(expected = "opposite side")]
+    fn one_sided_periodic_fails_at_construction_in_3d() {
+        BoundaryConditions3D::<f64, _, _, _, _, _, _>::new(Neumann,
+                                                           Neumann,
+                                                           Neumann,
+                                                           Neumann,
+                                                           Neumann,
+                                                           Periodic);
     }
 }
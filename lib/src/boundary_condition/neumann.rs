@@ -1,9 +1,23 @@
+use std::io::{self, Read, Write};
 use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
 
 pub struct Neumann;
 
-impl BoundaryCondition for Neumann {
-    fn calculate_boundary(&self, other: f32) -> f32 {
+impl<T> BoundaryCondition<T> for Neumann
+    where T: Float
+{
+    fn calculate_boundary(&self, other: T, _: BoundaryPoint<T>) -> T {
         other
     }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Neumann")
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Neumann")
+    }
 }
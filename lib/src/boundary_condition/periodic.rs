@@ -0,0 +1,31 @@
+use std::io::{self, Read, Write};
+use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
+
+/// Wraps the field around, so that the ghost cells on this side are copied
+/// from the cells next to the opposite side. The opposite side needs to be
+/// `Periodic` as well.
+pub struct Periodic;
+
+impl<T> BoundaryCondition<T> for Periodic
+    where T: Float
+{
+    fn calculate_boundary(&self, other: T, _: BoundaryPoint<T>) -> T {
+        // The boundary conditions copy the opposite side instead of calling this
+        other
+    }
+
+    fn is_periodic(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Periodic")
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Periodic")
+    }
+}
@@ -0,0 +1,28 @@
+use std::io::{self, Read, Write};
+use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
+
+/// A Dirichlet boundary condition whose value depends on the position along
+/// the boundary and on the simulation time, like a heated strip on part of a
+/// wall or an oscillating wall temperature. The function isn't part of a
+/// checkpoint.
+pub struct Profile<F>(pub F);
+
+impl<T, F> BoundaryCondition<T> for Profile<F>
+    where T: Float,
+          F: Fn(BoundaryPoint<T>) -> T
+{
+    fn calculate_boundary(&self, _: T, point: BoundaryPoint<T>) -> T {
+        (self.0)(point)
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Profile")
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Profile")
+    }
+}
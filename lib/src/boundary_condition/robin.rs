@@ -0,0 +1,56 @@
+use std::io::{self, Read, Write};
+use BoundaryCondition;
+use BoundaryPoint;
+use checkpoint;
+use Float;
+
+/// Convective heat transfer to the surroundings,
+/// `-k dT/dn = alpha (T - T_inf)`, where `n` is the outward normal. A heat
+/// transfer coefficient of zero insulates the boundary, while a very large
+/// one approaches a Dirichlet condition with the ambient value.
+pub struct Robin<T>
+    where T: Float
+{
+    pub conductivity: T,
+    pub heat_transfer_coefficient: T,
+    pub ambient: T,
+}
+
+impl<T> Robin<T>
+    where T: Float
+{
+    pub fn new(conductivity: T, heat_transfer_coefficient: T, ambient: T) -> Self {
+        Robin {
+            conductivity,
+            heat_transfer_coefficient,
+            ambient,
+        }
+    }
+}
+
+impl<T> BoundaryCondition<T> for Robin<T>
+    where T: Float
+{
+    fn calculate_boundary(&self, other: T, point: BoundaryPoint<T>) -> T {
+        // The ghost cell holds the value on the boundary, so
+        // -k (T - other) / distance = alpha (T - T_inf)
+        let k = self.conductivity;
+        let alpha_distance = self.heat_transfer_coefficient * point.distance();
+        (k * other + alpha_distance * self.ambient) / (k + alpha_distance)
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Robin")?;
+        self.conductivity.write_to(writer)?;
+        self.heat_transfer_coefficient.write_to(writer)?;
+        self.ambient.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Robin")?;
+        self.conductivity = T::read_from(reader)?;
+        self.heat_transfer_coefficient = T::read_from(reader)?;
+        self.ambient = T::read_from(reader)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,379 @@
+//! Binary checkpoints of the solver state. All values are stored in little
+//! endian byte order, starting with a header that identifies the file, the
+//! format version, the size of the scalar type and the kind of solver that
+//! wrote it.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use DataField;
+use Float;
+
+const MAGIC: &[u8; 4] = b"RSCP";
+const VERSION: u32 = 2;
+
+/// Writes the header, where `kind` identifies the solver, so that a
+/// checkpoint can't be loaded into a different kind of solver.
+pub fn write_header<T>(writer: &mut dyn Write, kind: &str) -> io::Result<()>
+    where T: Float
+{
+    writer.write_all(MAGIC)?;
+    write_u32(writer, VERSION)?;
+    write_u32(writer, mem::size_of::<T>() as u32)?;
+    write_block(writer, kind.as_bytes())
+}
+
+pub fn read_header<T>(reader: &mut dyn Read, kind: &str) -> io::Result<()>
+    where T: Float
+{
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a checkpoint file".to_string()));
+    }
+
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(invalid_data(format!("Unsupported checkpoint version {}, expected {}",
+                                        version,
+                                        VERSION)));
+    }
+
+    let scalar_size = read_u32(reader)? as usize;
+    if scalar_size != mem::size_of::<T>() {
+        return Err(invalid_data(format!("The checkpoint stores {} byte scalars, expected {}",
+                                        scalar_size,
+                                        mem::size_of::<T>())));
+    }
+
+    let written_kind = read_block(reader)?;
+    if written_kind != kind.as_bytes() {
+        return Err(invalid_data(format!("The checkpoint was written by a {}, expected a {}",
+                                        String::from_utf8_lossy(&written_kind),
+                                        kind)));
+    }
+
+    Ok(())
+}
+
+/// The values that follow the header in the checkpoints of all solvers: the
+/// index of the current fields, the step count, the time, the step sizes, the
+/// grid spacing and the states of the equation and the boundary conditions.
+pub struct SolverState<T>
+    where T: Float
+{
+    pub current: usize,
+    pub steps: usize,
+    pub time: T,
+    pub trial_delta_t: T,
+    pub delta_t: T,
+    pub h: T,
+    pub states: Vec<Vec<u8>>,
+}
+
+impl<T> SolverState<T>
+    where T: Float
+{
+    /// Writes the header for the `kind` of solver followed by the values.
+    pub fn write_to(&self, writer: &mut dyn Write, kind: &str) -> io::Result<()> {
+        write_header::<T>(writer, kind)?;
+        write_u64(writer, self.current as u64)?;
+        write_u64(writer, self.steps as u64)?;
+        for &value in &[self.time, self.trial_delta_t, self.delta_t, self.h] {
+            value.write_to(writer)?;
+        }
+
+        // The states are written as blocks of known length, so that they can
+        // be checked before any of them gets restored
+        write_u64(writer, self.states.len() as u64)?;
+        for state in &self.states {
+            write_block(writer, state)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the values written by `write_to`, which need to contain
+    /// `state_count` states.
+    pub fn read_from(reader: &mut dyn Read, kind: &str, state_count: usize) -> io::Result<Self> {
+        read_header::<T>(reader, kind)?;
+        let current = read_u64(reader)? as usize;
+        if current > 1 {
+            return Err(invalid_data(format!("Invalid current fields {}", current)));
+        }
+        let steps = read_u64(reader)? as usize;
+        let time = T::read_from(reader)?;
+        let trial_delta_t = T::read_from(reader)?;
+        let delta_t = T::read_from(reader)?;
+        let h = T::read_from(reader)?;
+
+        let written_count = read_u64(reader)?;
+        if written_count != state_count as u64 {
+            return Err(invalid_data(format!("The checkpoint has {} states, expected {}",
+                                            written_count,
+                                            state_count)));
+        }
+        let mut states = Vec::with_capacity(state_count);
+        for _ in 0..state_count {
+            states.push(read_block(reader)?);
+        }
+
+        Ok(SolverState {
+            current,
+            steps,
+            time,
+            trial_delta_t,
+            delta_t,
+            h,
+            states,
+        })
+    }
+}
+
+/// Loads `states` with `load`, which has to read every one of them
+/// completely. If that fails, the `backup` gets loaded instead, so that a
+/// failed checkpoint doesn't leave a mix of old and new states behind.
+pub fn restore_states<F>(states: &[Vec<u8>], backup: &[Vec<u8>], mut load: F) -> io::Result<()>
+    where F: FnMut(&mut [&[u8]]) -> io::Result<()>
+{
+    let mut load_completely = |states: &[Vec<u8>]| {
+        let mut readers: Vec<&[u8]> = states.iter().map(|state| &state[..]).collect();
+        load(&mut readers)?;
+        if readers.iter().any(|reader| !reader.is_empty()) {
+            return Err(invalid_data("The states of the checkpoint don't match the solver"
+                                        .to_string()));
+        }
+        Ok(())
+    };
+
+    if let Err(error) = load_completely(states) {
+        load_completely(backup).expect("Restoring the previous states failed");
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Writes the identifier that starts the state of an equation or a boundary
+/// condition. It has to stay the same across versions, as `read_id` checks
+/// it when the state gets loaded again.
+pub fn write_id(writer: &mut dyn Write, id: &str) -> io::Result<()> {
+    write_block(writer, id.as_bytes())
+}
+
+/// Reads the identifier written by `write_id` and checks that it is `id`.
+pub fn read_id(reader: &mut dyn Read, id: &str) -> io::Result<()> {
+    let written_id = match read_block(reader) {
+        Ok(written_id) => written_id,
+        Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => Vec::new(),
+        Err(error) => return Err(error),
+    };
+    if written_id != id.as_bytes() {
+        return Err(invalid_data(format!("The checkpoint contains the state of a {:?}, \
+                                         expected a {}",
+                                        String::from_utf8_lossy(&written_id),
+                                        id)));
+    }
+    Ok(())
+}
+
+/// Writes the bytes prefixed with their length.
+pub fn write_block(writer: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+/// Reads the bytes written by `write_block`. They are read incrementally, so
+/// that a corrupt length can't allocate more memory than there is data.
+pub fn read_block(reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let length = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < length {
+        return Err(truncated());
+    }
+    Ok(bytes)
+}
+
+/// Reads a field of a checkpoint that needs to match the dimensions and ghost
+/// layers of `template`.
+pub fn read_matching_field<T>(reader: &mut dyn Read,
+                              template: &DataField<T>)
+                              -> io::Result<DataField<T>>
+    where T: Float
+{
+    let mut field = DataField::read_from(reader)?;
+    if field.dimensions != template.dimensions {
+        return Err(invalid_data(format!("The checkpoint has fields of {:?} cells, expected {:?}",
+                                        field.dimensions,
+                                        template.dimensions)));
+    }
+    field.set_ghost_layers(template.ghost_layers());
+    Ok(field)
+}
+
+pub fn write_u32(writer: &mut dyn Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn write_u64(writer: &mut dyn Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u64(reader: &mut dyn Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn truncated() -> io::Error {
+    invalid_data("The checkpoint is truncated".to_string())
+}
+
+/// Turns reading past the end of the data into an `InvalidData` error.
+pub fn map_truncated(error: io::Error) -> io::Error {
+    if error.kind() == io::ErrorKind::UnexpectedEof {
+        truncated()
+    } else {
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use boundary_condition::{Dirichlet, Neumann, Robin};
+    use differential_equation::ThermalConduction;
+    use BoundaryConditions;
+    use Solver;
+    use TimeIntegration;
+    use TimeStepControl;
+
+    type TestSolver = Solver<f64,
+                             ThermalConduction,
+                             Dirichlet<f64>,
+                             Neumann,
+                             Robin<f64>,
+                             Neumann>;
+
+    fn solver(dimensions: (usize, usize)) -> TestSolver {
+        let boundary_conditions = BoundaryConditions::new(Dirichlet(1.0),
+                                                          Neumann,
+                                                          Robin::new(1.0, 0.5, 2.0),
+                                                          Neumann);
+        let mut solver = Solver::with_time_integration(ThermalConduction,
+                                                       boundary_conditions,
+                                                       dimensions,
+                                                       0.5,
+                                                       1.0,
+                                                       TimeIntegration::BogackiShampine);
+        solver.time_step_control = TimeStepControl::ErrorControl {
+            absolute_tolerance: 1e-4,
+            relative_tolerance: 1e-4,
+        };
+        solver
+    }
+
+    fn started_solver() -> TestSolver {
+        let mut solver = solver((12, 10));
+        for ((x, y), cell) in solver.get_field_mut().iter_mut() {
+            *cell = (x as f64 * 0.9).sin() * (y as f64 * 0.5).cos();
+        }
+        solver.run_steps(15);
+        solver
+    }
+
+    fn checkpoint(solver: &TestSolver) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        solver.save_checkpoint(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn load(solver: &mut TestSolver, mut bytes: &[u8]) -> io::Result<()> {
+        solver.load_checkpoint(&mut bytes)
+    }
+
+    fn assert_rejected(solver: &mut TestSolver, bytes: &[u8]) {
+        let before = checkpoint(solver);
+        let error = load(solver, bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(before == checkpoint(solver), "A failed load changed the solver");
+    }
+
+    #[test]
+    fn resumes_bit_identically() {
+        let mut uninterrupted = started_solver();
+        let bytes = checkpoint(&uninterrupted);
+
+        let mut resumed = solver((12, 10));
+        load(&mut resumed, &bytes).unwrap();
+        assert!(bytes == checkpoint(&resumed));
+
+        uninterrupted.run_steps(20);
+        resumed.run_steps(20);
+        assert_eq!(resumed.steps(), 35);
+        assert_eq!(resumed.time().to_bits(), uninterrupted.time().to_bits());
+        for y in 0..10 {
+            for x in 0..12 {
+                assert_eq!(resumed[(x, y)].to_bits(), uninterrupted[(x, y)].to_bits());
+            }
+        }
+        assert!(checkpoint(&resumed) == checkpoint(&uninterrupted));
+    }
+
+    #[test]
+    fn rejects_truncated_checkpoints() {
+        let bytes = checkpoint(&started_solver());
+        let mut solver = solver((12, 10));
+        for length in (0..bytes.len()).step_by(7).chain(Some(bytes.len() - 1)) {
+            assert_rejected(&mut solver, &bytes[..length]);
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_checkpoints() {
+        let bytes = checkpoint(&started_solver());
+        let mut solver = solver((12, 10));
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert_rejected(&mut solver, &magic);
+
+        let mut version = bytes.clone();
+        version[4] = 99;
+        assert_rejected(&mut solver, &version);
+
+        // Huge dimensions of the first field must not be allocated
+        let mut dimensions = Vec::new();
+        dimensions.extend_from_slice(&12u64.to_le_bytes());
+        dimensions.extend_from_slice(&10u64.to_le_bytes());
+        let position = bytes.windows(16).position(|window| window == &dimensions[..]).unwrap();
+        for &(nx, ny) in &[(1u64 << 31, 1u64 << 31), (u64::MAX, 2), (12, 11)] {
+            let mut huge = bytes.clone();
+            huge[position..position + 8].copy_from_slice(&nx.to_le_bytes());
+            huge[position + 8..position + 16].copy_from_slice(&ny.to_le_bytes());
+            assert_rejected(&mut solver, &huge);
+        }
+    }
+
+    #[test]
+    fn rejects_checkpoints_of_other_solvers() {
+        // Different dimensions
+        let bytes = checkpoint(&started_solver());
+        assert_rejected(&mut solver((13, 10)), &bytes);
+
+        // Different boundary conditions
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut other = Solver::new(ThermalConduction, boundary_conditions, (12, 10), 0.5, 1.0);
+        let mut reader = &bytes[..];
+        let error = other.load_checkpoint(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,415 @@
+//! Colour mapping of fields into images, which can be written as PNG
+//! snapshots, animated GIFs or numbered PNG frame sequences.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use gif;
+use png;
+use DataField;
+use Float;
+
+/// A gradient between colour stops, which maps values from `0` to `1` to
+/// colours.
+#[derive(Clone, Debug)]
+pub struct ColorMap {
+    stops: Vec<(f64, [u8; 3])>,
+}
+
+impl ColorMap {
+    /// Creates a gradient between evenly spaced colours.
+    pub fn new(colors: &[[u8; 3]]) -> Self {
+        assert!(!colors.is_empty(), "A color map needs at least one color");
+        let last = (colors.len() - 1).max(1) as f64;
+        ColorMap {
+            stops: colors.iter()
+                         .enumerate()
+                         .map(|(index, &color)| (index as f64 / last, color))
+                         .collect(),
+        }
+    }
+
+    /// Creates a gradient from colour stops, given as increasing positions
+    /// between `0` and `1` along with their colour.
+    pub fn with_stops(stops: &[(f64, [u8; 3])]) -> Self {
+        assert!(!stops.is_empty(), "A color map needs at least one color");
+        assert!(stops.windows(2).all(|pair| pair[0].0 <= pair[1].0),
+                "The positions of the color stops need to be increasing");
+        ColorMap { stops: stops.to_vec() }
+    }
+
+    pub fn greyscale() -> Self {
+        ColorMap::new(&[[0, 0, 0], [255, 255, 255]])
+    }
+
+    pub fn viridis() -> Self {
+        ColorMap::new(&[[68, 1, 84],
+                        [72, 40, 120],
+                        [62, 73, 137],
+                        [49, 104, 142],
+                        [38, 130, 142],
+                        [31, 158, 137],
+                        [53, 183, 121],
+                        [110, 206, 88],
+                        [253, 231, 37]])
+    }
+
+    pub fn inferno() -> Self {
+        ColorMap::new(&[[0, 0, 4],
+                        [31, 12, 72],
+                        [85, 15, 109],
+                        [136, 34, 106],
+                        [186, 54, 85],
+                        [227, 89, 51],
+                        [249, 142, 9],
+                        [249, 203, 53],
+                        [252, 255, 164]])
+    }
+
+    /// A blue to red map with a neutral centre, meant for values that are
+    /// symmetric around the middle of the value range.
+    pub fn diverging() -> Self {
+        ColorMap::new(&[[59, 76, 192],
+                        [141, 176, 254],
+                        [221, 221, 221],
+                        [244, 154, 123],
+                        [180, 4, 38]])
+    }
+
+    /// Returns the colour at `position`, which gets clamped to `0` to `1`.
+    pub fn color(&self, position: f64) -> [u8; 3] {
+        let position = if position.is_nan() {
+            0.0
+        } else {
+            position.clamp(0.0, 1.0)
+        };
+
+        let next = self.stops
+                       .iter()
+                       .position(|&(stop, _)| stop >= position)
+                       .unwrap_or(self.stops.len() - 1);
+        if next == 0 {
+            return self.stops[0].1;
+        }
+
+        let (start, from) = self.stops[next - 1];
+        let (end, to) = self.stops[next];
+        let weight = if end > start {
+            (position - start) / (end - start)
+        } else {
+            1.0
+        };
+        let mut color = [0; 3];
+        for channel in 0..3 {
+            let value = from[channel] as f64 + weight * (to[channel] as f64 - from[channel] as f64);
+            color[channel] = value.round() as u8;
+        }
+        color
+    }
+}
+
+/// The range of values that gets mapped onto the colour map.
+#[derive(Clone, Copy, Debug)]
+pub enum ValueRange<T>
+    where T: Float
+{
+    /// Uses the smallest and largest value of every field.
+    Automatic,
+    Fixed(T, T),
+}
+
+/// An image with 8 bit RGB pixels stored row by row.
+#[derive(Clone, Debug)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        RgbImage {
+            width,
+            height,
+            pixels: vec![0; 3 * width * height],
+        }
+    }
+
+    pub fn set_pixel(&mut self, (x, y): (usize, usize), color: [u8; 3]) {
+        let index = 3 * (x + self.width * y);
+        self.pixels[index..index + 3].copy_from_slice(&color);
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()
+               .and_then(|mut writer| writer.write_image_data(&self.pixels))
+               .map_err(io::Error::other)
+    }
+}
+
+const COLOR_BAR_GAP: usize = 2;
+
+/// Renders fields with a colour map, optionally with a colour bar on the
+/// right that goes from the lowest value at the bottom to the highest value
+/// at the top.
+#[derive(Clone, Debug)]
+pub struct FieldRenderer<T>
+    where T: Float
+{
+    pub color_map: ColorMap,
+    pub value_range: ValueRange<T>,
+    pub color_bar: bool,
+}
+
+impl<T> FieldRenderer<T>
+    where T: Float
+{
+    pub fn new(color_map: ColorMap) -> Self {
+        FieldRenderer {
+            color_map,
+            value_range: ValueRange::Automatic,
+            color_bar: false,
+        }
+    }
+
+    /// Returns the values that get mapped onto both ends of the colour map.
+    pub fn range_of(&self, field: &DataField<T>) -> (T, T) {
+        match self.value_range {
+            ValueRange::Fixed(min, max) => (min, max),
+            ValueRange::Automatic => {
+                let (nx, ny) = field.dimensions;
+                let (mut min, mut max) = (field[(0, 0)], field[(0, 0)]);
+                for y in 0..ny {
+                    for x in 0..nx {
+                        let value = field[(x, y)];
+                        if value < min {
+                            min = value;
+                        }
+                        if value > max {
+                            max = value;
+                        }
+                    }
+                }
+                (min, max)
+            }
+        }
+    }
+
+    /// Returns the size of the images rendered for fields of the given
+    /// dimensions.
+    pub fn image_size(&self, (nx, ny): (usize, usize)) -> (usize, usize) {
+        if self.color_bar {
+            (nx + COLOR_BAR_GAP + (nx / 20).max(4), ny)
+        } else {
+            (nx, ny)
+        }
+    }
+
+    pub fn render(&self, field: &DataField<T>) -> RgbImage {
+        let (nx, ny) = field.dimensions;
+        let (min, max) = self.range_of(field);
+        let (min, max) = (min.to_f64(), max.to_f64());
+
+        let (width, height) = self.image_size(field.dimensions);
+        let mut image = RgbImage::new(width, height);
+
+        for y in 0..ny {
+            for x in 0..nx {
+                let position = if max > min {
+                    (field[(x, y)].to_f64() - min) / (max - min)
+                } else {
+                    0.5
+                };
+                image.set_pixel((x, y), self.color_map.color(position));
+            }
+        }
+
+        if self.color_bar {
+            for y in 0..ny {
+                let position = 1.0 - y as f64 / (ny - 1).max(1) as f64;
+                let color = self.color_map.color(position);
+                for x in nx + COLOR_BAR_GAP..width {
+                    image.set_pixel((x, y), color);
+                }
+            }
+        }
+
+        image
+    }
+}
+
+/// Writes images as the frames of an animated GIF that loops forever. The
+/// colours of every frame are reduced to a palette of 256 colours.
+pub struct GifWriter<W>
+    where W: Write
+{
+    encoder: gif::Encoder<W>,
+    dimensions: (usize, usize),
+    delay: u16,
+}
+
+impl<W> GifWriter<W>
+    where W: Write
+{
+    /// Starts a GIF with frames of the given size, which are shown for
+    /// `delay` hundredths of a second each.
+    pub fn new(writer: W, (width, height): (usize, usize), delay: u16) -> io::Result<Self> {
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "The frames are too large for a GIF"));
+        }
+
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &[])
+                              .map_err(io::Error::other)?;
+        encoder.set_repeat(gif::Repeat::Infinite)
+               .map_err(io::Error::other)?;
+
+        Ok(GifWriter {
+            encoder,
+            dimensions: (width, height),
+            delay,
+        })
+    }
+
+    pub fn add_frame(&mut self, image: &RgbImage) -> io::Result<()> {
+        if (image.width, image.height) != self.dimensions {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "All frames of a GIF need to have the same size"));
+        }
+
+        let mut frame = gif::Frame::from_rgb_speed(image.width as u16,
+                                                   image.height as u16,
+                                                   &image.pixels,
+                                                   10);
+        frame.delay = self.delay;
+        self.encoder
+            .write_frame(&frame)
+            .map_err(io::Error::other)
+    }
+}
+
+/// Writes images as numbered PNG files `<prefix>_000000.png`,
+/// `<prefix>_000001.png` and so on, which video encoders can pick up.
+pub struct FrameSequence {
+    directory: PathBuf,
+    prefix: String,
+    next_frame: usize,
+}
+
+impl FrameSequence {
+    pub fn new<P: Into<PathBuf>>(directory: P, prefix: &str) -> Self {
+        FrameSequence {
+            directory: directory.into(),
+            prefix: prefix.to_string(),
+            next_frame: 0,
+        }
+    }
+
+    /// Writes the next frame and returns the path of its file.
+    pub fn add_frame(&mut self, image: &RgbImage) -> io::Result<PathBuf> {
+        let path = self.directory.join(format!("{}_{:06}.png", self.prefix, self.next_frame));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        image.write_png(&mut writer)?;
+        writer.flush()?;
+        self.next_frame += 1;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorMap, FieldRenderer, GifWriter, RgbImage, ValueRange};
+    use DataField;
+
+    fn gradient() -> DataField<f64> {
+        let mut field = DataField::new((4, 3));
+        for ((x, y), cell) in field.iter_mut() {
+            *cell = -1.0 + x as f64 + 4.0 * y as f64;
+        }
+        field
+    }
+
+    #[test]
+    fn interpolates_between_the_stops() {
+        let color_map = ColorMap::with_stops(&[(0.0, [0, 0, 0]),
+                                               (0.25, [100, 200, 40]),
+                                               (1.0, [250, 200, 40])]);
+        assert_eq!(color_map.color(0.0), [0, 0, 0]);
+        assert_eq!(color_map.color(0.125), [50, 100, 20]);
+        assert_eq!(color_map.color(0.25), [100, 200, 40]);
+        assert_eq!(color_map.color(0.5), [150, 200, 40]);
+        assert_eq!(color_map.color(-3.0), [0, 0, 0]);
+        assert_eq!(color_map.color(7.0), [250, 200, 40]);
+        assert_eq!(color_map.color(f64::NAN), [0, 0, 0]);
+
+        let greyscale = ColorMap::greyscale();
+        assert_eq!(greyscale.color(0.5), [128, 128, 128]);
+        assert_eq!(ColorMap::new(&[[7, 8, 9]]).color(0.5), [7, 8, 9]);
+    }
+
+    #[test]
+    fn maps_the_automatic_range_onto_the_colour_map() {
+        let field = gradient();
+        let mut renderer = FieldRenderer::new(ColorMap::greyscale());
+        assert_eq!(renderer.range_of(&field), (-1.0, 10.0));
+
+        let image = renderer.render(&field);
+        assert_eq!((image.width, image.height), (4, 3));
+        assert_eq!(&image.pixels[..3], &[0, 0, 0]);
+        assert_eq!(&image.pixels[image.pixels.len() - 3..], &[255, 255, 255]);
+
+        renderer.value_range = ValueRange::Fixed(-1.0, 1.0);
+        assert_eq!(renderer.range_of(&field), (-1.0, 1.0));
+        let image = renderer.render(&field);
+        assert_eq!(&image.pixels[3..6], &[128, 128, 128]);
+        assert_eq!(&image.pixels[6..9], &[255, 255, 255]);
+
+        // A constant field gets the colour of the middle
+        let image = renderer.render(&DataField::new((3, 3)));
+        assert_eq!(&image.pixels[..3], &[128, 128, 128]);
+    }
+
+    #[test]
+    fn draws_the_colour_bar_from_low_to_high() {
+        let mut renderer = FieldRenderer::new(ColorMap::greyscale());
+        renderer.color_bar = true;
+        let field = DataField::<f64>::new((40, 30));
+        assert_eq!(renderer.image_size(field.dimensions), (46, 30));
+
+        let image = renderer.render(&field);
+        let pixel = |x: usize, y: usize| &image.pixels[3 * (x + 46 * y)..3 * (x + 46 * y) + 3];
+        assert_eq!(pixel(45, 0), &[255, 255, 255]);
+        assert_eq!(pixel(45, 29), &[0, 0, 0]);
+        assert_eq!(pixel(41, 0), &[0, 0, 0]);
+    }
+
+    #[test]
+    fn writes_png_headers() {
+        let mut bytes = Vec::new();
+        RgbImage::new(5, 3).write_png(&mut bytes).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 5, 0, 0, 0, 3]);
+        // 8 bit RGB
+        assert_eq!(&bytes[24..26], &[8, 2]);
+    }
+
+    #[test]
+    fn writes_gif_headers_and_checks_the_frame_size() {
+        let mut bytes = Vec::new();
+        {
+            let mut writer = GifWriter::new(&mut bytes, (5, 3), 4).unwrap();
+            writer.add_frame(&RgbImage::new(5, 3)).unwrap();
+            writer.add_frame(&RgbImage::new(5, 3)).unwrap();
+            assert!(writer.add_frame(&RgbImage::new(3, 5)).is_err());
+        }
+        assert_eq!(&bytes[..6], b"GIF89a");
+        assert_eq!(&bytes[6..10], &[5, 0, 3, 0]);
+        assert_eq!(bytes.last(), Some(&0x3b));
+
+        assert!(GifWriter::new(Vec::new(), (70000, 3), 4).is_err());
+    }
+}
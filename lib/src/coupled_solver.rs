@@ -0,0 +1,675 @@
+use std::io::{self, Read, Write};
+use std::mem;
+use boundary_condition::BoxedBoundaryConditions;
+use BoundaryCondition;
+use checkpoint::{self, SolverState};
+use CoupledEquation;
+use DataField;
+use Float;
+use PreCondition;
+use TimeIntegration;
+use time_integration::{self, ButcherTableau, Step, Stepper, System, TimeStepControl};
+use scoped_threadpool::Pool;
+use num_cpus;
+
+type Observers<S> = Vec<(usize, Box<dyn FnMut(&S) + Send>)>;
+
+pub struct CoupledSolver<T, E>
+    where T: Float,
+          E: CoupledEquation<T>
+{
+    equation: E,
+    pub boundary_conditions: Vec<BoxedBoundaryConditions<T>>,
+    delta_t: T,
+    h: T,
+    time: T,
+    steps: usize,
+    trial_delta_t: T,
+    field_names: Vec<String>,
+    fields: [Vec<DataField<T>>; 2],
+    current_fields: usize,
+    time_integration: TimeIntegration<T>,
+    pub time_step_control: TimeStepControl<T>,
+    observers: Observers<CoupledSolver<T, E>>,
+    scratch_fields: Vec<DataField<T>>,
+    thread_pool: Pool,
+}
+
+impl<T, E> CoupledSolver<T, E>
+    where T: Float,
+          E: CoupledEquation<T> + Sync + Send
+{
+    pub fn new(equation: E,
+               boundary_conditions: Vec<BoxedBoundaryConditions<T>>,
+               dimensions: (usize, usize),
+               delta_t: T,
+               h: T)
+               -> Self {
+        CoupledSolver::with_time_integration(equation,
+                                             boundary_conditions,
+                                             dimensions,
+                                             delta_t,
+                                             h,
+                                             TimeIntegration::ForwardEuler)
+    }
+
+    /// Only the explicit schemes are supported, as the equations don't
+    /// provide their diffusion terms separately.
+    pub fn with_time_integration(equation: E,
+                                 boundary_conditions: Vec<BoxedBoundaryConditions<T>>,
+                                 dimensions: (usize, usize),
+                                 delta_t: T,
+                                 h: T,
+                                 time_integration: TimeIntegration<T>)
+                                 -> Self {
+        assert!(time_integration.implicitness().is_none() &&
+                !time_integration.is_second_order(),
+                "The coupled solver only supports the explicit time integrations");
+        let field_names = equation.field_names();
+        assert_eq!(field_names.len(), boundary_conditions.len());
+
+        let field_count = field_names.len();
+        let ghost_layers = equation.ghost_layers();
+        let new_fields = || {
+            (0..field_count)
+                .map(|_| DataField::with_ghost_layers(dimensions, ghost_layers))
+                .collect()
+        };
+
+        CoupledSolver {
+            equation,
+            boundary_conditions,
+            delta_t,
+            h,
+            time: T::zero(),
+            steps: 0,
+            trial_delta_t: delta_t,
+            field_names,
+            fields: [new_fields(), new_fields()],
+            current_fields: 0,
+            time_integration,
+            time_step_control: TimeStepControl::Fixed,
+            observers: Vec::new(),
+            scratch_fields: Vec::new(),
+            thread_pool: Pool::new(num_cpus::get() as u32),
+        }
+    }
+
+    pub fn field_names(&self) -> &[String] {
+        &self.field_names
+    }
+
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.field_names.iter().position(|n| n == name)
+    }
+
+    pub fn execute_precondition<P: PreCondition<T> + ?Sized + Sync>(&mut self,
+                                                                   name: &str,
+                                                                   precondition: &P) {
+        let index = self.field_index(name).expect("Unknown field");
+        let current_field = &mut self.fields[self.current_fields][index];
+        let thread_count = self.thread_pool.thread_count() as usize;
+
+        self.thread_pool.scoped(|scope| {
+            for chunk in current_field.chunks_mut(thread_count) {
+                scope.execute(move || {
+                    for (coord, cell) in chunk {
+                        *cell = precondition.precondition(coord);
+                    }
+                });
+            }
+        });
+    }
+
+    pub fn solve_next_frame(&mut self) {
+        let delta_t = self.delta_t;
+        let time_integration = self.time_integration;
+        let stable_time_step = self.stable_time_step();
+        time_integration::solve_frame(self,
+                                      &time_integration,
+                                      self.time_step_control,
+                                      stable_time_step,
+                                      delta_t);
+
+        self.steps += 1;
+        self.notify_observers();
+    }
+
+    pub fn run_steps(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.solve_next_frame();
+        }
+    }
+
+    /// Registers an observer that gets called after every `interval` frames.
+    pub fn add_observer<F>(&mut self, interval: usize, observer: F)
+        where F: FnMut(&Self) + Send + 'static
+    {
+        assert!(interval > 0, "The observer interval needs to be positive");
+        self.observers.push((interval, Box::new(observer)));
+    }
+
+    fn notify_observers(&mut self) {
+        let mut observers = Vec::new();
+        mem::swap(&mut observers, &mut self.observers);
+        for &mut (interval, ref mut observer) in &mut observers {
+            if self.steps.is_multiple_of(interval) {
+                observer(self);
+            }
+        }
+        mem::swap(&mut observers, &mut self.observers);
+    }
+
+    pub fn stable_time_step(&self) -> Option<T> {
+        let limit = self.equation.max_stable_time_step(self.h);
+        match (limit, self.time_integration.stability_factor()) {
+            (Some(limit), Some(factor)) => Some(limit * factor),
+            _ => None,
+        }
+    }
+
+    /// Writes the fields, the time, the step count and the parameters of the
+    /// equation and the boundary conditions, so that the run can be resumed
+    /// bit-identically with `load_checkpoint`. The time integration scheme,
+    /// the time step control and the observers aren't part of a checkpoint.
+    pub fn save_checkpoint<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let writer: &mut dyn Write = writer;
+        let state = SolverState {
+            current: self.current_fields,
+            steps: self.steps,
+            time: self.time,
+            trial_delta_t: self.trial_delta_t,
+            delta_t: self.delta_t,
+            h: self.h,
+            states: self.save_states()?,
+        };
+        state.write_to(writer, "CoupledSolver")?;
+
+        for fields in &self.fields {
+            for field in fields {
+                field.write_to(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restores a checkpoint written by `save_checkpoint`. It has to be
+    /// written by a solver with the same equation, boundary conditions,
+    /// fields and dimensions. If an error occurs, the solver is left
+    /// unchanged.
+    pub fn load_checkpoint<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let reader: &mut dyn Read = reader;
+        self.read_checkpoint(reader).map_err(checkpoint::map_truncated)
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let state = SolverState::read_from(reader, "CoupledSolver", self.state_count())?;
+
+        let template = &self.fields[0][0];
+        let field_count = self.field_names.len();
+        let mut read_fields = || {
+            (0..field_count)
+                .map(|_| checkpoint::read_matching_field(reader, template))
+                .collect::<io::Result<Vec<_>>>()
+        };
+        let first = read_fields()?;
+        let second = read_fields()?;
+
+        // Everything got read, so only the states can still fail to restore
+        let backup = self.save_states()?;
+        checkpoint::restore_states(&state.states, &backup, |readers| self.load_states(readers))?;
+
+        self.fields = [first, second];
+        self.current_fields = state.current;
+        self.steps = state.steps;
+        self.time = state.time;
+        self.trial_delta_t = state.trial_delta_t;
+        self.delta_t = state.delta_t;
+        self.h = state.h;
+        Ok(())
+    }
+
+    /// The state of the equation followed by the states of the four
+    /// boundary conditions of every field.
+    fn state_count(&self) -> usize {
+        1 + 4 * self.boundary_conditions.len()
+    }
+
+    fn save_states(&self) -> io::Result<Vec<Vec<u8>>> {
+        let mut states = vec![Vec::new(); self.state_count()];
+        self.equation.save_state(&mut states[0])?;
+        for (conditions, states) in self.boundary_conditions.iter().zip(states[1..].chunks_mut(4)) {
+            conditions.up.save_state(&mut states[0])?;
+            conditions.down.save_state(&mut states[1])?;
+            conditions.left.save_state(&mut states[2])?;
+            conditions.right.save_state(&mut states[3])?;
+        }
+        Ok(states)
+    }
+
+    /// Restores the states returned by `save_states`.
+    fn load_states(&mut self, readers: &mut [&[u8]]) -> io::Result<()> {
+        self.equation.load_state(&mut readers[0])?;
+        for (conditions, readers) in self.boundary_conditions
+                                         .iter_mut()
+                                         .zip(readers[1..].chunks_mut(4)) {
+            conditions.up.load_state(&mut readers[0])?;
+            conditions.down.load_state(&mut readers[1])?;
+            conditions.left.load_state(&mut readers[2])?;
+            conditions.right.load_state(&mut readers[3])?;
+        }
+        Ok(())
+    }
+
+    /// Solves a step into the target fields and constrains them. Returns the
+    /// largest error of the step relative to the tolerances, if they are
+    /// given and the scheme contains an embedded pair.
+    fn step(&mut self, delta_t: T, tolerances: Option<(T, T)>) -> T {
+        let error = if let Some(tableau) = self.time_integration.runge_kutta_tableau() {
+            self.solve_runge_kutta(&tableau, delta_t, tolerances)
+        } else {
+            self.solve_explicit(delta_t);
+            T::zero()
+        };
+
+        let target_fields = &mut self.fields[self.current_fields ^ 1];
+        constrain_fields(&self.equation, target_fields, &mut self.thread_pool);
+        error
+    }
+
+    fn solve_explicit(&mut self, delta_t: T) {
+        // Prepare solving
+        let (fields_a, fields_b) = self.fields.split_at_mut(1);
+        let (current_fields, target_fields) = if self.current_fields == 0 {
+            (&mut fields_a[0], &mut fields_b[0])
+        } else {
+            (&mut fields_b[0], &mut fields_a[0])
+        };
+        let equation = &self.equation;
+        let h = self.h;
+        let thread_count = self.thread_pool.thread_count() as usize;
+
+        // Execute Boundary Conditions
+        calculate_boundaries(current_fields, &self.boundary_conditions, self.time, h);
+
+        // Solve every equation of the system for the whole field
+        self.thread_pool.scoped(|scope| {
+            let current_fields = &current_fields[..];
+            for (index, target_field) in target_fields.iter_mut().enumerate() {
+                for chunk in target_field.chunks_inner_mut(thread_count) {
+                    scope.execute(move || {
+                        for (coord, cell) in chunk {
+                            *cell = equation.solve(current_fields, index, coord, delta_t, h);
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    fn solve_runge_kutta(&mut self,
+                         tableau: &ButcherTableau,
+                         delta_t: T,
+                         tolerances: Option<(T, T)>)
+                         -> T {
+        let (fields_a, fields_b) = self.fields.split_at_mut(1);
+        let (current_fields, target_fields) = if self.current_fields == 0 {
+            (&mut fields_a[0], &mut fields_b[0])
+        } else {
+            (&mut fields_b[0], &mut fields_a[0])
+        };
+        let mut system = Rates {
+            equation: &self.equation,
+            boundary_conditions: &self.boundary_conditions,
+            h: self.h,
+        };
+        let step = Step {
+            time: self.time,
+            delta_t,
+            tolerances,
+        };
+        time_integration::runge_kutta_step(&mut system,
+                                           tableau,
+                                           step,
+                                           current_fields,
+                                           target_fields,
+                                           &mut self.scratch_fields,
+                                           &mut self.thread_pool)
+    }
+
+    pub fn time(&self) -> T {
+        self.time
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn delta_t(&self) -> T {
+        self.delta_t
+    }
+
+    pub fn h(&self) -> T {
+        self.h
+    }
+
+    pub fn get_fields(&self) -> &[DataField<T>] {
+        &self.fields[self.current_fields]
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&DataField<T>> {
+        self.field_index(name).map(move |index| &self.fields[self.current_fields][index])
+    }
+
+    pub fn get_field_mut(&mut self, name: &str) -> Option<&mut DataField<T>> {
+        match self.field_index(name) {
+            Some(index) => Some(&mut self.fields[self.current_fields][index]),
+            None => None,
+        }
+    }
+}
+
+impl<T, E> Stepper<T> for CoupledSolver<T, E>
+    where T: Float,
+          E: CoupledEquation<T> + Sync + Send
+{
+    fn step(&mut self, delta_t: T, tolerances: Option<(T, T)>) -> T {
+        CoupledSolver::step(self, delta_t, tolerances)
+    }
+
+    fn accept(&mut self, delta_t: T) {
+        // Swap the fields
+        self.current_fields ^= 1;
+        self.time = self.time + delta_t;
+    }
+
+    fn trial_delta_t(&mut self) -> &mut T {
+        &mut self.trial_delta_t
+    }
+}
+
+/// The right-hand sides of all equations of the system.
+struct Rates<'a, T, E>
+    where T: Float + 'a,
+          E: 'a
+{
+    equation: &'a E,
+    boundary_conditions: &'a [BoxedBoundaryConditions<T>],
+    h: T,
+}
+
+impl<'a, T, E> System<T> for Rates<'a, T, E>
+    where T: Float,
+          E: CoupledEquation<T> + Sync
+{
+    fn calculate_boundaries(&self, fields: &mut [DataField<T>], time: T) {
+        calculate_boundaries(fields, self.boundary_conditions, time, self.h);
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       index: usize,
+                       coord: (usize, usize),
+                       _: &mut DataField<T>)
+                       -> T {
+        self.equation.right_hand_side(fields, index, coord, self.h)
+    }
+}
+
+fn calculate_boundaries<T>(fields: &mut [DataField<T>],
+                           boundary_conditions: &[BoxedBoundaryConditions<T>],
+                           time: T,
+                           h: T)
+    where T: Float
+{
+    for (field, boundary_conditions) in fields.iter_mut().zip(boundary_conditions) {
+        boundary_conditions.calculate_boundaries(field, time, h);
+    }
+}
+
+/// Constrains the values of all fields at every cell together.
+fn constrain_fields<T, E>(equation: &E, fields: &mut [DataField<T>], thread_pool: &mut Pool)
+    where T: Float,
+          E: CoupledEquation<T> + Sync
+{
+    let thread_count = thread_pool.thread_count() as usize;
+    thread_pool.scoped(|scope| {
+        let mut chunks = fields.iter_mut()
+                               .map(|field| field.chunks_inner_mut(thread_count))
+                               .collect::<Vec<_>>();
+        loop {
+            let mut fields = chunks.iter_mut().filter_map(Iterator::next).collect::<Vec<_>>();
+            if fields.is_empty() {
+                break;
+            }
+            scope.execute(move || {
+                let mut cells = Vec::with_capacity(fields.len());
+                let mut values = Vec::with_capacity(fields.len());
+                loop {
+                    cells.clear();
+                    for field in &mut fields {
+                        match field.next() {
+                            Some((_, cell)) => cells.push(cell),
+                            None => return,
+                        }
+                    }
+                    values.clear();
+                    values.extend(cells.iter().map(|cell| **cell));
+                    equation.constrain(&mut values);
+                    for (cell, &value) in cells.iter_mut().zip(&values) {
+                        **cell = value;
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use boundary_condition::{Dirichlet, Neumann};
+    use differential_equation::NonIsothermalPhaseFieldEquation;
+    use BoundaryConditions;
+    use CoupledEquation;
+    use CoupledSolver;
+    use DataField;
+    use Float;
+    use TimeIntegration;
+    use TimeStepControl;
+
+    /// Two diffusing species that turn into each other, which conserves their
+    /// total amount.
+    struct Exchange {
+        diffusion: f64,
+        rate: f64,
+    }
+
+    impl CoupledEquation<f64> for Exchange {
+        fn field_names(&self) -> Vec<String> {
+            vec!["u".to_string(), "v".to_string()]
+        }
+
+        fn right_hand_side(&self,
+                           fields: &[DataField<f64>],
+                           field: usize,
+                           (x, y): (usize, usize),
+                           h: f64)
+                           -> f64 {
+            let u = &fields[field];
+            let laplacian = (u[(x - 1, y)] + u[(x + 1, y)] + u[(x, y - 1)] + u[(x, y + 1)] -
+                             4.0 * u[(x, y)]) / (h * h);
+            let exchange = self.rate * (fields[1 - field][(x, y)] - u[(x, y)]);
+            self.diffusion * laplacian + exchange
+        }
+
+        fn max_stable_time_step(&self, h: f64) -> Option<f64> {
+            Some(1.0 / (8.0 * self.diffusion / (h * h) + 2.0 * self.rate))
+        }
+
+        fn save_state(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+            self.diffusion.write_to(writer)?;
+            self.rate.write_to(writer)
+        }
+
+        fn load_state(&mut self, reader: &mut dyn io::Read) -> io::Result<()> {
+            self.diffusion = f64::read_from(reader)?;
+            self.rate = f64::read_from(reader)?;
+            Ok(())
+        }
+    }
+
+    type TestSolver = CoupledSolver<f64, Exchange>;
+
+    fn solver(time_integration: TimeIntegration<f64>, delta_t: f64) -> TestSolver {
+        let boundary_conditions = vec![BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann)
+                                           .boxed(),
+                                       BoundaryConditions::new(Neumann,
+                                                               Neumann,
+                                                               Dirichlet(0.5),
+                                                               Dirichlet(0.5))
+                                           .boxed()];
+        let equation = Exchange {
+            diffusion: 1.0,
+            rate: 0.5,
+        };
+        let mut solver = CoupledSolver::with_time_integration(equation,
+                                                              boundary_conditions,
+                                                              (12, 10),
+                                                              delta_t,
+                                                              1.0,
+                                                              time_integration);
+        for ((x, y), cell) in solver.get_field_mut("u").unwrap().iter_mut() {
+            *cell = (x as f64 * 0.6).sin() * (y as f64 * 0.4).cos();
+        }
+        solver
+    }
+
+    fn checkpoint(solver: &TestSolver) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        solver.save_checkpoint(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn largest_difference(a: &TestSolver, b: &TestSolver) -> f64 {
+        let mut difference = 0.0f64;
+        for (field_a, field_b) in a.get_fields().iter().zip(b.get_fields()) {
+            for y in 1..9 {
+                for x in 1..11 {
+                    difference = difference.max((field_a[(x, y)] - field_b[(x, y)]).abs());
+                }
+            }
+        }
+        difference
+    }
+
+    #[test]
+    fn time_step_controls_follow_the_reference_solution() {
+        let mut reference = solver(TimeIntegration::RungeKutta4, 0.01);
+        reference.run_steps(200);
+
+        let mut stability = solver(TimeIntegration::ForwardEuler, 1.0);
+        stability.time_step_control = TimeStepControl::Stability { safety_factor: 0.9 };
+        stability.run_steps(2);
+        assert!(largest_difference(&stability, &reference) < 1e-2);
+
+        let mut error_control = solver(TimeIntegration::BogackiShampine, 0.5);
+        error_control.time_step_control = TimeStepControl::ErrorControl {
+            absolute_tolerance: 1e-6,
+            relative_tolerance: 1e-6,
+        };
+        error_control.run_steps(4);
+        assert_eq!(error_control.time(), 2.0);
+        assert!(largest_difference(&error_control, &reference) < 1e-4);
+    }
+
+    #[test]
+    fn notifies_the_observers_at_their_interval() {
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let observed_steps = steps.clone();
+        let mut solver = solver(TimeIntegration::ForwardEuler, 0.1);
+        solver.add_observer(3, move |solver| observed_steps.lock().unwrap().push(solver.steps()));
+        solver.run_steps(10);
+        assert_eq!(*steps.lock().unwrap(), vec![3, 6, 9]);
+    }
+
+    #[test]
+    fn resumes_bit_identically() {
+        let mut original = solver(TimeIntegration::BogackiShampine, 0.5);
+        original.time_step_control = TimeStepControl::ErrorControl {
+            absolute_tolerance: 1e-4,
+            relative_tolerance: 1e-4,
+        };
+        original.run_steps(3);
+        let bytes = checkpoint(&original);
+
+        let mut resumed = solver(TimeIntegration::BogackiShampine, 0.1);
+        resumed.time_step_control = original.time_step_control;
+        resumed.load_checkpoint(&mut &bytes[..]).unwrap();
+        original.run_steps(5);
+        resumed.run_steps(5);
+
+        // The checkpoints contain the fields, the time and the step count
+        assert!(checkpoint(&resumed) == checkpoint(&original));
+        assert_eq!(resumed.steps(), 8);
+    }
+
+    #[test]
+    fn rejects_truncated_checkpoints() {
+        let bytes = checkpoint(&solver(TimeIntegration::ForwardEuler, 0.1));
+        for length in (0..bytes.len()).step_by(11) {
+            let mut solver = solver(TimeIntegration::ForwardEuler, 0.1);
+            let error = solver.load_checkpoint(&mut &bytes[..length]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(solver.time(), 0.0);
+        }
+    }
+
+    type NonIsothermalSolver = CoupledSolver<f64, NonIsothermalPhaseFieldEquation<f64>>;
+
+    fn non_isothermal(latent_heat: f64) -> NonIsothermalSolver {
+        let boundary_conditions = (0..2)
+            .map(|_| BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann).boxed())
+            .collect();
+        let equation = NonIsothermalPhaseFieldEquation {
+            gamma: 1.0,
+            tau: 1.0,
+            epsilon: 4.0,
+            tm: 1.0,
+            la: 2.0,
+            diffusivity: 1.0,
+            latent_heat,
+        };
+        let mut solver = CoupledSolver::new(equation, boundary_conditions, (20, 20), 5.0, 1.0);
+        for ((x, y), cell) in solver.get_field_mut("phi").unwrap().iter_mut() {
+            *cell = if (7..13).contains(&x) && (7..13).contains(&y) { 1.0 } else { 0.0 };
+        }
+        for (_, cell) in solver.get_field_mut("t").unwrap().iter_mut() {
+            *cell = 0.5;
+        }
+        solver
+    }
+
+    #[test]
+    fn non_isothermal_phase_field_stays_stable_and_resumes() {
+        let mut solver = non_isothermal(0.5);
+        solver.time_step_control = TimeStepControl::Stability { safety_factor: 0.9 };
+        solver.run_steps(4);
+        let phi = solver.get_field("phi").unwrap();
+        for y in 1..19 {
+            for x in 1..19 {
+                assert!(phi[(x, y)].abs() < 1.5, "The phase field became {}", phi[(x, y)]);
+            }
+        }
+
+        // The parameters of the equation are restored as well
+        let mut bytes = Vec::new();
+        solver.save_checkpoint(&mut bytes).unwrap();
+        let mut resumed = non_isothermal(0.1);
+        resumed.load_checkpoint(&mut &bytes[..]).unwrap();
+        let mut resumed_bytes = Vec::new();
+        resumed.save_checkpoint(&mut resumed_bytes).unwrap();
+        assert!(bytes == resumed_bytes);
+    }
+}
@@ -0,0 +1,75 @@
+//! Fields as comma separated values. Every line holds a row of the field,
+//! starting at `y = 0`, and the boundary is included.
+
+use std::io::{self, BufRead, Write};
+use checkpoint::invalid_data;
+use DataField;
+use Float;
+
+pub fn write<T, W>(writer: &mut W, field: &DataField<T>) -> io::Result<()>
+    where T: Float,
+          W: Write
+{
+    let (nx, ny) = field.dimensions;
+    for y in 0..ny {
+        for x in 0..nx {
+            if x > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{:?}", field[(x, y)])?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Reads a field written by `write`. Empty lines are ignored, but all other
+/// lines need to contain the same number of values.
+pub fn read<T, R>(reader: R) -> io::Result<DataField<T>>
+    where T: Float,
+          R: BufRead
+{
+    let mut values = Vec::new();
+    let mut nx = 0;
+    let mut ny = 0;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let row_start = values.len();
+        for value in line.split(',') {
+            match value.trim().parse::<f64>() {
+                Ok(value) => values.push(T::from_f64(value)),
+                Err(_) => {
+                    return Err(invalid_data(format!("Invalid value \"{}\" in line {}",
+                                                    value.trim(),
+                                                    index + 1)))
+                }
+            }
+        }
+
+        let row_length = values.len() - row_start;
+        if ny == 0 {
+            nx = row_length;
+        } else if row_length != nx {
+            return Err(invalid_data(format!("Line {} contains {} values instead of {}",
+                                            index + 1,
+                                            row_length,
+                                            nx)));
+        }
+        ny += 1;
+    }
+
+    if ny == 0 {
+        return Err(invalid_data("The file doesn't contain any values".to_string()));
+    }
+
+    let mut field = DataField::new((nx, ny));
+    for ((x, y), cell) in field.iter_mut() {
+        *cell = values[x + nx * y];
+    }
+    Ok(field)
+}
@@ -1,49 +1,126 @@
+use std::io::{self, Read, Write};
 use std::ops::{Index, IndexMut};
 use std::slice;
+use checkpoint::{invalid_data, map_truncated, read_u64, write_u64};
+use Float;
 
-pub struct DataField {
+/// The number of cells that get allocated up front when reading a field.
+const MAX_PREALLOCATED_CELLS: usize = 1 << 16;
+
+pub struct DataField<T>
+    where T: Float
+{
     pub dimensions: (usize, usize),
-    field: Vec<f32>,
+    ghost_layers: usize,
+    field: Vec<T>,
 }
 
-impl Index<(usize, usize)> for DataField {
-    type Output = f32;
+impl<T> Index<(usize, usize)> for DataField<T>
+    where T: Float
+{
+    type Output = T;
 
-    fn index(&self, (x, y): (usize, usize)) -> &f32 {
+    fn index(&self, (x, y): (usize, usize)) -> &T {
         let (nx, _) = self.dimensions;
         let index = x + nx * y;
         &self.field[index]
     }
 }
 
-impl IndexMut<(usize, usize)> for DataField {
-    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut f32 {
+impl<T> IndexMut<(usize, usize)> for DataField<T>
+    where T: Float
+{
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
         let (nx, _) = self.dimensions;
         let index = x + nx * y;
         &mut self.field[index]
     }
 }
 
-impl DataField {
-    pub fn new((nx, ny): (usize, usize)) -> Self {
+impl<T> DataField<T>
+    where T: Float
+{
+    pub fn new(dimensions: (usize, usize)) -> Self {
+        DataField::with_ghost_layers(dimensions, 1)
+    }
+
+    /// Creates a field whose boundary consists of `ghost_layers` layers of
+    /// cells on every side, which wider stencils need.
+    pub fn with_ghost_layers((nx, ny): (usize, usize), ghost_layers: usize) -> Self {
+        assert!(ghost_layers >= 1, "A field needs at least one ghost layer");
         DataField {
             dimensions: (nx, ny),
-            field: vec![0.0; nx * ny],
+            ghost_layers,
+            field: vec![T::zero(); nx * ny],
         }
     }
 
+    pub fn ghost_layers(&self) -> usize {
+        self.ghost_layers
+    }
+
+    pub fn set_ghost_layers(&mut self, ghost_layers: usize) {
+        assert!(ghost_layers >= 1, "A field needs at least one ghost layer");
+        self.ghost_layers = ghost_layers;
+    }
+
+    /// Returns whether the coordinate lies inside the ghost layers.
+    pub fn is_inner(&self, (x, y): (usize, usize)) -> bool {
+        let (nx, ny) = self.dimensions;
+        let layers = self.ghost_layers;
+        x >= layers && y >= layers && x + layers < nx && y + layers < ny
+    }
+
     pub fn contains(&self, (x, y): (usize, usize)) -> bool {
         let (nx, ny) = self.dimensions;
         x < nx && y < ny
     }
 
-    pub fn set(&mut self, coord: (usize, usize), value: f32) {
+    pub fn set(&mut self, coord: (usize, usize), value: T) {
         if self.contains(coord) {
             self[coord] = value;
         }
     }
 
-    pub fn iter_mut(&mut self) -> IterMut {
+    pub fn copy_from(&mut self, other: &DataField<T>) {
+        self.field.copy_from_slice(&other.field);
+        self.ghost_layers = other.ghost_layers;
+    }
+
+    /// Writes the dimensions followed by all cells, including the boundary.
+    pub fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let (nx, ny) = self.dimensions;
+        write_u64(writer, nx as u64)?;
+        write_u64(writer, ny as u64)?;
+        for &value in &self.field {
+            value.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a field written by `write_to`. The cells are read incrementally,
+    /// so that corrupt dimensions can't allocate more memory than there is
+    /// data.
+    pub fn read_from(reader: &mut dyn Read) -> io::Result<Self> {
+        let nx = read_u64(reader)? as usize;
+        let ny = read_u64(reader)? as usize;
+        let cell_count = match nx.checked_mul(ny) {
+            Some(cell_count) => cell_count,
+            None => return Err(invalid_data(format!("Invalid field dimensions {}x{}", nx, ny))),
+        };
+
+        let mut field = Vec::with_capacity(cell_count.min(MAX_PREALLOCATED_CELLS));
+        for _ in 0..cell_count {
+            field.push(T::read_from(reader).map_err(map_truncated)?);
+        }
+        Ok(DataField {
+            dimensions: (nx, ny),
+            ghost_layers: 1,
+            field,
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
         IterMut {
             iter: self.field.iter_mut(),
             nx: self.dimensions.0,
@@ -52,18 +129,20 @@ impl DataField {
         }
     }
 
-    pub fn iter_inner_mut(&mut self) -> IterInnerMut {
-        let field = &mut self.field[..(self.dimensions.1 - 1) * self.dimensions.0];
-        let field = &mut field[self.dimensions.0 + 1..];
+    /// Iterates over the cells inside the ghost layers.
+    pub fn iter_inner_mut(&mut self) -> IterInnerMut<'_, T> {
+        let (nx, ny) = self.dimensions;
+        let layers = self.ghost_layers;
         IterInnerMut {
-            iter: field.iter_mut(),
-            nx: self.dimensions.0 - 1,
-            x: 1,
-            y: 1,
+            iter: self.field[layers * nx..(ny - layers) * nx].iter_mut(),
+            nx,
+            ghost_layers: layers,
+            x: 0,
+            y: layers,
         }
     }
 
-    pub fn chunks_mut(&mut self, count: usize) -> ChunksMut {
+    pub fn chunks_mut(&mut self, count: usize) -> ChunksMut<'_, T> {
         let chunk_size = (self.field.len() as f32 / count as f32).ceil() as usize;
         ChunksMut {
             iter: self.field.chunks_mut(chunk_size),
@@ -73,30 +152,53 @@ impl DataField {
         }
     }
 
-    pub fn chunks_inner_mut(&mut self, count: usize) -> ChunksInnerMut {
-        let len = self.field.len();
-        let sliced_field = &mut self.field[self.dimensions.0..len - self.dimensions.0];
+    /// Splits the cells inside the ghost layers into `count` chunks that can
+    /// be processed in parallel.
+    pub fn chunks_inner_mut(&mut self, count: usize) -> ChunksInnerMut<'_, T> {
+        let (nx, ny) = self.dimensions;
+        let layers = self.ghost_layers;
+        let sliced_field = &mut self.field[layers * nx..(ny - layers) * nx];
         let cell_count = sliced_field.len();
-        let chunk_size = (cell_count as f32 / count as f32).ceil() as usize;
+        let chunk_size = ((cell_count as f32 / count as f32).ceil() as usize).max(1);
         ChunksInnerMut {
             iter: sliced_field.chunks_mut(chunk_size),
-            nx: self.dimensions.0,
+            nx,
+            ghost_layers: layers,
             x: 0,
-            y: 1,
+            y: layers,
         }
     }
 }
 
-pub struct IterMut<'field> {
-    iter: slice::IterMut<'field, f32>,
+/// Makes sure that there are at least `count` fields with the same
+/// dimensions and ghost layers as `template`.
+pub fn reserve_fields<T>(fields: &mut Vec<DataField<T>>, count: usize, template: &DataField<T>)
+    where T: Float
+{
+    if fields.iter().any(|field| {
+        field.dimensions != template.dimensions || field.ghost_layers != template.ghost_layers
+    }) {
+        fields.clear();
+    }
+    while fields.len() < count {
+        fields.push(DataField::with_ghost_layers(template.dimensions, template.ghost_layers));
+    }
+}
+
+pub struct IterMut<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::IterMut<'field, T>,
     nx: usize,
     x: usize,
     y: usize,
 }
 
-impl<'field> Iterator for IterMut<'field> {
-    type Item = ((usize, usize), &'field mut f32);
-    fn next(&mut self) -> Option<((usize, usize), &'field mut f32)> {
+impl<'field, T> Iterator for IterMut<'field, T>
+    where T: Float
+{
+    type Item = ((usize, usize), &'field mut T);
+    fn next(&mut self) -> Option<((usize, usize), &'field mut T)> {
         let value = self.iter.next();
         if let Some(value) = value {
             let result = ((self.x, self.y), value);
@@ -112,16 +214,20 @@ impl<'field> Iterator for IterMut<'field> {
     }
 }
 
-pub struct ChunksMut<'field> {
-    iter: slice::ChunksMut<'field, f32>,
+pub struct ChunksMut<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::ChunksMut<'field, T>,
     nx: usize,
     x: usize,
     y: usize,
 }
 
-impl<'field> Iterator for ChunksMut<'field> {
-    type Item = IterMut<'field>;
-    fn next(&mut self) -> Option<IterMut<'field>> {
+impl<'field, T> Iterator for ChunksMut<'field, T>
+    where T: Float
+{
+    type Item = IterMut<'field, T>;
+    fn next(&mut self) -> Option<IterMut<'field, T>> {
         let chunk = self.iter.next();
         if let Some(chunk) = chunk {
             let len = chunk.len();
@@ -133,7 +239,7 @@ impl<'field> Iterator for ChunksMut<'field> {
             };
             self.x += len;
             self.y += self.x / self.nx;
-            self.x = self.x % self.nx;
+            self.x %= self.nx;
             Some(result)
         } else {
             None
@@ -141,68 +247,129 @@ impl<'field> Iterator for ChunksMut<'field> {
     }
 }
 
-pub struct IterInnerMut<'field> {
-    iter: slice::IterMut<'field, f32>,
+pub struct IterInnerMut<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::IterMut<'field, T>,
     nx: usize,
+    ghost_layers: usize,
     x: usize,
     y: usize,
 }
 
-impl<'field> Iterator for IterInnerMut<'field> {
-    type Item = ((usize, usize), &'field mut f32);
-    fn next(&mut self) -> Option<((usize, usize), &'field mut f32)> {
-        let value = self.iter.next();
-        if let Some(value) = value {
-            let result = ((self.x, self.y), value);
+impl<'field, T> Iterator for IterInnerMut<'field, T>
+    where T: Float
+{
+    type Item = ((usize, usize), &'field mut T);
+    fn next(&mut self) -> Option<((usize, usize), &'field mut T)> {
+        loop {
+            let value = self.iter.next()?;
+            let coord = (self.x, self.y);
             self.x += 1;
-            if self.x >= self.nx {
-                self.x = 1;
+            if self.x == self.nx {
+                self.x = 0;
                 self.y += 1;
-                self.iter.next();
-                self.iter.next();
             }
-            Some(result)
-        } else {
-            None
+            // Skip the ghost layers on the left and right
+            if coord.0 >= self.ghost_layers && coord.0 + self.ghost_layers < self.nx {
+                return Some((coord, value));
+            }
         }
     }
 }
 
-pub struct ChunksInnerMut<'field> {
-    iter: slice::ChunksMut<'field, f32>,
+pub struct ChunksInnerMut<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::ChunksMut<'field, T>,
     nx: usize,
+    ghost_layers: usize,
     x: usize,
     y: usize,
 }
 
-impl<'field> Iterator for ChunksInnerMut<'field> {
-    type Item = IterInnerMut<'field>;
-    fn next(&mut self) -> Option<IterInnerMut<'field>> {
+impl<'field, T> Iterator for ChunksInnerMut<'field, T>
+    where T: Float
+{
+    type Item = IterInnerMut<'field, T>;
+    fn next(&mut self) -> Option<IterInnerMut<'field, T>> {
         let chunk = self.iter.next();
         if let Some(chunk) = chunk {
-            let chunk = if self.x == self.nx {
-                self.x = 1;
-                self.y += 1;
-                &mut chunk[2..]
-            } else if self.x == 0 {
-                self.x = 1;
-                &mut chunk[1..]
-            } else {
-                chunk
-            };
             let len = chunk.len();
             let result = IterInnerMut {
                 iter: chunk.iter_mut(),
-                nx: self.nx - 1,
+                nx: self.nx,
+                ghost_layers: self.ghost_layers,
                 x: self.x,
                 y: self.y,
             };
             self.x += len;
             self.y += self.x / self.nx;
-            self.x = self.x % self.nx;
+            self.x %= self.nx;
             Some(result)
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts how often every cell gets visited by the chunks, checking that
+    /// each cell comes with its own coordinate.
+    fn visits(ghost_layers: usize, thread_count: usize) -> DataField<f64> {
+        let (nx, ny) = (9, 8);
+        let mut field = DataField::with_ghost_layers((nx, ny), ghost_layers);
+        for ((x, y), cell) in field.iter_mut() {
+            *cell = (y * nx + x) as f64;
+        }
+
+        let mut visits = DataField::with_ghost_layers((nx, ny), ghost_layers);
+        for chunk in field.chunks_inner_mut(thread_count) {
+            for ((x, y), cell) in chunk {
+                assert_eq!(*cell, (y * nx + x) as f64);
+                visits[(x, y)] += 1.0;
+            }
+        }
+        visits
+    }
+
+    #[test]
+    fn chunks_visit_every_inner_cell_once() {
+        for &ghost_layers in &[1, 2] {
+            for &thread_count in &[1, 2, 3, 5, 7, 16, 200] {
+                let visits = visits(ghost_layers, thread_count);
+                for y in 0..8 {
+                    for x in 0..9 {
+                        let expected = if visits.is_inner((x, y)) { 1.0 } else { 0.0 };
+                        assert_eq!(visits[(x, y)],
+                                   expected,
+                                   "Cell {:?} with {} ghost layers and {} threads",
+                                   (x, y),
+                                   ghost_layers,
+                                   thread_count);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn iter_inner_visits_every_inner_cell_once() {
+        for &ghost_layers in &[1, 2] {
+            let mut field = DataField::<f64>::with_ghost_layers((7, 9), ghost_layers);
+            let mut visits = DataField::<f64>::with_ghost_layers((7, 9), ghost_layers);
+            for (coord, _) in field.iter_inner_mut() {
+                visits[coord] += 1.0;
+            }
+            for y in 0..9 {
+                for x in 0..7 {
+                    let expected = if visits.is_inner((x, y)) { 1.0 } else { 0.0 };
+                    assert_eq!(visits[(x, y)], expected);
+                }
+            }
+        }
+    }
+}
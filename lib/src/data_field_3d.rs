@@ -0,0 +1,241 @@
+use std::ops::{Index, IndexMut};
+use std::slice;
+use Float;
+
+pub struct DataField3D<T>
+    where T: Float
+{
+    pub dimensions: (usize, usize, usize),
+    field: Vec<T>,
+}
+
+impl<T> Index<(usize, usize, usize)> for DataField3D<T>
+    where T: Float
+{
+    type Output = T;
+
+    fn index(&self, (x, y, z): (usize, usize, usize)) -> &T {
+        let (nx, ny, _) = self.dimensions;
+        let index = x + nx * (y + ny * z);
+        &self.field[index]
+    }
+}
+
+impl<T> IndexMut<(usize, usize, usize)> for DataField3D<T>
+    where T: Float
+{
+    fn index_mut(&mut self, (x, y, z): (usize, usize, usize)) -> &mut T {
+        let (nx, ny, _) = self.dimensions;
+        let index = x + nx * (y + ny * z);
+        &mut self.field[index]
+    }
+}
+
+impl<T> DataField3D<T>
+    where T: Float
+{
+    pub fn new((nx, ny, nz): (usize, usize, usize)) -> Self {
+        DataField3D {
+            dimensions: (nx, ny, nz),
+            field: vec![T::zero(); nx * ny * nz],
+        }
+    }
+
+    pub fn contains(&self, (x, y, z): (usize, usize, usize)) -> bool {
+        let (nx, ny, nz) = self.dimensions;
+        x < nx && y < ny && z < nz
+    }
+
+    pub fn set(&mut self, coord: (usize, usize, usize), value: T) {
+        if self.contains(coord) {
+            self[coord] = value;
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut3D<'_, T> {
+        IterMut3D {
+            iter: self.field.iter_mut(),
+            dimensions: self.dimensions,
+            x: 0,
+            y: 0,
+            z: 0,
+        }
+    }
+
+    pub fn iter_inner_mut(&mut self) -> IterInnerMut3D<'_, T> {
+        let (nx, ny, nz) = self.dimensions;
+        let plane = nx * ny;
+        let field = &mut self.field[plane..(nz - 1) * plane];
+        IterInnerMut3D {
+            iter: field.iter_mut(),
+            dimensions: self.dimensions,
+            x: 0,
+            y: 0,
+            z: 1,
+        }
+    }
+
+    pub fn chunks_mut(&mut self, count: usize) -> ChunksMut3D<'_, T> {
+        let chunk_size = (self.field.len() as f32 / count as f32).ceil() as usize;
+        ChunksMut3D {
+            iter: self.field.chunks_mut(chunk_size),
+            dimensions: self.dimensions,
+            x: 0,
+            y: 0,
+            z: 0,
+        }
+    }
+
+    pub fn chunks_inner_mut(&mut self, count: usize) -> ChunksInnerMut3D<'_, T> {
+        // Chunks always consist of whole xy-planes, so every chunk can skip
+        // the x and y boundaries on its own.
+        let (nx, ny, nz) = self.dimensions;
+        let plane = nx * ny;
+        let planes_per_chunk = (((nz - 2) as f32 / count as f32).ceil() as usize).max(1);
+        let sliced_field = &mut self.field[plane..(nz - 1) * plane];
+        ChunksInnerMut3D {
+            iter: sliced_field.chunks_mut(planes_per_chunk * plane),
+            dimensions: self.dimensions,
+            z: 1,
+        }
+    }
+}
+
+pub struct IterMut3D<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::IterMut<'field, T>,
+    dimensions: (usize, usize, usize),
+    x: usize,
+    y: usize,
+    z: usize,
+}
+
+impl<'field, T> Iterator for IterMut3D<'field, T>
+    where T: Float
+{
+    type Item = ((usize, usize, usize), &'field mut T);
+    fn next(&mut self) -> Option<((usize, usize, usize), &'field mut T)> {
+        let value = self.iter.next();
+        if let Some(value) = value {
+            let (nx, ny, _) = self.dimensions;
+            let result = ((self.x, self.y, self.z), value);
+            self.x += 1;
+            if self.x >= nx {
+                self.x = 0;
+                self.y += 1;
+                if self.y >= ny {
+                    self.y = 0;
+                    self.z += 1;
+                }
+            }
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct ChunksMut3D<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::ChunksMut<'field, T>,
+    dimensions: (usize, usize, usize),
+    x: usize,
+    y: usize,
+    z: usize,
+}
+
+impl<'field, T> Iterator for ChunksMut3D<'field, T>
+    where T: Float
+{
+    type Item = IterMut3D<'field, T>;
+    fn next(&mut self) -> Option<IterMut3D<'field, T>> {
+        let chunk = self.iter.next();
+        if let Some(chunk) = chunk {
+            let (nx, ny, _) = self.dimensions;
+            let len = chunk.len();
+            let result = IterMut3D {
+                iter: chunk.iter_mut(),
+                dimensions: self.dimensions,
+                x: self.x,
+                y: self.y,
+                z: self.z,
+            };
+            let index = self.x + nx * (self.y + ny * self.z) + len;
+            self.x = index % nx;
+            self.y = (index / nx) % ny;
+            self.z = index / (nx * ny);
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct IterInnerMut3D<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::IterMut<'field, T>,
+    dimensions: (usize, usize, usize),
+    x: usize,
+    y: usize,
+    z: usize,
+}
+
+impl<'field, T> Iterator for IterInnerMut3D<'field, T>
+    where T: Float
+{
+    type Item = ((usize, usize, usize), &'field mut T);
+    fn next(&mut self) -> Option<((usize, usize, usize), &'field mut T)> {
+        let (nx, ny, _) = self.dimensions;
+        for value in self.iter.by_ref() {
+            let (x, y, z) = (self.x, self.y, self.z);
+            self.x += 1;
+            if self.x >= nx {
+                self.x = 0;
+                self.y += 1;
+                if self.y >= ny {
+                    self.y = 0;
+                    self.z += 1;
+                }
+            }
+            if x > 0 && x < nx - 1 && y > 0 && y < ny - 1 {
+                return Some(((x, y, z), value));
+            }
+        }
+        None
+    }
+}
+
+pub struct ChunksInnerMut3D<'field, T>
+    where T: Float + 'field
+{
+    iter: slice::ChunksMut<'field, T>,
+    dimensions: (usize, usize, usize),
+    z: usize,
+}
+
+impl<'field, T> Iterator for ChunksInnerMut3D<'field, T>
+    where T: Float
+{
+    type Item = IterInnerMut3D<'field, T>;
+    fn next(&mut self) -> Option<IterInnerMut3D<'field, T>> {
+        let chunk = self.iter.next();
+        if let Some(chunk) = chunk {
+            let (nx, ny, _) = self.dimensions;
+            let planes = chunk.len() / (nx * ny);
+            let result = IterInnerMut3D {
+                iter: chunk.iter_mut(),
+                dimensions: self.dimensions,
+                x: 0,
+                y: 0,
+                z: self.z,
+            };
+            self.z += planes;
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::io::{self, Read, Write};
+use checkpoint::{self, invalid_data, read_u32, write_u32};
+use DataField;
+use DifferentialEquation;
+use Float;
+use Velocity;
+
+/// How the values get interpolated to the cell faces, where the flow carries
+/// them across.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdvectionScheme {
+    /// Takes the value of the cell the flow comes from. It never overshoots,
+    /// but smears out sharp fronts.
+    Upwind,
+    /// A second order TVD scheme that limits the slope to the smaller of
+    /// both sides, which is the most diffusive of the limiters.
+    Minmod,
+    /// A second order TVD scheme with van Leer's smooth limiter, which keeps
+    /// fronts sharper than the minmod limiter.
+    VanLeer,
+}
+
+impl AdvectionScheme {
+    const ALL: [AdvectionScheme; 3] = [AdvectionScheme::Upwind,
+                                       AdvectionScheme::Minmod,
+                                       AdvectionScheme::VanLeer];
+
+    /// The limited slope of the values at the upwind cell, given the
+    /// differences to its neighbours against and along the flow.
+    fn limit<T>(self, backward: T, forward: T) -> T
+        where T: Float
+    {
+        if self == AdvectionScheme::Upwind || backward * forward <= T::zero() {
+            return T::zero();
+        }
+
+        match self {
+            AdvectionScheme::Minmod => {
+                if backward.abs() < forward.abs() {
+                    backward
+                } else {
+                    forward
+                }
+            }
+            _ => T::from_f64(2.0) * backward * forward / (backward + forward),
+        }
+    }
+}
+
+/// The advection-diffusion equation `du/dt = D laplacian(u) - div(v u)`,
+/// where the prescribed velocity `v` carries the quantity along, like heat
+/// in a flow. The advective fluxes are evaluated at the cell faces, so the
+/// total amount is conserved and the velocity should be free of divergence.
+/// Open sides of the field can use the `InflowOutflow` boundary condition.
+pub struct AdvectionDiffusionEquation<T, V>
+    where T: Float,
+          V: Velocity<T>
+{
+    pub diffusion: T,
+    pub velocity: V,
+    pub scheme: AdvectionScheme,
+}
+
+impl<T, V> AdvectionDiffusionEquation<T, V>
+    where T: Float,
+          V: Velocity<T>
+{
+    /// The advective flux through the face between the cell at `(x, y)` and
+    /// its neighbour in the direction of `step`, where `speed` is the velocity
+    /// through the face.
+    fn flux(&self,
+            field: &DataField<T>,
+            (x, y): (usize, usize),
+            (step_x, step_y): (usize, usize),
+            speed: T)
+            -> T {
+        let upper = (x + step_x, y + step_y);
+        let (upwind, downwind) = if speed >= T::zero() {
+            ((x, y), upper)
+        } else {
+            (upper, (x, y))
+        };
+        let value = field[upwind];
+        if self.scheme == AdvectionScheme::Upwind {
+            return speed * value;
+        }
+
+        let behind = if speed >= T::zero() {
+            (x - step_x, y - step_y)
+        } else {
+            (x + 2 * step_x, y + 2 * step_y)
+        };
+        let slope = self.scheme.limit(value - field[behind], field[downwind] - value);
+        speed * (value + slope / T::from_f64(2.0))
+    }
+}
+
+impl<T, V> DifferentialEquation<T> for AdvectionDiffusionEquation<T, V>
+    where T: Float,
+          V: Velocity<T>
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
+        let center = field[(x, y)];
+        let neighbours = field[(x - 1, y)] + field[(x + 1, y)] + field[(x, y - 1)] +
+                         field[(x, y + 1)];
+        let laplacian = (neighbours - T::from_f64(4.0) * center) / (h * h);
+
+        // The velocities at the faces are the averages of both cells
+        let two = T::from_f64(2.0);
+        let velocity = self.velocity.at((x, y));
+        let left = (self.velocity.at((x - 1, y)).0 + velocity.0) / two;
+        let right = (self.velocity.at((x + 1, y)).0 + velocity.0) / two;
+        let up = (self.velocity.at((x, y - 1)).1 + velocity.1) / two;
+        let down = (self.velocity.at((x, y + 1)).1 + velocity.1) / two;
+
+        let divergence = self.flux(field, (x, y), (1, 0), right) -
+                         self.flux(field, (x - 1, y), (1, 0), left) +
+                         self.flux(field, (x, y), (0, 1), down) -
+                         self.flux(field, (x, y - 1), (0, 1), up);
+
+        self.diffusion * laplacian - divergence / h
+    }
+
+    fn diffusion_coefficient(&self) -> Option<T> {
+        Some(self.diffusion)
+    }
+
+    /// Falls back to the limit of the diffusion if the velocity doesn't know
+    /// its largest speed.
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        // The limited schemes only stay TVD up to half the Courant number
+        let courant = if self.scheme == AdvectionScheme::Upwind {
+            T::one()
+        } else {
+            T::from_f64(0.5)
+        };
+        let advection = match self.velocity.max_speed() {
+            Some((u, v)) => (u + v) / (courant * h),
+            None => T::zero(),
+        };
+        let rate = advection + T::from_f64(4.0) * self.diffusion / (h * h);
+
+        if rate > T::zero() {
+            Some(T::one() / rate)
+        } else {
+            None
+        }
+    }
+
+    fn ghost_layers(&self) -> usize {
+        if self.scheme == AdvectionScheme::Upwind {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Writes the diffusion coefficient and the scheme. The scheme can't be
+    /// changed by loading a state, as it determines the ghost layers of the
+    /// fields, so `load_state` only checks it.
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "AdvectionDiffusion")?;
+        self.diffusion.write_to(writer)?;
+        let index = AdvectionScheme::ALL.iter().position(|&scheme| scheme == self.scheme);
+        write_u32(writer, index.unwrap() as u32)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "AdvectionDiffusion")?;
+        let diffusion = T::read_from(reader)?;
+        let index = read_u32(reader)? as usize;
+        match AdvectionScheme::ALL.get(index) {
+            Some(&scheme) if scheme == self.scheme => {}
+            Some(scheme) => {
+                return Err(invalid_data(format!("The checkpoint uses the {:?} scheme, \
+                                                 expected {:?}",
+                                                scheme,
+                                                self.scheme)))
+            }
+            None => return Err(invalid_data(format!("Unknown advection scheme {}", index))),
+        }
+        self.diffusion = diffusion;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DataField;
+    use DifferentialEquation;
+    use velocity::{AnalyticVelocity, Velocity, VelocityField};
+    use super::{AdvectionDiffusionEquation, AdvectionScheme};
+
+    fn equation<V>(velocity: V, diffusion: f64) -> AdvectionDiffusionEquation<f64, V>
+        where V: Velocity<f64>
+    {
+        AdvectionDiffusionEquation {
+            diffusion,
+            velocity,
+            scheme: AdvectionScheme::Upwind,
+        }
+    }
+
+    #[test]
+    fn unknown_speeds_fall_back_to_the_diffusion_limit() {
+        let vortex = |(x, y): (usize, usize)| (y as f64 - 5.0, 5.0 - x as f64);
+        let unknown = AnalyticVelocity {
+            velocity: vortex,
+            max_speed: None,
+        };
+        assert_eq!(equation(unknown, 0.5).max_stable_time_step(1.0), Some(0.5));
+
+        let known = AnalyticVelocity {
+            velocity: vortex,
+            max_speed: Some((5.0, 5.0)),
+        };
+        assert_eq!(equation(known, 0.5).max_stable_time_step(1.0), Some(1.0 / 12.0));
+
+        let still = AnalyticVelocity {
+            velocity: vortex,
+            max_speed: None,
+        };
+        assert_eq!(equation(still, 0.0).max_stable_time_step(1.0), None);
+    }
+
+    #[test]
+    fn velocity_fields_know_their_largest_speed() {
+        let mut x = DataField::new((6, 5));
+        let mut y = DataField::new((6, 5));
+        x[(0, 4)] = -3.0;
+        x[(2, 2)] = 2.0;
+        y[(5, 0)] = 1.5;
+        y[(1, 1)] = -4.0;
+        let velocity = VelocityField::new(x, y);
+
+        assert_eq!(velocity.max_speed(), Some((3.0, 4.0)));
+        assert_eq!(equation(velocity, 0.0).max_stable_time_step(1.0), Some(1.0 / 7.0));
+    }
+
+    #[test]
+    fn checks_the_scheme_of_a_state() {
+        let still = |diffusion| {
+            let velocity = AnalyticVelocity {
+                velocity: |_| (0.0, 0.0),
+                max_speed: None,
+            };
+            equation(velocity, diffusion)
+        };
+        let mut state = Vec::new();
+        still(0.5).save_state(&mut state).unwrap();
+
+        let mut upwind = still(0.1);
+        upwind.load_state(&mut &state[..]).unwrap();
+        assert_eq!(upwind.diffusion, 0.5);
+
+        let mut van_leer = still(0.1);
+        van_leer.scheme = AdvectionScheme::VanLeer;
+        let error = van_leer.load_state(&mut &state[..]).unwrap_err();
+        assert_eq!(error.to_string(), "The checkpoint uses the Upwind scheme, expected VanLeer");
+        assert_eq!(van_leer.diffusion, 0.1);
+    }
+}
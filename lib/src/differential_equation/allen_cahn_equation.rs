@@ -0,0 +1,128 @@
+use std::io::{self, Read, Write};
+use checkpoint;
+use DataField;
+use DifferentialEquation;
+use Float;
+use differential_equation::FreeEnergy;
+use geometry::Circle;
+use precondition::ShapePreCondition;
+
+/// The Allen-Cahn equation `dphi/dt = -L (f'(phi) - kappa laplacian(phi))`
+/// for a non-conserved order parameter, whose interfaces move with their
+/// mean curvature. Unlike the Cahn-Hilliard equation, the amount of each
+/// phase changes over time.
+pub struct AllenCahnEquation<T, F>
+    where T: Float,
+          F: FreeEnergy<T>
+{
+    /// The relaxation rate `L` of the order parameter.
+    pub mobility: T,
+    /// The gradient energy coefficient, which sets the width of the
+    /// interfaces.
+    pub kappa: T,
+    pub free_energy: F,
+}
+
+impl<T, F> AllenCahnEquation<T, F>
+    where T: Float,
+          F: FreeEnergy<T>
+{
+    /// A disk of the phase 1 in the middle of a field of the phase 0 with a
+    /// radius of a quarter of the field, which shrinks until it vanishes.
+    pub fn reference_condition(&self,
+                               (nx, ny): (usize, usize))
+                               -> ShapePreCondition<Circle, T> {
+        ShapePreCondition {
+            shape: Circle {
+                center: ((nx / 2) as f64, (ny / 2) as f64),
+                radius: (nx.min(ny) / 4) as f64,
+            },
+            inside: T::one(),
+            outside: T::zero(),
+        }
+    }
+}
+
+impl<T, F> DifferentialEquation<T> for AllenCahnEquation<T, F>
+    where T: Float,
+          F: FreeEnergy<T>
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
+        let center = field[(x, y)];
+        let neighbours = field[(x - 1, y)] + field[(x + 1, y)] + field[(x, y - 1)] +
+                         field[(x, y + 1)];
+        let laplacian = (neighbours - T::from_f64(4.0) * center) / (h * h);
+
+        -self.mobility * (self.free_energy.derivative(center) - self.kappa * laplacian)
+    }
+
+    fn diffusion_coefficient(&self) -> Option<T> {
+        Some(self.mobility * self.kappa)
+    }
+
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        let curvature = self.free_energy.max_curvature();
+        let curvature = if curvature > T::zero() {
+            curvature
+        } else {
+            T::zero()
+        };
+        let eigenvalue = self.mobility *
+                         (T::from_f64(8.0) * self.kappa / (h * h) + curvature);
+
+        Some(T::from_f64(2.0) / eigenvalue)
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "AllenCahn")?;
+        self.mobility.write_to(writer)?;
+        self.kappa.write_to(writer)?;
+        self.free_energy.save_state(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "AllenCahn")?;
+        self.mobility = T::read_from(reader)?;
+        self.kappa = T::read_from(reader)?;
+        self.free_energy.load_state(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DifferentialEquation;
+    use differential_equation::{CahnHilliardEquation, DoubleWell};
+    use super::AllenCahnEquation;
+
+    #[test]
+    fn restores_the_free_energy_with_the_state() {
+        let equation = AllenCahnEquation {
+            mobility: 2.0,
+            kappa: 0.5,
+            free_energy: DoubleWell { height: 3.0 },
+        };
+        let mut state = Vec::new();
+        equation.save_state(&mut state).unwrap();
+
+        let mut restored = AllenCahnEquation {
+            mobility: 1.0,
+            kappa: 1.0,
+            free_energy: DoubleWell { height: 1.0 },
+        };
+        let mut reader = &state[..];
+        restored.load_state(&mut reader).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!((restored.mobility, restored.kappa), (2.0, 0.5));
+        assert_eq!(restored.free_energy.height, 3.0);
+
+        // The state of a Cahn-Hilliard equation has the same values
+        let cahn_hilliard = CahnHilliardEquation {
+            mobility: 2.0,
+            kappa: 0.5,
+            free_energy: DoubleWell { height: 3.0 },
+        };
+        let mut state = Vec::new();
+        cahn_hilliard.save_state(&mut state).unwrap();
+        assert!(restored.load_state(&mut &state[..]).is_err());
+    }
+}
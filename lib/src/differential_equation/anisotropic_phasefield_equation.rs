@@ -0,0 +1,218 @@
+use std::io::{self, Read, Write};
+use checkpoint::{self, read_u64, write_u64};
+use DataField;
+use DifferentialEquation;
+use Float;
+
+/// The phase field equation with a gradient energy that depends on the
+/// orientation of the interface, as in Kobayashi's model of dendritic
+/// growth. The gradient energy coefficient `gamma` gets scaled by
+/// `(1 + anisotropy * cos(symmetry * (angle - orientation)))^2`, where
+/// `angle` is the direction of the gradient. Anisotropies above
+/// `1 / (symmetry^2 - 1)` lead to corners with missing orientations.
+pub struct AnisotropicPhaseFieldEquation<T>
+    where T: Float
+{
+    pub t: T,
+    pub gamma: T,
+    pub tau: T,
+    pub epsilon: T,
+    pub tm: T,
+    pub la: T,
+    pub anisotropy: T,
+    pub symmetry: usize,
+    /// The angle of the preferred growth direction in radians.
+    pub orientation: T,
+}
+
+impl<T> AnisotropicPhaseFieldEquation<T>
+    where T: Float
+{
+    /// The gradient energy flux `sigma^2 grad + sigma sigma' rotated grad`
+    /// for a gradient at a cell face, with the anisotropy `sigma`.
+    fn flux(&self, (gradient_x, gradient_y): (T, T)) -> (T, T) {
+        let angle = gradient_y.to_f64().atan2(gradient_x.to_f64());
+        let symmetry = self.symmetry as f64;
+        let phase = symmetry * (angle - self.orientation.to_f64());
+        let anisotropy = self.anisotropy.to_f64();
+        let sigma = 1.0 + anisotropy * phase.cos();
+        let derivative = -anisotropy * symmetry * phase.sin();
+
+        let (square, product) = (T::from_f64(sigma * sigma), T::from_f64(sigma * derivative));
+        (square * gradient_x - product * gradient_y, square * gradient_y + product * gradient_x)
+    }
+}
+
+impl<T> DifferentialEquation<T> for AnisotropicPhaseFieldEquation<T>
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
+        let center = field[(x, y)];
+        let left = field[(x - 1, y)];
+        let right = field[(x + 1, y)];
+        let up = field[(x, y - 1)];
+        let down = field[(x, y + 1)];
+        let up_left = field[(x - 1, y - 1)];
+        let up_right = field[(x + 1, y - 1)];
+        let down_left = field[(x - 1, y + 1)];
+        let down_right = field[(x + 1, y + 1)];
+
+        let center2 = center * center;
+        let center3 = center2 * center;
+
+        let l = self.la * (self.tm - self.t) / self.tm;
+
+        let two = T::from_f64(2.0);
+        let three = T::from_f64(3.0);
+        let four = T::from_f64(4.0);
+        let six = T::from_f64(6.0);
+        let eighteen = T::from_f64(18.0);
+
+        // The gradients at the faces, where the tangential derivatives are
+        // averaged over both neighbouring cells
+        let left_flux = self.flux(((center - left) / h,
+                                   (down + down_left - up - up_left) / (four * h)));
+        let right_flux = self.flux(((right - center) / h,
+                                    (down + down_right - up - up_right) / (four * h)));
+        let up_flux = self.flux(((right + up_right - left - up_left) / (four * h),
+                                 (center - up) / h));
+        let down_flux = self.flux(((right + down_right - left - down_left) / (four * h),
+                                   (down - center) / h));
+        let divergence = (right_flux.0 - left_flux.0 + down_flux.1 - up_flux.1) / h;
+
+        let a = two * self.gamma * divergence;
+        let w = eighteen / (self.epsilon * self.epsilon) * self.gamma *
+                (two * center3 - three * center2 + center);
+        let f = l / self.epsilon * six * center * (T::one() - center);
+
+        (a - w + f) / self.tau
+    }
+
+    fn diffusion_coefficient(&self) -> Option<T> {
+        Some(T::from_f64(2.0) * self.gamma / self.tau)
+    }
+
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        // The gradient energy is at most stiffer by the largest anisotropy
+        // factor and its derivative
+        let l = self.la * (self.tm - self.t) / self.tm;
+        let sigma = T::one() + self.anisotropy.abs();
+        let derivative = self.anisotropy.abs() * T::from_f64(self.symmetry as f64);
+        let diffusion = T::from_f64(16.0) * self.gamma * sigma * (sigma + derivative) / (h * h);
+        let reaction = T::from_f64(18.0) * self.gamma / (self.epsilon * self.epsilon) +
+                       T::from_f64(6.0) * l.abs() / self.epsilon;
+
+        Some(T::from_f64(2.0) * self.tau / (diffusion + reaction))
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "AnisotropicPhaseField")?;
+        for &parameter in &[self.t,
+                            self.gamma,
+                            self.tau,
+                            self.epsilon,
+                            self.tm,
+                            self.la,
+                            self.anisotropy,
+                            self.orientation] {
+            parameter.write_to(writer)?;
+        }
+        write_u64(writer, self.symmetry as u64)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "AnisotropicPhaseField")?;
+        for parameter in &mut [&mut self.t,
+                               &mut self.gamma,
+                               &mut self.tau,
+                               &mut self.epsilon,
+                               &mut self.tm,
+                               &mut self.la,
+                               &mut self.anisotropy,
+                               &mut self.orientation] {
+            **parameter = T::read_from(reader)?;
+        }
+        self.symmetry = read_u64(reader)? as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use boundary_condition::Neumann;
+    use BoundaryConditions;
+    use Solver;
+    use super::AnisotropicPhaseFieldEquation;
+
+    const SIZE: usize = 61;
+    const CENTER: usize = SIZE / 2;
+    const AXES: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    const DIAGONALS: [(isize, isize); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+    /// Grows a round seed in the middle of the field and returns the phase
+    /// field.
+    fn grow(anisotropy: f64, orientation: f64) -> Vec<Vec<f64>> {
+        let equation = AnisotropicPhaseFieldEquation {
+            t: 0.5,
+            gamma: 1.0,
+            tau: 2.0,
+            epsilon: 3.0,
+            tm: 1.0,
+            la: 1.0,
+            anisotropy,
+            symmetry: 4,
+            orientation,
+        };
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver = Solver::new(equation, boundary_conditions, (SIZE, SIZE), 0.1, 1.0);
+        for ((x, y), cell) in solver.get_field_mut().iter_mut() {
+            let (dx, dy) = (x as f64 - CENTER as f64, y as f64 - CENTER as f64);
+            *cell = if dx * dx + dy * dy < 36.0 { 1.0 } else { 0.0 };
+        }
+        solver.run_steps(400);
+
+        (0..SIZE).map(|y| (0..SIZE).map(|x| solver[(x, y)]).collect()).collect()
+    }
+
+    /// The distance from the centre to the interface in the direction.
+    fn radius(phase: &[Vec<f64>], (dx, dy): (isize, isize)) -> f64 {
+        let step = ((dx * dx + dy * dy) as f64).sqrt();
+        let value = |distance: isize| {
+            let x = CENTER as isize + distance * dx;
+            let y = CENTER as isize + distance * dy;
+            phase[y as usize][x as usize]
+        };
+        let mut distance = 0;
+        while value(distance + 1) >= 0.5 {
+            distance += 1;
+        }
+        let (inside, outside) = (value(distance), value(distance + 1));
+        (distance as f64 + (inside - 0.5) / (inside - outside)) * step
+    }
+
+    /// Returns the radius along the axes and along the diagonals, which
+    /// have to be the same in all four directions.
+    fn radii(phase: &[Vec<f64>]) -> (f64, f64) {
+        let mut radii = [0.0; 2];
+        for (first, directions) in radii.iter_mut().zip(&[AXES, DIAGONALS]) {
+            *first = radius(phase, directions[0]);
+            for &direction in &directions[1..] {
+                assert!((radius(phase, direction) - *first).abs() < 1e-9);
+            }
+        }
+        (radii[0], radii[1])
+    }
+
+    #[test]
+    fn grows_fastest_along_the_symmetry_axes() {
+        let (axis, diagonal) = radii(&grow(0.0, 0.0));
+        assert!((axis - diagonal).abs() < 0.5, "{} {}", axis, diagonal);
+
+        let (axis, diagonal) = radii(&grow(0.05, 0.0));
+        assert!(axis > diagonal + 1.0, "{} {}", axis, diagonal);
+
+        let (axis, diagonal) = radii(&grow(0.05, PI / 4.0));
+        assert!(diagonal > axis + 1.0, "{} {}", axis, diagonal);
+    }
+}
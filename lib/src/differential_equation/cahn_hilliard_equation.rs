@@ -0,0 +1,209 @@
+use std::io::{self, Read, Write};
+use checkpoint;
+use DataField;
+use DifferentialEquation;
+use Float;
+
+/// The bulk free energy density of a mixture with the concentration `c`.
+pub trait FreeEnergy<T>
+    where T: Float
+{
+    fn energy(&self, c: T) -> T;
+
+    /// The derivative of the energy with respect to the concentration.
+    fn derivative(&self, c: T) -> T;
+
+    /// The largest second derivative of the energy, which limits the stable
+    /// time step along with the gradient energy.
+    fn max_curvature(&self) -> T;
+
+    /// Writes the parameters of the energy into a checkpoint.
+    fn save_state(&self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Restores the parameters written by `save_state`.
+    fn load_state(&mut self, _reader: &mut dyn Read) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The double well `height * c^2 (1 - c)^2`, which separates the mixture
+/// into phases with the concentrations 0 and 1.
+pub struct DoubleWell<T>
+    where T: Float
+{
+    pub height: T,
+}
+
+impl<T> FreeEnergy<T> for DoubleWell<T>
+    where T: Float
+{
+    fn energy(&self, c: T) -> T {
+        let product = c * (T::one() - c);
+        self.height * product * product
+    }
+
+    fn derivative(&self, c: T) -> T {
+        let two = T::from_f64(2.0);
+        two * self.height * c * (T::one() - c) * (T::one() - two * c)
+    }
+
+    fn max_curvature(&self) -> T {
+        // Reached in the minima at 0 and 1
+        T::from_f64(2.0) * self.height
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "DoubleWell")?;
+        self.height.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "DoubleWell")?;
+        self.height = T::read_from(reader)?;
+        Ok(())
+    }
+}
+
+/// The Cahn-Hilliard equation `dc/dt = M laplacian(mu)` with the chemical
+/// potential `mu = f'(c) - kappa laplacian(c)`, which describes spinodal
+/// decomposition. The concentration is conserved, as long as the boundary
+/// conditions don't let anything flow out of the field. As the chemical
+/// potential of the neighbours is needed as well, the stencil reaches two
+/// cells beyond the field.
+pub struct CahnHilliardEquation<T, F>
+    where T: Float,
+          F: FreeEnergy<T>
+{
+    pub mobility: T,
+    /// The gradient energy coefficient, which sets the width of the
+    /// interfaces between the phases.
+    pub kappa: T,
+    pub free_energy: F,
+}
+
+impl<T, F> CahnHilliardEquation<T, F>
+    where T: Float,
+          F: FreeEnergy<T>
+{
+    fn chemical_potential(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
+        let center = field[(x, y)];
+        let neighbours = field[(x - 1, y)] + field[(x + 1, y)] + field[(x, y - 1)] +
+                         field[(x, y + 1)];
+        let laplacian = (neighbours - T::from_f64(4.0) * center) / (h * h);
+
+        self.free_energy.derivative(center) - self.kappa * laplacian
+    }
+}
+
+impl<T, F> DifferentialEquation<T> for CahnHilliardEquation<T, F>
+    where T: Float,
+          F: FreeEnergy<T>
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
+        let center = self.chemical_potential(field, (x, y), h);
+        let neighbours = self.chemical_potential(field, (x - 1, y), h) +
+                         self.chemical_potential(field, (x + 1, y), h) +
+                         self.chemical_potential(field, (x, y - 1), h) +
+                         self.chemical_potential(field, (x, y + 1), h);
+
+        self.mobility * (neighbours - T::from_f64(4.0) * center) / (h * h)
+    }
+
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        // Largest eigenvalue of the linearized equation
+        let laplacian = T::from_f64(8.0) / (h * h);
+        let curvature = self.free_energy.max_curvature();
+        let curvature = if curvature > T::zero() {
+            curvature
+        } else {
+            T::zero()
+        };
+        let eigenvalue = self.mobility * laplacian * (self.kappa * laplacian + curvature);
+
+        Some(T::from_f64(2.0) / eigenvalue)
+    }
+
+    fn ghost_layers(&self) -> usize {
+        2
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "CahnHilliard")?;
+        self.mobility.write_to(writer)?;
+        self.kappa.write_to(writer)?;
+        self.free_energy.save_state(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "CahnHilliard")?;
+        self.mobility = T::read_from(reader)?;
+        self.kappa = T::read_from(reader)?;
+        self.free_energy.load_state(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boundary_condition::Neumann;
+    use BoundaryConditions;
+    use DifferentialEquation;
+    use Solver;
+    use TimeIntegration;
+    use super::{CahnHilliardEquation, DoubleWell};
+
+    #[test]
+    fn neumann_boundaries_conserve_the_concentration() {
+        let equation = CahnHilliardEquation {
+            mobility: 1.0,
+            kappa: 0.5,
+            free_energy: DoubleWell { height: 1.0 },
+        };
+        let delta_t = 0.9 * equation.max_stable_time_step(1.0).unwrap();
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver = Solver::with_time_integration(equation,
+                                                       boundary_conditions,
+                                                       (20, 16),
+                                                       delta_t,
+                                                       1.0,
+                                                       TimeIntegration::ForwardEuler);
+        for ((x, y), cell) in solver.get_field_mut().iter_mut() {
+            *cell = 0.5 + 0.2 * (x as f64 * 1.7).sin() * (y as f64 * 2.3).cos();
+        }
+
+        let total = |solver: &Solver<_, _, _, _, _, _>| {
+            let mut total = 0.0;
+            for y in 2..14 {
+                for x in 2..18 {
+                    total += solver[(x, y)];
+                }
+            }
+            total
+        };
+        let initial = total(&solver);
+        let initial_corner = solver[(2, 2)];
+        solver.run_steps(500);
+
+        assert!((total(&solver) - initial).abs() < 1e-9);
+        // The mixture has to have started to separate
+        assert!((solver[(2, 2)] - initial_corner).abs() > 1e-3);
+    }
+
+    #[test]
+    fn the_state_includes_the_free_energy() {
+        let equation = |height| {
+            CahnHilliardEquation {
+                mobility: 1.0,
+                kappa: 0.5,
+                free_energy: DoubleWell { height },
+            }
+        };
+        let mut state = Vec::new();
+        equation(2.5).save_state(&mut state).unwrap();
+
+        let mut restored = equation(1.0);
+        restored.load_state(&mut &state[..]).unwrap();
+        assert_eq!(restored.free_energy.height, 2.5);
+    }
+}
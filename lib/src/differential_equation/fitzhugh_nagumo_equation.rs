@@ -0,0 +1,211 @@
+use std::f64::consts::PI;
+use std::io::{self, Read, Write};
+use checkpoint;
+use CoupledEquation;
+use DataField;
+use Float;
+use geometry::Rectangle;
+use precondition::ShapePreCondition;
+
+/// The FitzHugh-Nagumo model of an excitable medium, such as nerve or heart
+/// tissue, with the fast activator `u` and the slow inhibitor `v`:
+///
+/// `du/dt = du laplacian(u) + u - u^3 / 3 - v + current`
+///
+/// `dv/dt = dv laplacian(v) + epsilon (u + a - b v)`
+///
+/// Excitations travel through the medium as waves, which curl up into
+/// spirals when they are broken.
+pub struct FitzHughNagumoEquation<T>
+    where T: Float
+{
+    /// Shifts the nullcline of the inhibitor.
+    pub a: T,
+    /// The rate at which the inhibitor decays.
+    pub b: T,
+    /// The ratio of the time scales of the inhibitor and the activator.
+    pub epsilon: T,
+    /// The diffusion coefficient of the activator.
+    pub du: T,
+    /// The diffusion coefficient of the inhibitor.
+    pub dv: T,
+    /// The external stimulus applied to the activator everywhere.
+    pub current: T,
+}
+
+impl<T> FitzHughNagumoEquation<T>
+    where T: Float
+{
+    /// Creates an excitable medium with the classic parameters and a
+    /// stationary inhibitor.
+    pub fn new() -> Self {
+        FitzHughNagumoEquation {
+            a: T::from_f64(0.7),
+            b: T::from_f64(0.8),
+            epsilon: T::from_f64(0.08),
+            du: T::one(),
+            dv: T::zero(),
+            current: T::zero(),
+        }
+    }
+
+    /// The homogeneous resting state, where both nullclines intersect. If
+    /// they intersect three times, it's the state with the lowest activator.
+    pub fn resting_state(&self) -> (T, T) {
+        let (a, b, current) = (self.a.to_f64(), self.b.to_f64(), self.current.to_f64());
+
+        let u = if b == 0.0 {
+            -a
+        } else {
+            // The intersection solves the depressed cubic u^3 + p u + q = 0
+            let (p, q) = (3.0 / b - 3.0, 3.0 * (a / b - current));
+            let discriminant = q * q / 4.0 + p * p * p / 27.0;
+            if discriminant >= 0.0 {
+                let root = discriminant.sqrt();
+                (-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt()
+            } else {
+                // Three real roots, which only happens for negative p
+                let radius = 2.0 * (-p / 3.0).sqrt();
+                let angle = (3.0 * q / (p * radius)).clamp(-1.0, 1.0).acos();
+                radius * (angle / 3.0 - 4.0 * PI / 3.0).cos()
+            }
+        };
+
+        (T::from_f64(u), T::from_f64(u - u * u * u / 3.0 + current))
+    }
+
+    /// The resting state with an excited quarter of the field next to a
+    /// refractory quarter, so that the wave front has a free end, which
+    /// curls up into a spiral. With the default parameters, the spiral needs
+    /// a field that is a few hundred units long, or it drifts out of the
+    /// field and the medium returns to rest.
+    pub fn reference_conditions(&self,
+                                (nx, ny): (usize, usize))
+                                -> Vec<(&'static str, ShapePreCondition<Rectangle, T>)> {
+        let (u, v) = self.resting_state();
+
+        vec![("u",
+              ShapePreCondition {
+                  shape: Rectangle {
+                      position: (0, 0),
+                      size: (nx / 2, ny / 2),
+                  },
+                  inside: T::from_f64(2.0),
+                  outside: u,
+              }),
+             ("v",
+              ShapePreCondition {
+                  shape: Rectangle {
+                      position: (0, ny / 2),
+                      size: (nx / 2, ny - ny / 2),
+                  },
+                  inside: T::from_f64(2.0),
+                  outside: v,
+              })]
+    }
+}
+
+impl<T> Default for FitzHughNagumoEquation<T>
+    where T: Float
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CoupledEquation<T> for FitzHughNagumoEquation<T>
+    where T: Float
+{
+    fn field_names(&self) -> Vec<String> {
+        vec!["u".to_string(), "v".to_string()]
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       field: usize,
+                       (x, y): (usize, usize),
+                       h: T)
+                       -> T {
+        let values = &fields[field];
+        let neighbours = values[(x - 1, y)] + values[(x + 1, y)] + values[(x, y - 1)] +
+                         values[(x, y + 1)];
+        let laplacian = (neighbours - T::from_f64(4.0) * values[(x, y)]) / (h * h);
+
+        let (u, v) = (fields[0][(x, y)], fields[1][(x, y)]);
+
+        if field == 0 {
+            self.du * laplacian + u - u * u * u / T::from_f64(3.0) - v + self.current
+        } else {
+            self.dv * laplacian + self.epsilon * (u + self.a - self.b * v)
+        }
+    }
+
+    /// The limit of the diffusion together with the decay of the reactions,
+    /// assuming the activator stays within [-2, 2], as it does for the
+    /// classic parameters.
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        let laplacian = T::from_f64(8.0) / (h * h);
+        let activator = self.du * laplacian + T::from_f64(3.0);
+        let inhibitor = self.dv * laplacian + (self.epsilon * self.b).abs();
+        let rate = if activator > inhibitor {
+            activator
+        } else {
+            inhibitor
+        };
+
+        Some(T::from_f64(2.0) / rate)
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "FitzHughNagumo")?;
+        for value in &[self.a, self.b, self.epsilon, self.du, self.dv, self.current] {
+            value.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "FitzHughNagumo")?;
+        self.a = T::read_from(reader)?;
+        self.b = T::read_from(reader)?;
+        self.epsilon = T::read_from(reader)?;
+        self.du = T::read_from(reader)?;
+        self.dv = T::read_from(reader)?;
+        self.current = T::read_from(reader)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FitzHughNagumoEquation;
+
+    fn resting_state(a: f64, b: f64, current: f64) -> (f64, f64) {
+        let mut equation = FitzHughNagumoEquation::new();
+        equation.a = a;
+        equation.b = b;
+        equation.current = current;
+        let (u, v) = equation.resting_state();
+
+        // Both nullclines have to go through the state
+        assert!((u - u * u * u / 3.0 - v + current).abs() < 1e-12);
+        assert!((u + a - b * v).abs() < 1e-12);
+        (u, v)
+    }
+
+    #[test]
+    fn resting_state_lies_on_both_nullclines() {
+        resting_state(0.7, 0.8, 0.0);
+        resting_state(0.7, 0.8, 0.5);
+        resting_state(-0.3, 2.0, 0.2);
+        assert_eq!(resting_state(0.0, 1.0, 0.0), (0.0, 0.0));
+        assert_eq!(resting_state(0.5, 0.0, 0.0).0, -0.5);
+    }
+
+    #[test]
+    fn resting_state_has_the_lowest_activator_of_three() {
+        // The nullclines intersect at 0 and +-sqrt(3/2)
+        let (u, _) = resting_state(0.0, 2.0, 0.0);
+        assert!((u + 1.5f64.sqrt()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{self, Read, Write};
+use checkpoint;
+use CoupledEquation;
+use DataField;
+use Float;
+use geometry::Rectangle;
+use precondition::ShapePreCondition;
+
+/// The Gray-Scott model of the autocatalytic reaction `U + 2V -> 3V`, where
+/// `U` gets fed into the system and `V` gets removed from it:
+///
+/// `du/dt = du laplacian(u) - u v^2 + feed (1 - u)`
+///
+/// `dv/dt = dv laplacian(v) + u v^2 - (feed + kill) v`
+///
+/// Depending on the feed and kill rates, the concentrations form spots,
+/// stripes or labyrinths.
+pub struct GrayScottEquation<T>
+    where T: Float
+{
+    /// The diffusion coefficient of `u`.
+    pub du: T,
+    /// The diffusion coefficient of `v`.
+    pub dv: T,
+    /// The rate at which `u` gets replenished.
+    pub feed: T,
+    /// The rate at which `v` decays in addition to the feed rate.
+    pub kill: T,
+}
+
+impl<T> GrayScottEquation<T>
+    where T: Float
+{
+    /// Creates a model with Pearson's parameters for a labyrinth of stripes,
+    /// which fills the field within a few thousand time units with `h = 1`.
+    pub fn new() -> Self {
+        GrayScottEquation {
+            du: T::from_f64(0.16),
+            dv: T::from_f64(0.08),
+            feed: T::from_f64(0.037),
+            kill: T::from_f64(0.06),
+        }
+    }
+
+    /// The homogeneous steady state `u = 1, v = 0`, perturbed by a square in
+    /// the middle of the field, from which the pattern spreads.
+    pub fn reference_conditions(&self,
+                                (nx, ny): (usize, usize))
+                                -> Vec<(&'static str, ShapePreCondition<Rectangle, T>)> {
+        let size = (nx.min(ny) / 10).max(1);
+        let square = || {
+            Rectangle {
+                position: ((nx - size) / 2, (ny - size) / 2),
+                size: (size, size),
+            }
+        };
+
+        vec![("u",
+              ShapePreCondition {
+                  shape: square(),
+                  inside: T::from_f64(0.5),
+                  outside: T::one(),
+              }),
+             ("v",
+              ShapePreCondition {
+                  shape: square(),
+                  inside: T::from_f64(0.25),
+                  outside: T::zero(),
+              })]
+    }
+}
+
+impl<T> Default for GrayScottEquation<T>
+    where T: Float
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CoupledEquation<T> for GrayScottEquation<T>
+    where T: Float
+{
+    fn field_names(&self) -> Vec<String> {
+        vec!["u".to_string(), "v".to_string()]
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       field: usize,
+                       (x, y): (usize, usize),
+                       h: T)
+                       -> T {
+        let values = &fields[field];
+        let neighbours = values[(x - 1, y)] + values[(x + 1, y)] + values[(x, y - 1)] +
+                         values[(x, y + 1)];
+        let laplacian = (neighbours - T::from_f64(4.0) * values[(x, y)]) / (h * h);
+
+        let (u, v) = (fields[0][(x, y)], fields[1][(x, y)]);
+        let reaction = u * v * v;
+
+        if field == 0 {
+            self.du * laplacian - reaction + self.feed * (T::one() - u)
+        } else {
+            self.dv * laplacian + reaction - (self.feed + self.kill) * v
+        }
+    }
+
+    /// The limit of the diffusion together with the decay of the reactions,
+    /// as long as both concentrations stay within [0, 1].
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        let laplacian = T::from_f64(8.0) / (h * h);
+        let u = self.du * laplacian + T::one() + self.feed;
+        let v = self.dv * laplacian + self.feed + self.kill;
+        let rate = if u > v {
+            u
+        } else {
+            v
+        };
+
+        Some(T::from_f64(2.0) / rate)
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "GrayScott")?;
+        for value in &[self.du, self.dv, self.feed, self.kill] {
+            value.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "GrayScott")?;
+        self.du = T::read_from(reader)?;
+        self.dv = T::read_from(reader)?;
+        self.feed = T::read_from(reader)?;
+        self.kill = T::read_from(reader)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boundary_condition::Periodic;
+    use BoundaryConditions;
+    use CoupledSolver;
+    use TimeIntegration;
+    use TimeStepControl;
+    use super::GrayScottEquation;
+
+    #[test]
+    fn stays_within_bounds_at_the_stable_time_step() {
+        let equation = GrayScottEquation::new();
+        let conditions = equation.reference_conditions((40, 40));
+        let periodic = || BoundaryConditions::new(Periodic, Periodic, Periodic, Periodic).boxed();
+        let boundary_conditions = vec![periodic(), periodic()];
+        let mut solver = CoupledSolver::with_time_integration(equation,
+                                                              boundary_conditions,
+                                                              (40, 40),
+                                                              10.0,
+                                                              1.0,
+                                                              TimeIntegration::Heun);
+        solver.time_step_control = TimeStepControl::Stability { safety_factor: 1.0 };
+        for &(name, ref condition) in &conditions {
+            solver.execute_precondition(name, condition);
+        }
+        solver.run_steps(30);
+
+        for field in solver.get_fields() {
+            for y in 1..39 {
+                for x in 1..39 {
+                    assert!(field[(x, y)] >= 0.0 && field[(x, y)] <= 1.0);
+                }
+            }
+        }
+        let v = solver.get_field("v").unwrap();
+        assert!(v[(20, 20)] > 0.01, "The pattern has to have survived");
+    }
+}
@@ -1,11 +1,127 @@
 mod thermal_conduction;
 mod phasefield_equation;
+mod non_isothermal_phasefield_equation;
+mod anisotropic_phasefield_equation;
+mod multi_phasefield_equation;
+mod cahn_hilliard_equation;
+mod allen_cahn_equation;
+mod gray_scott_equation;
+mod fitzhugh_nagumo_equation;
+mod advection_diffusion_equation;
+mod wave_equation;
 
 pub use self::thermal_conduction::ThermalConduction;
 pub use self::phasefield_equation::PhaseFieldEquation;
+pub use self::non_isothermal_phasefield_equation::NonIsothermalPhaseFieldEquation;
+pub use self::anisotropic_phasefield_equation::AnisotropicPhaseFieldEquation;
+pub use self::multi_phasefield_equation::MultiPhaseFieldEquation;
+pub use self::cahn_hilliard_equation::{CahnHilliardEquation, DoubleWell, FreeEnergy};
+pub use self::allen_cahn_equation::AllenCahnEquation;
+pub use self::gray_scott_equation::GrayScottEquation;
+pub use self::fitzhugh_nagumo_equation::FitzHughNagumoEquation;
+pub use self::advection_diffusion_equation::{AdvectionDiffusionEquation, AdvectionScheme};
+pub use self::wave_equation::WaveEquation;
 
+use std::io::{self, Read, Write};
 use DataField;
+use DataField3D;
+use Float;
 
-pub trait DifferentialEquation {
-    fn solve(&self, field: &DataField, coord: (usize, usize), delta_t: f32, h: f32) -> f32;
+pub trait DifferentialEquation<T>
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField<T>, coord: (usize, usize), h: T) -> T;
+
+    fn solve(&self, field: &DataField<T>, coord: (usize, usize), delta_t: T, h: T) -> T {
+        field[coord] + delta_t * self.right_hand_side(field, coord, h)
+    }
+
+    fn diffusion_coefficient(&self) -> Option<T> {
+        None
+    }
+
+    fn max_stable_time_step(&self, _h: T) -> Option<T> {
+        None
+    }
+
+    /// The number of ghost layers the stencil reaches beyond the field,
+    /// which is 1 for the 5-point Laplacian.
+    fn ghost_layers(&self) -> usize {
+        1
+    }
+
+    /// Whether `right_hand_side` is the second time derivative of the field,
+    /// like the acceleration in the wave equation. These equations need the
+    /// `Leapfrog` time integration.
+    fn is_second_order(&self) -> bool {
+        false
+    }
+
+    /// Writes the parameters of the equation into a checkpoint. The built-in
+    /// equations start with an identifier written by `checkpoint::write_id`.
+    fn save_state(&self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Restores the parameters written by `save_state`.
+    fn load_state(&mut self, _reader: &mut dyn Read) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub trait DifferentialEquation3D<T>
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField3D<T>, coord: (usize, usize, usize), h: T) -> T;
+
+    fn solve(&self, field: &DataField3D<T>, coord: (usize, usize, usize), delta_t: T, h: T) -> T {
+        field[coord] + delta_t * self.right_hand_side(field, coord, h)
+    }
+}
+
+pub trait CoupledEquation<T>
+    where T: Float
+{
+    fn field_names(&self) -> Vec<String>;
+
+    /// The number of ghost layers the stencil reaches beyond the fields.
+    fn ghost_layers(&self) -> usize {
+        1
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       field: usize,
+                       coord: (usize, usize),
+                       h: T)
+                       -> T;
+
+    fn solve(&self,
+             fields: &[DataField<T>],
+             field: usize,
+             coord: (usize, usize),
+             delta_t: T,
+             h: T)
+             -> T {
+        fields[field][coord] + delta_t * self.right_hand_side(fields, field, coord, h)
+    }
+
+    /// The largest stable forward Euler step of the whole system.
+    fn max_stable_time_step(&self, _h: T) -> Option<T> {
+        None
+    }
+
+    /// Moves the values of all fields at a cell back into the allowed states
+    /// after every step, like phase fractions that have to sum up to one.
+    fn constrain(&self, _values: &mut [T]) {}
+
+    /// Writes the parameters of the equation into a checkpoint.
+    fn save_state(&self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Restores the parameters written by `save_state`.
+    fn load_state(&mut self, _reader: &mut dyn Read) -> io::Result<()> {
+        Ok(())
+    }
 }
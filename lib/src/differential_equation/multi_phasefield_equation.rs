@@ -0,0 +1,267 @@
+use std::f64::consts::PI;
+use std::io::{self, Read, Write};
+use checkpoint;
+use CoupledEquation;
+use DataField;
+use Float;
+
+/// A multi phase field model with an obstacle potential, as used for grain
+/// growth in polycrystals. Every phase has its own field `phi<index>` with the
+/// fraction of the phase at each cell, and the fractions of a cell sum up to
+/// one. The phases interact in pairs, so that whatever one phase gains
+/// another phase loses.
+pub struct MultiPhaseFieldEquation<T>
+    where T: Float
+{
+    /// The width of the interfaces.
+    pub epsilon: T,
+    interface_energies: Vec<Vec<T>>,
+    mobilities: Vec<Vec<T>>,
+    /// The bulk energy of each phase. Phases with a lower energy grow at the
+    /// expense of the others.
+    pub bulk_energies: Vec<T>,
+}
+
+impl<T> MultiPhaseFieldEquation<T>
+    where T: Float
+{
+    /// Creates a model with the same interface energy and mobility between
+    /// all phases and without any bulk driving force.
+    pub fn new(phases: usize, epsilon: T, interface_energy: T, mobility: T) -> Self {
+        MultiPhaseFieldEquation::with_interfaces(epsilon,
+                                                 vec![vec![interface_energy; phases]; phases],
+                                                 vec![vec![mobility; phases]; phases])
+    }
+
+    /// Creates a model with individual interface energies and mobilities
+    /// between the phases and without any bulk driving force. Panics if the
+    /// matrices aren't square and symmetric.
+    pub fn with_interfaces(epsilon: T,
+                           interface_energies: Vec<Vec<T>>,
+                           mobilities: Vec<Vec<T>>)
+                           -> Self {
+        let phases = interface_energies.len();
+        assert!(is_symmetric(&interface_energies, phases),
+                "The interface energies need to be a symmetric matrix");
+        assert!(is_symmetric(&mobilities, phases),
+                "The mobilities need to be a symmetric matrix of the size of the interface \
+                 energies");
+        MultiPhaseFieldEquation {
+            epsilon,
+            interface_energies,
+            mobilities,
+            bulk_energies: vec![T::zero(); phases],
+        }
+    }
+
+    pub fn phases(&self) -> usize {
+        self.bulk_energies.len()
+    }
+
+    /// The symmetric matrix of the energies of the interfaces between each
+    /// pair of phases.
+    pub fn interface_energies(&self) -> &[Vec<T>] {
+        &self.interface_energies
+    }
+
+    /// The symmetric matrix of the mobilities of the interfaces between each
+    /// pair of phases.
+    pub fn mobilities(&self) -> &[Vec<T>] {
+        &self.mobilities
+    }
+
+    /// Whether the phase is present at the cell or at one of its neighbours.
+    /// Only these phases take part in the interactions at the cell.
+    fn is_active(&self, phi: &DataField<T>, (x, y): (usize, usize)) -> bool {
+        [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .iter()
+            .any(|&coord| phi[coord] > T::zero())
+    }
+
+    /// The variational derivative of the interface energy with respect to
+    /// the phase.
+    fn potential(&self, fields: &[DataField<T>], phase: usize, (x, y): (usize, usize), h: T) -> T {
+        let gradient_factor = self.epsilon * self.epsilon / T::from_f64(PI * PI);
+        let mut potential = T::zero();
+        for (other, phi) in fields.iter().enumerate() {
+            if other == phase {
+                continue;
+            }
+            let center = phi[(x, y)];
+            let neighbours = phi[(x - 1, y)] + phi[(x + 1, y)] + phi[(x, y - 1)] +
+                             phi[(x, y + 1)];
+            let laplacian = (neighbours - T::from_f64(4.0) * center) / (h * h);
+            let energy = T::from_f64(4.0) * self.interface_energies[phase][other] / self.epsilon;
+            potential = potential + energy * (gradient_factor * laplacian + center);
+        }
+        potential
+    }
+}
+
+impl<T> CoupledEquation<T> for MultiPhaseFieldEquation<T>
+    where T: Float
+{
+    fn field_names(&self) -> Vec<String> {
+        (0..self.phases()).map(|phase| format!("phi{}", phase)).collect()
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       field: usize,
+                       coord: (usize, usize),
+                       h: T)
+                       -> T {
+        if !self.is_active(&fields[field], coord) {
+            return T::zero();
+        }
+
+        let potential = self.potential(fields, field, coord, h);
+        let phi = fields[field][coord];
+        let driving_factor = T::from_f64(8.0 / PI);
+
+        let mut rate = T::zero();
+        let mut active = 0;
+        for (other, other_phi) in fields.iter().enumerate() {
+            if !self.is_active(other_phi, coord) {
+                continue;
+            }
+            active += 1;
+            if other == field {
+                continue;
+            }
+
+            let difference = potential - self.potential(fields, other, coord, h);
+            let driving_force = driving_factor * (phi * other_phi[coord]).abs().sqrt() *
+                                (self.bulk_energies[field] - self.bulk_energies[other]);
+            rate = rate - self.mobilities[field][other] * (difference + driving_force);
+        }
+
+        rate / T::from_f64(active as f64)
+    }
+
+    /// The limit of the diffusion of the interfaces between the pair of
+    /// phases that moves the fastest. With more than two phases at a cell,
+    /// the diffusion of a phase grows up to twice the diffusion of a single
+    /// interface.
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        let gradient_factor = self.epsilon * self.epsilon / T::from_f64(PI * PI);
+        let mut diffusion = T::zero();
+        for (energies, mobilities) in self.interface_energies.iter().zip(&self.mobilities) {
+            for (&energy, &mobility) in energies.iter().zip(mobilities) {
+                let pair = mobility * T::from_f64(4.0) * energy / self.epsilon * gradient_factor;
+                if pair > diffusion {
+                    diffusion = pair;
+                }
+            }
+        }
+
+        if diffusion > T::zero() {
+            Some(h * h / (T::from_f64(8.0) * diffusion))
+        } else {
+            None
+        }
+    }
+
+    fn constrain(&self, values: &mut [T]) {
+        // If every fraction ends up at zero, the phase that came the closest
+        // to remaining fills the cell
+        let mut largest = 0;
+        for (index, &value) in values.iter().enumerate() {
+            if value > values[largest] {
+                largest = index;
+            }
+        }
+
+        // The obstacle potential keeps the fractions within [0, 1]
+        let mut sum = T::zero();
+        for value in values.iter_mut() {
+            if *value < T::zero() {
+                *value = T::zero();
+            } else if *value > T::one() {
+                *value = T::one();
+            }
+            sum = sum + *value;
+        }
+
+        if sum > T::zero() {
+            for value in values.iter_mut() {
+                *value = *value / sum;
+            }
+        } else if !values.is_empty() {
+            values[largest] = T::one();
+        }
+    }
+
+    /// Writes the width of the interfaces and the energies and mobilities of
+    /// all phases. The number of phases is given by the fields.
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "MultiPhaseField")?;
+        self.epsilon.write_to(writer)?;
+        for row in self.interface_energies.iter().chain(&self.mobilities) {
+            for value in row {
+                value.write_to(writer)?;
+            }
+        }
+        for value in &self.bulk_energies {
+            value.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "MultiPhaseField")?;
+        self.epsilon = T::read_from(reader)?;
+        for row in self.interface_energies.iter_mut().chain(&mut self.mobilities) {
+            for value in row {
+                *value = T::read_from(reader)?;
+            }
+        }
+        for value in &mut self.bulk_energies {
+            *value = T::read_from(reader)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_symmetric<T>(matrix: &[Vec<T>], size: usize) -> bool
+    where T: Float
+{
+    matrix.len() == size && matrix.iter().all(|row| row.len() == size) &&
+    (0..size).all(|i| (0..i).all(|j| matrix[i][j] == matrix[j][i]))
+}
+
+#[cfg(test)]
+mod tests {
+    use CoupledEquation;
+    use super::MultiPhaseFieldEquation;
+
+    #[test]
+    fn constrain_normalizes_the_fractions() {
+        let equation = MultiPhaseFieldEquation::new(3, 4.0, 1.0, 1.0);
+        let mut values = [0.5, 1.5, -0.3];
+        equation.constrain(&mut values);
+        assert_eq!(values, [1.0 / 3.0, 2.0 / 3.0, 0.0]);
+    }
+
+    #[test]
+    fn constrain_keeps_the_largest_phase_if_all_vanish() {
+        let equation = MultiPhaseFieldEquation::new(3, 4.0, 1.0, 1.0);
+        let mut values = [-0.2, -0.05, -0.1];
+        equation.constrain(&mut values);
+        assert_eq!(values, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "symmetric")]
+    fn asymmetric_interface_energies_fail_at_construction() {
+        MultiPhaseFieldEquation::with_interfaces(4.0,
+                                                 vec![vec![1.0, 1.0], vec![2.0, 1.0]],
+                                                 vec![vec![1.0; 2]; 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "symmetric")]
+    fn mobilities_of_another_size_fail_at_construction() {
+        MultiPhaseFieldEquation::with_interfaces(4.0, vec![vec![1.0; 2]; 2], vec![vec![1.0; 3]; 3]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::io::{self, Read, Write};
+use checkpoint;
+use CoupledEquation;
+use DataField;
+use Float;
+
+const PHI: usize = 0;
+const TEMPERATURE: usize = 1;
+
+pub struct NonIsothermalPhaseFieldEquation<T>
+    where T: Float
+{
+    pub gamma: T,
+    pub tau: T,
+    pub epsilon: T,
+    pub tm: T,
+    pub la: T,
+    pub diffusivity: T,
+    pub latent_heat: T,
+}
+
+impl<T> NonIsothermalPhaseFieldEquation<T>
+    where T: Float
+{
+    fn phi_rate(&self, fields: &[DataField<T>], (x, y): (usize, usize), h: T) -> T {
+        let phi = &fields[PHI];
+        let center = phi[(x, y)];
+        let left = phi[(x - 1, y)];
+        let right = phi[(x + 1, y)];
+        let up = phi[(x, y - 1)];
+        let down = phi[(x, y + 1)];
+
+        let center2 = center * center;
+        let center3 = center2 * center;
+
+        let t = fields[TEMPERATURE][(x, y)];
+        let l = self.la * (self.tm - t) / self.tm;
+
+        let two = T::from_f64(2.0);
+        let three = T::from_f64(3.0);
+        let four = T::from_f64(4.0);
+        let six = T::from_f64(6.0);
+        let eighteen = T::from_f64(18.0);
+
+        let a = two * self.gamma * (up + down + left + right - four * center) / (h * h);
+        let w = eighteen / (self.epsilon * self.epsilon) * self.gamma *
+                (two * center3 - three * center2 + center);
+        let f = l / self.epsilon * six * center * (T::one() - center);
+
+        (a - w + f) / self.tau
+    }
+
+    fn temperature_rate(&self, fields: &[DataField<T>], (x, y): (usize, usize), h: T) -> T {
+        let t = &fields[TEMPERATURE];
+        let center = t[(x, y)];
+        let left = t[(x - 1, y)];
+        let right = t[(x + 1, y)];
+        let up = t[(x, y - 1)];
+        let down = t[(x, y + 1)];
+
+        let sum = left + right + up + down - T::from_f64(4.0) * center;
+
+        // The latent heat released by solidification feeds back into the temperature
+        self.diffusivity * sum / (h * h) + self.latent_heat * self.phi_rate(fields, (x, y), h)
+    }
+}
+
+impl<T> CoupledEquation<T> for NonIsothermalPhaseFieldEquation<T>
+    where T: Float
+{
+    fn field_names(&self) -> Vec<String> {
+        vec!["phi".to_string(), "t".to_string()]
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       field: usize,
+                       coord: (usize, usize),
+                       h: T)
+                       -> T {
+        if field == PHI {
+            self.phi_rate(fields, coord, h)
+        } else {
+            self.temperature_rate(fields, coord, h)
+        }
+    }
+
+    /// The limit of the phase field is the one of the isothermal equation with
+    /// the driving force at its largest, which it reaches at a temperature of
+    /// zero or twice the melting temperature. The temperature is limited by
+    /// its diffusion.
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        let diffusion = T::from_f64(16.0) * self.gamma / (h * h);
+        let reaction = T::from_f64(18.0) * self.gamma / (self.epsilon * self.epsilon) +
+                       T::from_f64(6.0) * self.la.abs() / self.epsilon;
+        let phi_limit = T::from_f64(2.0) * self.tau / (diffusion + reaction);
+        let temperature_limit = h * h / (T::from_f64(4.0) * self.diffusivity);
+
+        Some(if phi_limit < temperature_limit {
+            phi_limit
+        } else {
+            temperature_limit
+        })
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "NonIsothermalPhaseField")?;
+        for &parameter in &[self.gamma,
+                            self.tau,
+                            self.epsilon,
+                            self.tm,
+                            self.la,
+                            self.diffusivity,
+                            self.latent_heat] {
+            parameter.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "NonIsothermalPhaseField")?;
+        for parameter in &mut [&mut self.gamma,
+                               &mut self.tau,
+                               &mut self.epsilon,
+                               &mut self.tm,
+                               &mut self.la,
+                               &mut self.diffusivity,
+                               &mut self.latent_heat] {
+            **parameter = T::read_from(reader)?;
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,26 @@
+use std::io::{self, Read, Write};
+use checkpoint;
 use DataField;
+use DataField3D;
 use DifferentialEquation;
+use DifferentialEquation3D;
+use Float;
 
-pub struct PhaseFieldEquation {
-    pub t: f32,
-    pub gamma: f32,
-    pub tau: f32,
-    pub epsilon: f32,
-    pub tm: f32,
-    pub la: f32,
+pub struct PhaseFieldEquation<T>
+    where T: Float
+{
+    pub t: T,
+    pub gamma: T,
+    pub tau: T,
+    pub epsilon: T,
+    pub tm: T,
+    pub la: T,
 }
 
-impl DifferentialEquation for PhaseFieldEquation {
-    fn solve(&self, field: &DataField, (x, y): (usize, usize), delta_t: f32, h: f32) -> f32 {
+impl<T> DifferentialEquation<T> for PhaseFieldEquation<T>
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
         let center = field[(x, y)];
         let left = field[(x - 1, y)];
         let right = field[(x + 1, y)];
@@ -23,11 +32,84 @@ impl DifferentialEquation for PhaseFieldEquation {
 
         let l = self.la * (self.tm - self.t) / self.tm;
 
-        let a = 2.0 * self.gamma * (up + down + left + right - 4.0 * center) / (h * h);
-        let w = 18.0 / (self.epsilon * self.epsilon) * self.gamma *
-                (2.0 * center3 - 3.0 * center2 + center);
-        let f = l / self.epsilon * 6.0 * center * (1.0 - center);
+        let two = T::from_f64(2.0);
+        let three = T::from_f64(3.0);
+        let four = T::from_f64(4.0);
+        let six = T::from_f64(6.0);
+        let eighteen = T::from_f64(18.0);
 
-        center + delta_t / self.tau * (a - w + f)
+        let a = two * self.gamma * (up + down + left + right - four * center) / (h * h);
+        let w = eighteen / (self.epsilon * self.epsilon) * self.gamma *
+                (two * center3 - three * center2 + center);
+        let f = l / self.epsilon * six * center * (T::one() - center);
+
+        (a - w + f) / self.tau
+    }
+
+    fn diffusion_coefficient(&self) -> Option<T> {
+        Some(T::from_f64(2.0) * self.gamma / self.tau)
+    }
+
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        // Largest eigenvalues of the linearized diffusion and reaction terms
+        let l = self.la * (self.tm - self.t) / self.tm;
+        let diffusion = T::from_f64(16.0) * self.gamma / (h * h);
+        let reaction = T::from_f64(18.0) * self.gamma / (self.epsilon * self.epsilon) +
+                       T::from_f64(6.0) * l.abs() / self.epsilon;
+
+        Some(T::from_f64(2.0) * self.tau / (diffusion + reaction))
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "PhaseField")?;
+        for &parameter in &[self.t, self.gamma, self.tau, self.epsilon, self.tm, self.la] {
+            parameter.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "PhaseField")?;
+        for parameter in &mut [&mut self.t,
+                               &mut self.gamma,
+                               &mut self.tau,
+                               &mut self.epsilon,
+                               &mut self.tm,
+                               &mut self.la] {
+            **parameter = T::read_from(reader)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> DifferentialEquation3D<T> for PhaseFieldEquation<T>
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField3D<T>, (x, y, z): (usize, usize, usize), h: T) -> T {
+        let center = field[(x, y, z)];
+        let left = field[(x - 1, y, z)];
+        let right = field[(x + 1, y, z)];
+        let up = field[(x, y - 1, z)];
+        let down = field[(x, y + 1, z)];
+        let front = field[(x, y, z - 1)];
+        let back = field[(x, y, z + 1)];
+
+        let center2 = center * center;
+        let center3 = center2 * center;
+
+        let l = self.la * (self.tm - self.t) / self.tm;
+
+        let two = T::from_f64(2.0);
+        let three = T::from_f64(3.0);
+        let six = T::from_f64(6.0);
+        let eighteen = T::from_f64(18.0);
+
+        let a = two * self.gamma * (up + down + left + right + front + back - six * center) /
+                (h * h);
+        let w = eighteen / (self.epsilon * self.epsilon) * self.gamma *
+                (two * center3 - three * center2 + center);
+        let f = l / self.epsilon * six * center * (T::one() - center);
+
+        (a - w + f) / self.tau
     }
 }
@@ -1,18 +1,59 @@
+use std::io::{self, Read, Write};
+use checkpoint;
 use DataField;
+use DataField3D;
 use DifferentialEquation;
+use DifferentialEquation3D;
+use Float;
 
 pub struct ThermalConduction;
 
-impl DifferentialEquation for ThermalConduction {
-    fn solve(&self, field: &DataField, (x, y): (usize, usize), delta_t: f32, h: f32) -> f32 {
+impl<T> DifferentialEquation<T> for ThermalConduction
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
         let center = field[(x, y)];
         let left = field[(x - 1, y)];
         let right = field[(x + 1, y)];
         let up = field[(x, y - 1)];
         let down = field[(x, y + 1)];
 
-        let sum = left + right + up + down - 4.0 * center;
+        let sum = left + right + up + down - T::from_f64(4.0) * center;
 
-        center + delta_t * sum / (h * h)
+        sum / (h * h)
+    }
+
+    fn diffusion_coefficient(&self) -> Option<T> {
+        Some(T::one())
+    }
+
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        Some(h * h / T::from_f64(4.0))
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "ThermalConduction")
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "ThermalConduction")
+    }
+}
+
+impl<T> DifferentialEquation3D<T> for ThermalConduction
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField3D<T>, (x, y, z): (usize, usize, usize), h: T) -> T {
+        let center = field[(x, y, z)];
+        let left = field[(x - 1, y, z)];
+        let right = field[(x + 1, y, z)];
+        let up = field[(x, y - 1, z)];
+        let down = field[(x, y + 1, z)];
+        let front = field[(x, y, z - 1)];
+        let back = field[(x, y, z + 1)];
+
+        let sum = left + right + up + down + front + back - T::from_f64(6.0) * center;
+
+        sum / (h * h)
     }
 }
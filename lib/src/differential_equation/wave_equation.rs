@@ -0,0 +1,222 @@
+use std::io::{self, Read, Write};
+use checkpoint;
+use DataField;
+use DifferentialEquation;
+use Float;
+
+/// The wave equation `d^2u/dt^2 = speed^2 laplacian(u)`, like the
+/// displacement of a membrane or the pressure of sound. It's second order in
+/// time, so it needs the `Leapfrog` time integration. The `Absorbing`
+/// boundary condition lets waves leave the field.
+pub struct WaveEquation<T>
+    where T: Float
+{
+    /// The speed the waves travel with.
+    pub speed: T,
+}
+
+impl<T> DifferentialEquation<T> for WaveEquation<T>
+    where T: Float
+{
+    fn right_hand_side(&self, field: &DataField<T>, (x, y): (usize, usize), h: T) -> T {
+        let center = field[(x, y)];
+        let neighbours = field[(x - 1, y)] + field[(x + 1, y)] + field[(x, y - 1)] +
+                         field[(x, y + 1)];
+        let laplacian = (neighbours - T::from_f64(4.0) * center) / (h * h);
+
+        self.speed * self.speed * laplacian
+    }
+
+    fn max_stable_time_step(&self, h: T) -> Option<T> {
+        // The Courant number may be at most 1 / sqrt(2) in two dimensions
+        Some(h / (self.speed * T::from_f64(2.0).sqrt()))
+    }
+
+    fn is_second_order(&self) -> bool {
+        true
+    }
+
+    fn save_state(&self, writer: &mut dyn Write) -> io::Result<()> {
+        checkpoint::write_id(writer, "Wave")?;
+        self.speed.write_to(writer)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        checkpoint::read_id(reader, "Wave")?;
+        self.speed = T::read_from(reader)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boundary_condition::{Absorbing, Dirichlet, Neumann, Periodic};
+    use precondition::FieldPreCondition;
+    use BoundaryCondition;
+    use BoundaryConditions;
+    use DataField;
+    use Solver;
+    use TimeIntegration;
+    use super::WaveEquation;
+
+    /// The largest displacement left after a pulse travelling right along a
+    /// strip that is periodic in y hit the right boundary.
+    fn reflection<B>(boundary_condition: B) -> f64
+        where B: BoundaryCondition<f64> + Sync + Send
+    {
+        let dimensions = (122, 5);
+        let boundary_conditions =
+            BoundaryConditions::new(Periodic, Periodic, Neumann, boundary_condition);
+        let mut solver = Solver::with_time_integration(WaveEquation { speed: 1.0 },
+                                                       boundary_conditions,
+                                                       dimensions,
+                                                       0.5,
+                                                       1.0,
+                                                       TimeIntegration::Leapfrog);
+        let mut velocity = DataField::new(dimensions);
+        for ((x, _), cell) in velocity.iter_mut() {
+            let distance = (x as f64 - 80.0) / 5.0;
+            *cell = 2.0 * distance / 5.0 * (-distance * distance).exp();
+        }
+        for ((x, _), cell) in solver.get_field_mut().iter_mut() {
+            let distance = (x as f64 - 80.0) / 5.0;
+            *cell = (-distance * distance).exp();
+        }
+        solver.execute_velocity_precondition(&FieldPreCondition(velocity));
+        solver.run_steps(120);
+
+        let field = solver.get_field();
+        let mut displacement: f64 = 0.0;
+        for y in 1..dimensions.1 - 1 {
+            for x in 1..dimensions.0 - 1 {
+                displacement = displacement.max(field[(x, y)].abs());
+            }
+        }
+        displacement
+    }
+
+    #[test]
+    fn absorbing_boundaries_reflect_less_than_neumann() {
+        let neumann = reflection(Neumann);
+        let absorbing = reflection(Absorbing(1.0));
+        assert!(neumann > 0.5, "{}", neumann);
+        assert!(absorbing < 0.05 * neumann,
+                "absorbing {} vs neumann {}",
+                absorbing,
+                neumann);
+    }
+
+    /// The energy leapfrog conserves exactly between the time levels
+    /// `previous` and `current` of a field with fixed walls.
+    fn energy(previous: &[f64], current: &[f64], (nx, ny): (usize, usize), delta_t: f64) -> f64 {
+        let index = |x: usize, y: usize| x + y * nx;
+        let mut energy = 0.0;
+        for y in 0..ny {
+            for x in 0..nx {
+                let velocity = (current[index(x, y)] - previous[index(x, y)]) / delta_t;
+                energy += 0.5 * velocity * velocity;
+                // Each pair of neighbours gets counted once, including the
+                // walls at the zero ghost cells
+                for &(dx, dy) in &[(1, 0), (0, 1)] {
+                    let (ox, oy) = (x + dx, y + dy);
+                    let (current_other, previous_other) = if ox < nx && oy < ny {
+                        (current[index(ox, oy)], previous[index(ox, oy)])
+                    } else {
+                        (0.0, 0.0)
+                    };
+                    energy += 0.5 * (current[index(x, y)] - current_other) *
+                              (previous[index(x, y)] - previous_other);
+                }
+                if x == 0 {
+                    energy += 0.5 * current[index(x, y)] * previous[index(x, y)];
+                }
+                if y == 0 {
+                    energy += 0.5 * current[index(x, y)] * previous[index(x, y)];
+                }
+            }
+        }
+        energy
+    }
+
+    fn inner_values(field: &DataField<f64>) -> Vec<f64> {
+        let (nx, ny) = field.dimensions;
+        let mut values = Vec::new();
+        for y in 1..ny - 1 {
+            for x in 1..nx - 1 {
+                values.push(field[(x, y)]);
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn leapfrog_conserves_energy() {
+        let dimensions = (32, 26);
+        let delta_t = 0.5;
+        let boundary_conditions = BoundaryConditions::new(Dirichlet(0.0),
+                                                          Dirichlet(0.0),
+                                                          Dirichlet(0.0),
+                                                          Dirichlet(0.0));
+        let mut solver = Solver::with_time_integration(WaveEquation { speed: 1.0 },
+                                                       boundary_conditions,
+                                                       dimensions,
+                                                       delta_t,
+                                                       1.0,
+                                                       TimeIntegration::Leapfrog);
+        for ((x, y), cell) in solver.get_field_mut().iter_mut() {
+            let distance = (x as f64 - 12.0).powi(2) + (y as f64 - 14.0).powi(2);
+            *cell = (-distance / 8.0).exp();
+        }
+
+        let inner = (dimensions.0 - 2, dimensions.1 - 2);
+        let mut previous = inner_values(solver.get_field());
+        solver.run_steps(1);
+        let mut current = inner_values(solver.get_field());
+        let initial_energy = energy(&previous, &current, inner, delta_t);
+        assert!(initial_energy > 1.0);
+        for _ in 0..2000 {
+            solver.run_steps(1);
+            previous = current;
+            current = inner_values(solver.get_field());
+            let energy = energy(&previous, &current, inner, delta_t);
+            assert!((energy - initial_energy).abs() < 1e-9 * initial_energy,
+                    "the energy changed from {} to {}",
+                    initial_energy,
+                    energy);
+        }
+    }
+
+    #[test]
+    fn resumes_with_the_previous_time_level() {
+        let new_solver = || {
+            let boundary_conditions = BoundaryConditions::new(Absorbing(1.0),
+                                                              Neumann,
+                                                              Neumann,
+                                                              Absorbing(1.0));
+            Solver::with_time_integration(WaveEquation { speed: 1.0 },
+                                          boundary_conditions,
+                                          (20, 16),
+                                          0.5,
+                                          1.0,
+                                          TimeIntegration::Leapfrog)
+        };
+        let mut original = new_solver();
+        for ((x, y), cell) in original.get_field_mut().iter_mut() {
+            let distance = (x as f64 - 8.0).powi(2) + (y as f64 - 7.0).powi(2);
+            *cell = (-distance / 6.0).exp();
+        }
+        original.run_steps(7);
+        let mut bytes = Vec::new();
+        original.save_checkpoint(&mut bytes).unwrap();
+
+        // A truncated checkpoint leaves the solver unchanged
+        let mut resumed = new_solver();
+        assert!(resumed.load_checkpoint(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(resumed.steps(), 0);
+
+        resumed.load_checkpoint(&mut &bytes[..]).unwrap();
+        original.run_steps(10);
+        resumed.run_steps(10);
+        assert!(inner_values(resumed.get_field()) == inner_values(original.get_field()));
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+pub trait Float: Copy + PartialOrd + Debug + Send + Sync + 'static +
+                 Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> +
+                 Div<Output = Self> + Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn is_nan(self) -> bool;
+    fn write_to(self, writer: &mut dyn Write) -> io::Result<()>;
+    fn read_from(reader: &mut dyn Read) -> io::Result<Self>;
+}
+
+macro_rules! impl_float {
+    ($t:ty) => {
+        impl Float for $t {
+            fn zero() -> Self {
+                0.0
+            }
+
+            fn one() -> Self {
+                1.0
+            }
+
+            fn from_f64(value: f64) -> Self {
+                value as $t
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+
+            fn is_nan(self) -> bool {
+                <$t>::is_nan(self)
+            }
+
+            fn write_to(self, writer: &mut dyn Write) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+
+            fn read_from(reader: &mut dyn Read) -> io::Result<Self> {
+                let mut bytes = [0; mem::size_of::<$t>()];
+                reader.read_exact(&mut bytes)?;
+                Ok(<$t>::from_le_bytes(bytes))
+            }
+        }
+    }
+}
+
+impl_float!(f32);
+impl_float!(f64);
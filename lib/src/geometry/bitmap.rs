@@ -0,0 +1,62 @@
+use std::io::{self, Read};
+use png;
+use geometry::Shape;
+
+/// A shape given by the pixels of an image, which covers one cell per pixel
+/// starting at `position`.
+#[derive(Clone, Debug)]
+pub struct Bitmap {
+    pub position: (usize, usize),
+    pub width: usize,
+    pub height: usize,
+    /// Whether the pixels belong to the shape, stored row by row.
+    pub pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    /// Reads a PNG image, where the dark, opaque pixels belong to the shape.
+    pub fn read_png<R: Read>(reader: R, position: (usize, usize)) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(io::Error::other)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(io::Error::other)?;
+
+        let channels = info.color_type.samples();
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in buffer.chunks(info.line_size).take(height) {
+            for pixel in row.chunks(channels).take(width) {
+                let (brightness, alpha) = match *pixel {
+                    [grey] => (grey as u32, 255),
+                    [grey, alpha] => (grey as u32, alpha),
+                    [r, g, b] => ((r as u32 + g as u32 + b as u32) / 3, 255),
+                    [r, g, b, alpha] => ((r as u32 + g as u32 + b as u32) / 3, alpha),
+                    _ => (255, 0),
+                };
+                pixels.push(brightness < 128 && alpha >= 128);
+            }
+        }
+
+        Ok(Bitmap {
+            position,
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Shape for Bitmap {
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        let (left, top) = self.position;
+        x >= left && y >= top && x < left + self.width && y < top + self.height &&
+        self.pixels[x - left + self.width * (y - top)]
+    }
+
+    fn bounding_box(&self) -> ((f64, f64), (f64, f64)) {
+        let (left, top) = self.position;
+        ((left as f64, top as f64),
+         ((left + self.width) as f64 - 1.0, (top + self.height) as f64 - 1.0))
+    }
+}
@@ -0,0 +1,157 @@
+//! Cell types that turn parts of a field into obstacles or fixed values, and
+//! shapes to paint them with.
+
+mod bitmap;
+mod shape;
+
+use std::ops::{Index, IndexMut};
+use DataField;
+use Float;
+
+pub use self::bitmap::Bitmap;
+pub use self::shape::{Circle, Polygon, Rectangle, Shape};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellType<T>
+    where T: Float
+{
+    /// Gets solved by the differential equation.
+    Fluid,
+    /// Keeps its value, which its neighbours see like any other value.
+    Solid,
+    /// Gets set to the value before every step.
+    FixedValue(T),
+    /// A wall without any flux through it. The value of the cell doesn't
+    /// change and its neighbours don't see it. They see their own values
+    /// mirrored at the wall instead.
+    Insulated,
+}
+
+/// The type of every cell of a field. Only the fluid cells get solved, while
+/// the ghost cells of the field are left to the boundary conditions.
+#[derive(Clone, Debug)]
+pub struct Mask<T>
+    where T: Float
+{
+    pub dimensions: (usize, usize),
+    cells: Vec<CellType<T>>,
+}
+
+impl<T> Index<(usize, usize)> for Mask<T>
+    where T: Float
+{
+    type Output = CellType<T>;
+
+    fn index(&self, (x, y): (usize, usize)) -> &CellType<T> {
+        &self.cells[x + self.dimensions.0 * y]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Mask<T>
+    where T: Float
+{
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut CellType<T> {
+        &mut self.cells[x + self.dimensions.0 * y]
+    }
+}
+
+impl<T> Mask<T>
+    where T: Float
+{
+    /// Creates a mask where all cells are fluid.
+    pub fn new((nx, ny): (usize, usize)) -> Self {
+        Mask {
+            dimensions: (nx, ny),
+            cells: vec![CellType::Fluid; nx * ny],
+        }
+    }
+
+    /// Sets all cells whose centre lies inside of the shape.
+    pub fn fill<S: Shape + ?Sized>(&mut self, shape: &S, cell_type: CellType<T>) {
+        let (nx, ny) = self.dimensions;
+        if nx == 0 || ny == 0 {
+            return;
+        }
+
+        let ((min_x, min_y), (max_x, max_y)) = shape.bounding_box();
+        let clamp = |value: f64, n: usize| value.clamp(0.0, (n - 1) as f64) as usize;
+        for y in clamp(min_y.floor(), ny)..clamp(max_y.ceil(), ny) + 1 {
+            for x in clamp(min_x.floor(), nx)..clamp(max_x.ceil(), nx) + 1 {
+                if shape.contains((x, y)) {
+                    self[(x, y)] = cell_type;
+                }
+            }
+        }
+    }
+
+    pub fn is_fluid(&self, coord: (usize, usize)) -> bool {
+        self[coord] == CellType::Fluid
+    }
+
+    /// Writes the values of the fixed value cells into the field.
+    pub fn apply_fixed_values(&self, field: &mut DataField<T>) {
+        for ((x, y), cell) in field.iter_mut() {
+            if let CellType::FixedValue(value) = self[(x, y)] {
+                *cell = value;
+            }
+        }
+    }
+
+    /// Evaluates a stencil at a fluid cell, which may reach as far as the
+    /// ghost layers of the field. If there are insulated cells within its
+    /// reach, the stencil gets evaluated on a copy of the neighbourhood in
+    /// `patch` instead, where the insulated cells are replaced by the values
+    /// mirrored at the walls, so that nothing flows through them. Neighbours
+    /// on a diagonal can't be mirrored, so they get the value of the cell
+    /// itself.
+    pub fn evaluate<F>(&self,
+                       field: &DataField<T>,
+                       (x, y): (usize, usize),
+                       patch: &mut DataField<T>,
+                       stencil: F)
+                       -> T
+        where F: FnOnce(&DataField<T>, (usize, usize)) -> T
+    {
+        let reach = field.ghost_layers();
+        let size = 2 * reach + 1;
+        let insulated = |coord| self[coord] == CellType::Insulated;
+        let near_wall = (y - reach..y + reach + 1)
+                            .any(|cy| (x - reach..x + reach + 1).any(|cx| insulated((cx, cy))));
+        if !near_wall {
+            return stencil(field, (x, y));
+        }
+
+        if patch.dimensions != (size, size) || patch.ghost_layers() != reach {
+            *patch = DataField::with_ghost_layers((size, size), reach);
+        }
+
+        for py in 0..size {
+            for px in 0..size {
+                let coord = (x + px - reach, y + py - reach);
+                patch[(px, py)] = if !insulated(coord) {
+                    field[coord]
+                } else if px != reach && py != reach {
+                    field[(x, y)]
+                } else {
+                    // Walk from the cell towards the insulated cell to find
+                    // the wall and mirror the distance beyond it
+                    let (dx, dy) = (px as isize - reach as isize, py as isize - reach as isize);
+                    let distance = dx.abs() + dy.abs();
+                    let (dx, dy) = (dx.signum(), dy.signum());
+                    let at = |steps: isize| {
+                        ((x as isize + steps * dx) as usize, (y as isize + steps * dy) as usize)
+                    };
+                    let wall = (1..distance + 1).find(|&steps| insulated(at(steps))).unwrap();
+                    let mirrored = 2 * wall - 1 - distance;
+                    if mirrored.abs() <= reach as isize && !insulated(at(mirrored)) {
+                        field[at(mirrored)]
+                    } else {
+                        field[(x, y)]
+                    }
+                };
+            }
+        }
+
+        stencil(patch, (reach, reach))
+    }
+}
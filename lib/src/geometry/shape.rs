@@ -0,0 +1,96 @@
+/// A shape in cell coordinates, where the cell `(x, y)` has its centre at
+/// `(x, y)`.
+pub trait Shape {
+    /// Returns whether the centre of the cell lies inside of the shape.
+    fn contains(&self, coord: (usize, usize)) -> bool;
+
+    /// Returns the smallest and largest coordinates the shape covers.
+    fn bounding_box(&self) -> ((f64, f64), (f64, f64));
+}
+
+impl<S> Shape for Box<S>
+    where S: Shape + ?Sized
+{
+    fn contains(&self, coord: (usize, usize)) -> bool {
+        (**self).contains(coord)
+    }
+
+    fn bounding_box(&self) -> ((f64, f64), (f64, f64)) {
+        (**self).bounding_box()
+    }
+}
+
+/// An axis aligned rectangle of cells, given by its top left cell and size.
+#[derive(Clone, Debug)]
+pub struct Rectangle {
+    pub position: (usize, usize),
+    pub size: (usize, usize),
+}
+
+impl Shape for Rectangle {
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        let ((left, top), (width, height)) = (self.position, self.size);
+        x >= left && y >= top && x < left + width && y < top + height
+    }
+
+    fn bounding_box(&self) -> ((f64, f64), (f64, f64)) {
+        let ((left, top), (width, height)) = (self.position, self.size);
+        ((left as f64, top as f64),
+         ((left + width) as f64 - 1.0, (top + height) as f64 - 1.0))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Circle {
+    pub center: (f64, f64),
+    pub radius: f64,
+}
+
+impl Shape for Circle {
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        let (dx, dy) = (x as f64 - self.center.0, y as f64 - self.center.1);
+        dx * dx + dy * dy <= self.radius * self.radius
+    }
+
+    fn bounding_box(&self) -> ((f64, f64), (f64, f64)) {
+        let ((x, y), radius) = (self.center, self.radius);
+        ((x - radius, y - radius), (x + radius, y + radius))
+    }
+}
+
+/// A polygon given by its corners. Self intersecting polygons use the even
+/// odd rule.
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    pub vertices: Vec<(f64, f64)>,
+}
+
+impl Shape for Polygon {
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        let (x, y) = (x as f64, y as f64);
+        let mut inside = false;
+        let mut previous = match self.vertices.last() {
+            Some(&vertex) => vertex,
+            None => return false,
+        };
+        for &vertex in &self.vertices {
+            let ((x1, y1), (x2, y2)) = (previous, vertex);
+            // Count the edges crossed by a ray going to the right
+            if (y1 > y) != (y2 > y) && x < x1 + (y - y1) * (x2 - x1) / (y2 - y1) {
+                inside = !inside;
+            }
+            previous = vertex;
+        }
+        inside
+    }
+
+    fn bounding_box(&self) -> ((f64, f64), (f64, f64)) {
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in &self.vertices {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        (min, max)
+    }
+}
@@ -1,22 +1,62 @@
+extern crate gif;
 extern crate num_cpus;
+extern crate png;
 extern crate scoped_threadpool;
 
 pub mod boundary_condition;
+pub mod checkpoint;
+pub mod colormap;
+mod coupled_solver;
+pub mod csv;
 mod data_field;
+mod data_field_3d;
 pub mod differential_equation;
+mod float;
+pub mod geometry;
+mod linear_solver;
+pub mod npy;
 mod solver;
+mod solver_3d;
+pub mod source;
+mod time_integration;
 pub mod precondition;
+pub mod velocity;
+pub mod vtk;
 
-pub use boundary_condition::{BoundaryCondition, BoundaryConditions};
+pub use boundary_condition::{BoundaryCondition, BoundaryConditions, BoundaryConditions3D,
+                             BoundaryPoint};
+pub use coupled_solver::CoupledSolver;
 pub use data_field::DataField;
-pub use differential_equation::DifferentialEquation;
-pub use precondition::PreCondition;
+pub use data_field_3d::DataField3D;
+pub use differential_equation::{CoupledEquation, DifferentialEquation, DifferentialEquation3D};
+pub use float::Float;
+pub use linear_solver::{LinearSolver, LinearSolverMethod, LinearSystem};
+pub use precondition::{PreCondition, PreCondition3D};
 pub use solver::Solver;
+pub use solver_3d::Solver3D;
+pub use source::Source;
+pub use time_integration::{ButcherTableau, TimeIntegration, TimeStepControl};
+pub use velocity::Velocity;
 
 pub mod prelude {
     pub use BoundaryCondition;
     pub use BoundaryConditions;
+    pub use BoundaryConditions3D;
+    pub use BoundaryPoint;
+    pub use CoupledEquation;
+    pub use CoupledSolver;
     pub use DifferentialEquation;
+    pub use DifferentialEquation3D;
+    pub use Float;
+    pub use LinearSolver;
+    pub use LinearSolverMethod;
+    pub use LinearSystem;
     pub use PreCondition;
+    pub use PreCondition3D;
     pub use Solver;
+    pub use Solver3D;
+    pub use Source;
+    pub use TimeIntegration;
+    pub use TimeStepControl;
+    pub use Velocity;
 }
@@ -0,0 +1,403 @@
+use std::mem;
+use DataField;
+use data_field::reserve_fields;
+use Float;
+use geometry::{CellType, Mask};
+use scoped_threadpool::Pool;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinearSolverMethod {
+    Jacobi,
+    GaussSeidel,
+    ConjugateGradient,
+}
+
+/// The system `(1 + 4 alpha) x - alpha (x_left + x_right + x_up + x_down) = b`
+/// for the inner cells of `x`. Cells of the mask that aren't fluid are held at
+/// `b` and insulated neighbours are replaced by the cell itself.
+#[derive(Clone, Copy)]
+pub struct LinearSystem<'a, T>
+    where T: Float + 'a
+{
+    pub b: &'a DataField<T>,
+    pub alpha: T,
+    pub mask: Option<&'a Mask<T>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LinearSolver<T>
+    where T: Float
+{
+    pub method: LinearSolverMethod,
+    pub tolerance: T,
+    pub max_iterations: usize,
+}
+
+impl<T> LinearSolver<T>
+    where T: Float
+{
+    pub fn new(method: LinearSolverMethod) -> Self {
+        LinearSolver {
+            method,
+            tolerance: T::from_f64(1e-6),
+            max_iterations: 1000,
+        }
+    }
+
+    /// Solves the system using the current content of `x` as the initial
+    /// guess. The ghost cells are kept up to date with `apply_boundaries`.
+    /// Returns the number of iterations needed.
+    pub fn solve<B>(&self,
+                    x: &mut DataField<T>,
+                    system: LinearSystem<T>,
+                    apply_boundaries: B,
+                    workspace: &mut Vec<DataField<T>>,
+                    pool: &mut Pool)
+                    -> usize
+        where B: Fn(&mut DataField<T>)
+    {
+        match self.method {
+            LinearSolverMethod::Jacobi => {
+                self.solve_jacobi(x, &system, apply_boundaries, workspace, pool)
+            }
+            LinearSolverMethod::GaussSeidel => {
+                self.solve_gauss_seidel(x, &system, apply_boundaries, workspace, pool)
+            }
+            LinearSolverMethod::ConjugateGradient => {
+                self.solve_conjugate_gradient(x, &system, apply_boundaries, workspace, pool)
+            }
+        }
+    }
+
+    fn solve_jacobi<B>(&self,
+                       x: &mut DataField<T>,
+                       system: &LinearSystem<T>,
+                       apply_boundaries: B,
+                       workspace: &mut Vec<DataField<T>>,
+                       pool: &mut Pool)
+                       -> usize
+        where B: Fn(&mut DataField<T>)
+    {
+        let (b, alpha, mask) = (system.b, system.alpha, system.mask);
+        reserve_fields(workspace, 2, x);
+        let (next, residual) = workspace.split_at_mut(1);
+        let (next, residual) = (&mut next[0], &mut residual[0]);
+        let threshold = self.tolerance * norm(b);
+        let diagonal = T::one() + T::from_f64(4.0) * alpha;
+        let thread_count = pool.thread_count() as usize;
+
+        for iteration in 0..self.max_iterations {
+            apply_boundaries(x);
+            apply_operator(pool, x, residual, alpha, mask);
+            if residual_norm(b, residual) <= threshold {
+                return iteration;
+            }
+
+            pool.scoped(|scope| {
+                let x = &*x;
+                for chunk in next.chunks_inner_mut(thread_count) {
+                    scope.execute(move || {
+                        for (coord, cell) in chunk {
+                            *cell = if is_fluid(mask, coord) {
+                                (b[coord] + alpha * neighbours(x, mask, coord)) / diagonal
+                            } else {
+                                b[coord]
+                            };
+                        }
+                    });
+                }
+            });
+            mem::swap(x, next);
+        }
+
+        apply_boundaries(x);
+        self.max_iterations
+    }
+
+    fn solve_gauss_seidel<B>(&self,
+                             x: &mut DataField<T>,
+                             system: &LinearSystem<T>,
+                             apply_boundaries: B,
+                             workspace: &mut Vec<DataField<T>>,
+                             pool: &mut Pool)
+                             -> usize
+        where B: Fn(&mut DataField<T>)
+    {
+        let (b, alpha, mask) = (system.b, system.alpha, system.mask);
+        reserve_fields(workspace, 1, x);
+        let residual = &mut workspace[0];
+        let threshold = self.tolerance * norm(b);
+        let diagonal = T::one() + T::from_f64(4.0) * alpha;
+        let (nx, ny) = x.dimensions;
+        let layers = x.ghost_layers();
+
+        for iteration in 0..self.max_iterations {
+            apply_boundaries(x);
+            apply_operator(pool, x, residual, alpha, mask);
+            if residual_norm(b, residual) <= threshold {
+                return iteration;
+            }
+
+            for cy in layers..ny - layers {
+                for cx in layers..nx - layers {
+                    let coord = (cx, cy);
+                    x[coord] = if is_fluid(mask, coord) {
+                        (b[coord] + alpha * neighbours(x, mask, coord)) / diagonal
+                    } else {
+                        b[coord]
+                    };
+                }
+            }
+        }
+
+        apply_boundaries(x);
+        self.max_iterations
+    }
+
+    fn solve_conjugate_gradient<B>(&self,
+                                   x: &mut DataField<T>,
+                                   system: &LinearSystem<T>,
+                                   apply_boundaries: B,
+                                   workspace: &mut Vec<DataField<T>>,
+                                   pool: &mut Pool)
+                                   -> usize
+        where B: Fn(&mut DataField<T>)
+    {
+        let (b, alpha, mask) = (system.b, system.alpha, system.mask);
+        reserve_fields(workspace, 4, x);
+        let (r, rest) = workspace.split_at_mut(1);
+        let (p, rest) = rest.split_at_mut(1);
+        let (ap, offset) = rest.split_at_mut(1);
+        let (r, p, ap, offset) = (&mut r[0], &mut p[0], &mut ap[0], &mut offset[0]);
+        let threshold = self.tolerance * norm(b);
+        let (nx, ny) = x.dimensions;
+        let layers = x.ghost_layers();
+
+        // The boundary conditions make the operator affine, so its constant
+        // part is determined once and removed when applying it to directions.
+        for (_, cell) in p.iter_mut() {
+            *cell = T::zero();
+        }
+        apply_boundaries(p);
+        apply_operator(pool, p, offset, alpha, mask);
+
+        apply_boundaries(x);
+        apply_operator(pool, x, r, alpha, mask);
+        for cy in layers..ny - layers {
+            for cx in layers..nx - layers {
+                r[(cx, cy)] = b[(cx, cy)] - r[(cx, cy)];
+                p[(cx, cy)] = r[(cx, cy)];
+            }
+        }
+        let mut rr = dot(r, r);
+
+        for iteration in 0..self.max_iterations {
+            if rr.sqrt() <= threshold {
+                apply_boundaries(x);
+                return iteration;
+            }
+
+            apply_boundaries(p);
+            apply_operator(pool, p, ap, alpha, mask);
+            for cy in layers..ny - layers {
+                for cx in layers..nx - layers {
+                    ap[(cx, cy)] = ap[(cx, cy)] - offset[(cx, cy)];
+                }
+            }
+
+            let step = rr / dot(p, ap);
+            for cy in layers..ny - layers {
+                for cx in layers..nx - layers {
+                    x[(cx, cy)] = x[(cx, cy)] + step * p[(cx, cy)];
+                    r[(cx, cy)] = r[(cx, cy)] - step * ap[(cx, cy)];
+                }
+            }
+
+            let rr_next = dot(r, r);
+            let beta = rr_next / rr;
+            rr = rr_next;
+            for cy in layers..ny - layers {
+                for cx in layers..nx - layers {
+                    p[(cx, cy)] = r[(cx, cy)] + beta * p[(cx, cy)];
+                }
+            }
+        }
+
+        apply_boundaries(x);
+        self.max_iterations
+    }
+}
+
+fn apply_operator<T>(pool: &mut Pool,
+                     x: &DataField<T>,
+                     target: &mut DataField<T>,
+                     alpha: T,
+                     mask: Option<&Mask<T>>)
+    where T: Float
+{
+    let diagonal = T::one() + T::from_f64(4.0) * alpha;
+    let thread_count = pool.thread_count() as usize;
+
+    pool.scoped(|scope| {
+        for chunk in target.chunks_inner_mut(thread_count) {
+            scope.execute(move || {
+                for (coord, cell) in chunk {
+                    *cell = if is_fluid(mask, coord) {
+                        diagonal * x[coord] - alpha * neighbours(x, mask, coord)
+                    } else {
+                        x[coord]
+                    };
+                }
+            });
+        }
+    });
+}
+
+fn is_fluid<T>(mask: Option<&Mask<T>>, coord: (usize, usize)) -> bool
+    where T: Float
+{
+    match mask {
+        Some(mask) => mask.is_fluid(coord),
+        None => true,
+    }
+}
+
+/// Sums the four neighbours of a cell, where insulated neighbours are
+/// replaced by the cell itself, so that there is no flux towards them.
+fn neighbours<T>(x: &DataField<T>, mask: Option<&Mask<T>>, (cx, cy): (usize, usize)) -> T
+    where T: Float
+{
+    let mut sum = T::zero();
+    for &coord in &[(cx - 1, cy), (cx + 1, cy), (cx, cy - 1), (cx, cy + 1)] {
+        let value = match mask {
+            Some(mask) if mask[coord] == CellType::Insulated => x[(cx, cy)],
+            _ => x[coord],
+        };
+        sum = sum + value;
+    }
+    sum
+}
+
+fn dot<T>(a: &DataField<T>, b: &DataField<T>) -> T
+    where T: Float
+{
+    let (nx, ny) = a.dimensions;
+    let layers = a.ghost_layers();
+    let mut sum = T::zero();
+    for y in layers..ny - layers {
+        for x in layers..nx - layers {
+            sum = sum + a[(x, y)] * b[(x, y)];
+        }
+    }
+    sum
+}
+
+fn norm<T>(field: &DataField<T>) -> T
+    where T: Float
+{
+    dot(field, field).sqrt()
+}
+
+fn residual_norm<T>(b: &DataField<T>, ax: &DataField<T>) -> T
+    where T: Float
+{
+    let (nx, ny) = b.dimensions;
+    let layers = b.ghost_layers();
+    let mut sum = T::zero();
+    for y in layers..ny - layers {
+        for x in layers..nx - layers {
+            let residual = b[(x, y)] - ax[(x, y)];
+            sum = sum + residual * residual;
+        }
+    }
+    sum.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use boundary_condition::{BoxedBoundaryConditions, Dirichlet, Neumann};
+    use BoundaryConditions;
+
+    const METHODS: [LinearSolverMethod; 3] = [LinearSolverMethod::Jacobi,
+                                              LinearSolverMethod::GaussSeidel,
+                                              LinearSolverMethod::ConjugateGradient];
+
+    fn right_hand_side() -> DataField<f64> {
+        let mut b = DataField::new((14, 11));
+        for ((x, y), cell) in b.iter_inner_mut() {
+            *cell = (x as f64 * 0.7).sin() + (y as f64 * 0.4).cos();
+        }
+        b
+    }
+
+    fn solve(method: LinearSolverMethod,
+             boundary_conditions: &BoxedBoundaryConditions<f64>,
+             b: &DataField<f64>)
+             -> DataField<f64> {
+        let mut solver = LinearSolver::new(method);
+        solver.tolerance = 1e-10;
+        solver.max_iterations = 10000;
+        let system = LinearSystem {
+            b,
+            alpha: 0.8,
+            mask: None,
+        };
+        let apply_boundaries = |field: &mut DataField<f64>| {
+            boundary_conditions.calculate_boundaries(field, 0.0, 1.0)
+        };
+        let mut x = DataField::new(b.dimensions);
+        let iterations = solver.solve(&mut x,
+                                      system,
+                                      apply_boundaries,
+                                      &mut Vec::new(),
+                                      &mut Pool::new(2));
+        assert!(iterations < solver.max_iterations, "{:?} didn't converge", method);
+        x
+    }
+
+    fn relative_residual(x: &DataField<f64>, b: &DataField<f64>) -> f64 {
+        let mut ax = DataField::new(x.dimensions);
+        apply_operator(&mut Pool::new(2), x, &mut ax, 0.8, None);
+        residual_norm(b, &ax) / norm(b)
+    }
+
+    #[test]
+    fn solves_dirichlet_and_neumann_systems() {
+        let b = right_hand_side();
+        let dirichlet = BoundaryConditions::new(Dirichlet(1.0),
+                                                Dirichlet(-0.5),
+                                                Dirichlet(0.0),
+                                                Dirichlet(2.0))
+            .boxed();
+        let neumann = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann).boxed();
+
+        for boundary_conditions in &[dirichlet, neumann] {
+            for &method in &METHODS {
+                let x = solve(method, boundary_conditions, &b);
+                let residual = relative_residual(&x, &b);
+                assert!(residual <= 1e-9, "{:?} left a residual of {}", method, residual);
+            }
+        }
+    }
+
+    #[test]
+    fn conjugate_gradient_agrees_with_gauss_seidel() {
+        let b = right_hand_side();
+        let boundary_conditions = BoundaryConditions::new(Dirichlet(1.0),
+                                                          Neumann,
+                                                          Dirichlet(0.0),
+                                                          Neumann)
+            .boxed();
+        let gauss_seidel = solve(LinearSolverMethod::GaussSeidel, &boundary_conditions, &b);
+        let conjugate_gradient =
+            solve(LinearSolverMethod::ConjugateGradient, &boundary_conditions, &b);
+
+        for y in 1..10 {
+            for x in 1..13 {
+                let difference = gauss_seidel[(x, y)] - conjugate_gradient[(x, y)];
+                assert!(difference.abs() < 1e-8);
+            }
+        }
+    }
+}
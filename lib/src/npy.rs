@@ -0,0 +1,260 @@
+//! Fields in the NumPy `.npy` format, which `numpy.load` reads directly. A
+//! field with the dimensions `(nx, ny)` is stored as an array of the shape
+//! `(ny, nx)`, so `array[y, x]` is the cell `(x, y)`. The boundary is
+//! included.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use checkpoint::invalid_data;
+use DataField;
+use Float;
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// The number of values that get allocated up front when reading an array.
+const MAX_PREALLOCATED_CELLS: usize = 1 << 16;
+
+pub fn write<T, W>(writer: &mut W, field: &DataField<T>) -> io::Result<()>
+    where T: Float,
+          W: Write
+{
+    let (nx, ny) = field.dimensions;
+    let descr = if mem::size_of::<T>() == 4 {
+        "<f4"
+    } else {
+        "<f8"
+    };
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}",
+                             descr,
+                             ny,
+                             nx);
+
+    // The data needs to start at a multiple of 64 bytes
+    let unpadded_length = MAGIC.len() + 4 + header.len() + 1;
+    let padding = (64 - unpadded_length % 64) % 64;
+    for _ in 0..padding {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for y in 0..ny {
+        for x in 0..nx {
+            field[(x, y)].write_to(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a two dimensional array of little endian `f4` or `f8` values. The
+/// values are converted to the scalar type of the field if necessary.
+pub fn read<T, R>(reader: &mut R) -> io::Result<DataField<T>>
+    where T: Float,
+          R: Read
+{
+    let reader: &mut dyn Read = reader;
+    read_array(reader).map_err(map_eof)
+}
+
+fn read_array<T>(reader: &mut dyn Read) -> io::Result<DataField<T>>
+    where T: Float
+{
+    let mut magic = [0; 6];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a NumPy file".to_string()));
+    }
+
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    let header_length = match version[0] {
+        1 => {
+            let mut bytes = [0; 2];
+            reader.read_exact(&mut bytes)?;
+            u16::from_le_bytes(bytes) as usize
+        }
+        2 | 3 => {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            u32::from_le_bytes(bytes) as usize
+        }
+        major => return Err(invalid_data(format!("Unsupported NumPy format version {}", major))),
+    };
+
+    // A corrupt header length can't allocate more memory than there is data
+    let mut header = Vec::new();
+    reader.take(header_length as u64).read_to_end(&mut header)?;
+    if header.len() < header_length {
+        return Err(truncated());
+    }
+    let header = String::from_utf8_lossy(&header);
+
+    let descr = header_value(&header, "descr")?;
+    let fortran_order = header_value(&header, "fortran_order")?;
+    let shape = header_value(&header, "shape")?;
+
+    let fortran_order = match fortran_order {
+        "True" => true,
+        "False" => false,
+        _ => return Err(invalid_data(format!("Invalid fortran_order {}", fortran_order))),
+    };
+
+    let shape = shape.trim_start_matches('(')
+                     .trim_end_matches(')')
+                     .split(',')
+                     .map(str::trim)
+                     .filter(|dimension| !dimension.is_empty())
+                     .map(str::parse::<usize>)
+                     .collect::<Result<Vec<_>, _>>()
+                     .map_err(|_| invalid_data(format!("Invalid shape {}", shape)))?;
+    let (nx, ny) = match shape[..] {
+        [ny, nx] if nx >= 3 && ny >= 3 => (nx, ny),
+        [ny, nx] => {
+            return Err(invalid_data(format!("The array of {}x{} cells is too small, a field \
+                                             needs at least 3x3",
+                                            nx,
+                                            ny)))
+        }
+        _ => {
+            return Err(invalid_data(format!("Expected a two dimensional array, found the \
+                                             shape {:?}",
+                                            shape)))
+        }
+    };
+
+    let read_value: fn(&mut dyn Read) -> io::Result<T> = match descr.trim_matches('\'') {
+        "<f4" => |reader| f32::read_from(reader).map(|value| T::from_f64(value as f64)),
+        "<f8" => |reader| f64::read_from(reader).map(T::from_f64),
+        descr => return Err(invalid_data(format!("Unsupported dtype {}", descr))),
+    };
+
+    let cell_count = nx.checked_mul(ny)
+                       .ok_or_else(|| invalid_data(format!("Invalid shape {:?}", shape)))?;
+
+    // The values are read incrementally, so that a corrupt shape can't
+    // allocate more memory than there is data
+    let mut values = Vec::with_capacity(cell_count.min(MAX_PREALLOCATED_CELLS));
+    for _ in 0..cell_count {
+        values.push(read_value(reader)?);
+    }
+
+    let mut field = DataField::new((nx, ny));
+    for ((x, y), cell) in field.iter_mut() {
+        *cell = if fortran_order {
+            values[y + ny * x]
+        } else {
+            values[x + nx * y]
+        };
+    }
+    Ok(field)
+}
+
+fn truncated() -> io::Error {
+    invalid_data("The NumPy file is truncated".to_string())
+}
+
+fn map_eof(error: io::Error) -> io::Error {
+    if error.kind() == io::ErrorKind::UnexpectedEof {
+        truncated()
+    } else {
+        error
+    }
+}
+
+/// Extracts the value of a key from the Python dictionary literal that forms
+/// the header.
+fn header_value<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+    let missing = || invalid_data(format!("The header doesn't contain the key {}", key));
+    let start = header.find(&format!("'{}':", key)).ok_or_else(missing)? + key.len() + 3;
+    let value = header[start..].trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')').map(|end| end + 1)
+    } else {
+        value.find(&[',', '}'][..])
+    };
+    Ok(value[..end.ok_or_else(missing)?].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::{read, write};
+    use DataField;
+
+    fn header(shape: &str) -> Vec<u8> {
+        let header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}\n",
+                             shape);
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes
+    }
+
+    fn assert_invalid(bytes: &[u8]) {
+        let mut reader = bytes;
+        match read::<f64, _>(&mut reader) {
+            Ok(_) => panic!("The array got accepted"),
+            Err(error) => assert_eq!(error.kind(), io::ErrorKind::InvalidData),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut field = DataField::new((4, 3));
+        for ((x, y), cell) in field.iter_mut() {
+            *cell = x as f32 + 0.1 * y as f32;
+        }
+        let mut bytes = Vec::new();
+        write(&mut bytes, &field).unwrap();
+        assert_eq!((bytes.len() - 4 * 3 * 4) % 64, 0);
+
+        let read: DataField<f64> = read(&mut &bytes[..]).unwrap();
+        assert_eq!(read.dimensions, (4, 3));
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(read[(x, y)], field[(x, y)] as f64);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_fortran_order() {
+        let mut bytes = header("(3, 4)");
+        let position = bytes.windows(5).position(|window| window == b"False").unwrap();
+        bytes.splice(position..position + 5, b"True ".iter().cloned());
+        for value in 0..12 {
+            bytes.extend_from_slice(&(value as f64).to_le_bytes());
+        }
+
+        let field: DataField<f64> = read(&mut &bytes[..]).unwrap();
+        assert_eq!(field.dimensions, (4, 3));
+        assert_eq!(field[(1, 0)], 3.0);
+        assert_eq!(field[(0, 1)], 1.0);
+    }
+
+    #[test]
+    fn rejects_truncated_arrays() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &DataField::<f64>::new((4, 3))).unwrap();
+        for length in 0..bytes.len() {
+            assert_invalid(&bytes[..length]);
+        }
+    }
+
+    #[test]
+    fn rejects_huge_headers_and_shapes() {
+        // A header length of 4 GiB without the data
+        let mut bytes = b"\x93NUMPY\x02\x00".to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(b"{'descr': '<f8', ");
+        assert_invalid(&bytes);
+
+        assert_invalid(&header("(4294967296, 4294967296)"));
+        assert_invalid(&header("(18446744073709551615, 2)"));
+        assert_invalid(&header("(2, 10)"));
+        assert_invalid(&header("(10,)"));
+    }
+}
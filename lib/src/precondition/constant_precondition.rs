@@ -1,9 +1,22 @@
+use Float;
 use PreCondition;
+use PreCondition3D;
 
-pub struct ConstantPreCondition(pub f32);
+pub struct ConstantPreCondition<T>(pub T) where T: Float;
 
-impl PreCondition for ConstantPreCondition {
-    fn precondition(&self, _: (usize, usize)) -> f32 {
+impl<T> PreCondition<T> for ConstantPreCondition<T>
+    where T: Float
+{
+    fn precondition(&self, _: (usize, usize)) -> T {
+        let &ConstantPreCondition(value) = self;
+        value
+    }
+}
+
+impl<T> PreCondition3D<T> for ConstantPreCondition<T>
+    where T: Float
+{
+    fn precondition(&self, _: (usize, usize, usize)) -> T {
         let &ConstantPreCondition(value) = self;
         value
     }
@@ -0,0 +1,15 @@
+use DataField;
+use Float;
+use PreCondition;
+
+/// Initializes the solver with the values of a field, e.g. one that was read
+/// from a file. The field needs to have the dimensions of the solver.
+pub struct FieldPreCondition<T>(pub DataField<T>) where T: Float;
+
+impl<T> PreCondition<T> for FieldPreCondition<T>
+    where T: Float
+{
+    fn precondition(&self, coord: (usize, usize)) -> T {
+        self.0[coord]
+    }
+}
@@ -1,7 +1,21 @@
 mod constant_precondition;
+mod field_precondition;
+mod shape_precondition;
 
 pub use self::constant_precondition::ConstantPreCondition;
+pub use self::field_precondition::FieldPreCondition;
+pub use self::shape_precondition::ShapePreCondition;
 
-pub trait PreCondition {
-    fn precondition(&self, coord: (usize, usize)) -> f32;
+use Float;
+
+pub trait PreCondition<T>
+    where T: Float
+{
+    fn precondition(&self, coord: (usize, usize)) -> T;
+}
+
+pub trait PreCondition3D<T>
+    where T: Float
+{
+    fn precondition(&self, coord: (usize, usize, usize)) -> T;
 }
@@ -0,0 +1,26 @@
+use Float;
+use PreCondition;
+use geometry::Shape;
+
+/// Sets the cells inside of the shape to `inside` and all other cells to
+/// `outside`.
+pub struct ShapePreCondition<S, T>
+    where T: Float
+{
+    pub shape: S,
+    pub inside: T,
+    pub outside: T,
+}
+
+impl<S, T> PreCondition<T> for ShapePreCondition<S, T>
+    where S: Shape,
+          T: Float
+{
+    fn precondition(&self, coord: (usize, usize)) -> T {
+        if self.shape.contains(coord) {
+            self.inside
+        } else {
+            self.outside
+        }
+    }
+}
@@ -1,69 +1,148 @@
+use std::io::{self, Read, Write};
+use std::mem;
 use std::ops::Index;
+use std::slice;
 use BoundaryCondition;
 use BoundaryConditions;
+use checkpoint::{self, invalid_data, read_u64, write_u64, SolverState};
 use DataField;
+use data_field::reserve_fields;
 use DifferentialEquation;
+use Float;
+use geometry::Mask;
+use LinearSolver;
+use LinearSystem;
 use PreCondition;
+use Source;
+use TimeIntegration;
+use time_integration::{self, ButcherTableau, Step, Stepper, System, TimeStepControl};
 use scoped_threadpool::Pool;
 use num_cpus;
 
-pub struct Solver<D, BU, BD, BL, BR>
-    where D: DifferentialEquation,
-          BU: BoundaryCondition,
-          BD: BoundaryCondition,
-          BL: BoundaryCondition,
-          BR: BoundaryCondition
+type Observers<S> = Vec<(usize, Box<dyn FnMut(&S) + Send>)>;
+type Sources<T> = Vec<Box<dyn Source<T> + Sync + Send>>;
+
+pub struct Solver<T, D, BU, BD, BL, BR>
+    where T: Float,
+          D: DifferentialEquation<T>,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>
 {
     differential_equation: D,
-    pub boundary_conditions: BoundaryConditions<BU, BD, BL, BR>,
-    delta_t: f32,
-    h: f32,
-    fields: [DataField; 2],
+    pub boundary_conditions: BoundaryConditions<T, BU, BD, BL, BR>,
+    delta_t: T,
+    h: T,
+    fields: [DataField<T>; 2],
     current_field: usize,
+    /// The previous time level of second order equations along with the
+    /// step since then. `None` until the first step.
+    history: Option<(DataField<T>, T)>,
+    /// The initial rate of change of second order equations, which the first
+    /// step uses.
+    initial_velocity: Option<DataField<T>>,
+    time_integration: TimeIntegration<T>,
+    pub time_step_control: TimeStepControl<T>,
+    /// Marks cells as obstacles or fixed values. It needs to have the
+    /// dimensions of the field.
+    pub mask: Option<Mask<T>>,
+    time: T,
+    steps: usize,
+    trial_delta_t: T,
+    observers: Observers<Solver<T, D, BU, BD, BL, BR>>,
+    sources: Sources<T>,
+    /// The rates of all sources summed up
+    source_rates: Vec<DataField<T>>,
+    scratch_fields: Vec<DataField<T>>,
+    linear_solver_workspace: Vec<DataField<T>>,
     thread_pool: Pool,
 }
 
-impl<D, BU, BD, BL, BR> Index<(usize, usize)> for Solver<D, BU, BD, BL, BR>
-    where D: DifferentialEquation,
-          BU: BoundaryCondition,
-          BD: BoundaryCondition,
-          BL: BoundaryCondition,
-          BR: BoundaryCondition
+impl<T, D, BU, BD, BL, BR> Index<(usize, usize)> for Solver<T, D, BU, BD, BL, BR>
+    where T: Float,
+          D: DifferentialEquation<T>,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>
 {
-    type Output = f32;
+    type Output = T;
 
-    fn index(&self, index: (usize, usize)) -> &f32 {
+    fn index(&self, index: (usize, usize)) -> &T {
         &self.fields[self.current_field][index]
     }
 }
 
-impl<D, BU, BD, BL, BR> Solver<D, BU, BD, BL, BR>
-    where D: DifferentialEquation + Sync + Send,
-          BU: BoundaryCondition + Sync + Send,
-          BD: BoundaryCondition + Sync + Send,
-          BL: BoundaryCondition + Sync + Send,
-          BR: BoundaryCondition + Sync + Send
+impl<T, D, BU, BD, BL, BR> Solver<T, D, BU, BD, BL, BR>
+    where T: Float,
+          D: DifferentialEquation<T> + Sync + Send,
+          BU: BoundaryCondition<T> + Sync + Send,
+          BD: BoundaryCondition<T> + Sync + Send,
+          BL: BoundaryCondition<T> + Sync + Send,
+          BR: BoundaryCondition<T> + Sync + Send
 {
     pub fn new(equation: D,
-               boundary_conditions: BoundaryConditions<BU, BD, BL, BR>,
+               boundary_conditions: BoundaryConditions<T, BU, BD, BL, BR>,
                dimensions: (usize, usize),
-               delta_t: f32,
-               h: f32)
+               delta_t: T,
+               h: T)
                -> Self {
+        Solver::with_time_integration(equation,
+                                      boundary_conditions,
+                                      dimensions,
+                                      delta_t,
+                                      h,
+                                      TimeIntegration::ForwardEuler)
+    }
+
+    pub fn with_time_integration(equation: D,
+                                 boundary_conditions: BoundaryConditions<T, BU, BD, BL, BR>,
+                                 dimensions: (usize, usize),
+                                 delta_t: T,
+                                 h: T,
+                                 time_integration: TimeIntegration<T>)
+                                 -> Self {
+        if time_integration.implicitness().is_some() {
+            assert!(equation.diffusion_coefficient().is_some(),
+                    "Implicit time integration requires an equation with a diffusion term");
+        }
+        assert!(equation.is_second_order() == time_integration.is_second_order(),
+                "Equations that are second order in time require the leapfrog time integration \
+                 and vice versa");
+
+        let ghost_layers = equation.ghost_layers();
+        let new_field = || DataField::with_ghost_layers(dimensions, ghost_layers);
+
         Solver {
             differential_equation: equation,
-            boundary_conditions: boundary_conditions,
-            delta_t: delta_t,
-            h: h,
-            fields: [DataField::new(dimensions), DataField::new(dimensions)],
+            boundary_conditions,
+            delta_t,
+            h,
+            fields: [new_field(), new_field()],
             current_field: 0,
+            history: None,
+            initial_velocity: None,
+            time_integration,
+            time_step_control: TimeStepControl::Fixed,
+            mask: None,
+            time: T::zero(),
+            steps: 0,
+            trial_delta_t: delta_t,
+            observers: Vec::new(),
+            sources: Vec::new(),
+            source_rates: Vec::new(),
+            scratch_fields: Vec::new(),
+            linear_solver_workspace: Vec::new(),
             thread_pool: Pool::new(num_cpus::get() as u32),
         }
     }
 
-    pub fn execute_precondition<P: PreCondition + ?Sized + Sync>(&mut self, precondition: &P) {
+    pub fn execute_precondition<P: PreCondition<T> + ?Sized + Sync>(&mut self, precondition: &P) {
         let current_field = &mut self.fields[self.current_field];
         let thread_count = self.thread_pool.thread_count() as usize;
+        // A second order equation starts over from the new field
+        self.history = None;
 
         self.thread_pool.scoped(|scope| {
             for chunk in current_field.chunks_mut(thread_count) {
@@ -76,7 +155,272 @@ impl<D, BU, BD, BL, BR> Solver<D, BU, BD, BL, BR>
         });
     }
 
+    /// Sets the initial rate of change of an equation that is second order in
+    /// time, like the velocity of a membrane. Without it, the field starts at
+    /// rest. It can be called before or after `execute_precondition`, which
+    /// only discards the previous time level.
+    pub fn execute_velocity_precondition<P>(&mut self, precondition: &P)
+        where P: PreCondition<T> + ?Sized + Sync
+    {
+        let current_field = &self.fields[self.current_field];
+        let mut velocity = DataField::with_ghost_layers(current_field.dimensions,
+                                                        current_field.ghost_layers());
+        let thread_count = self.thread_pool.thread_count() as usize;
+
+        self.thread_pool.scoped(|scope| {
+            for chunk in velocity.chunks_mut(thread_count) {
+                scope.execute(move || {
+                    for (coord, cell) in chunk {
+                        *cell = precondition.precondition(coord);
+                    }
+                });
+            }
+        });
+
+        self.history = None;
+        self.initial_velocity = Some(velocity);
+    }
+
     pub fn solve_next_frame(&mut self) {
+        let delta_t = self.delta_t;
+        self.solve_frame(delta_t);
+    }
+
+    pub fn run_steps(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.solve_next_frame();
+        }
+    }
+
+    /// Solves frames until the simulated time reaches `time`. The last frame
+    /// is shortened so that the solver stops exactly at that time. It also
+    /// stops once the time is too large to still increase by a frame.
+    pub fn run_until(&mut self, time: T) {
+        assert!(self.delta_t > T::zero(), "The time step needs to be positive");
+        let epsilon = self.delta_t * T::from_f64(1e-6);
+        while time - self.time > epsilon {
+            let remaining = time - self.time;
+            let delta_t = if remaining < self.delta_t {
+                remaining
+            } else {
+                self.delta_t
+            };
+            let previous_time = self.time;
+            self.solve_frame(delta_t);
+            if self.time <= previous_time {
+                break;
+            }
+        }
+    }
+
+    /// Registers an observer that gets called after every `interval` frames.
+    pub fn add_observer<F>(&mut self, interval: usize, observer: F)
+        where F: FnMut(&Self) + Send + 'static
+    {
+        assert!(interval > 0, "The observer interval needs to be positive");
+        self.observers.push((interval, Box::new(observer)));
+    }
+
+    /// Adds a source whose rates get added to the right-hand side of the
+    /// differential equation in every step. For equations that are second
+    /// order in time, the rates are accelerations.
+    pub fn add_source<S>(&mut self, source: S)
+        where S: Source<T> + Sync + Send + 'static
+    {
+        self.sources.push(Box::new(source));
+    }
+
+    pub fn clear_sources(&mut self) {
+        self.sources.clear();
+    }
+
+    fn solve_frame(&mut self, delta_t: T) {
+        let time_integration = self.time_integration;
+        let stable_time_step = self.stable_time_step();
+        time_integration::solve_frame(self,
+                                      &time_integration,
+                                      self.time_step_control,
+                                      stable_time_step,
+                                      delta_t);
+
+        self.steps += 1;
+        self.notify_observers();
+    }
+
+    fn notify_observers(&mut self) {
+        let mut observers = Vec::new();
+        mem::swap(&mut observers, &mut self.observers);
+        for &mut (interval, ref mut observer) in &mut observers {
+            if self.steps.is_multiple_of(interval) {
+                observer(self);
+            }
+        }
+        mem::swap(&mut observers, &mut self.observers);
+    }
+
+    pub fn stable_time_step(&self) -> Option<T> {
+        let limit = self.differential_equation.max_stable_time_step(self.h);
+        match (limit, self.time_integration.stability_factor()) {
+            (Some(limit), Some(factor)) => Some(limit * factor),
+            _ => None,
+        }
+    }
+
+    pub fn time(&self) -> T {
+        self.time
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn delta_t(&self) -> T {
+        self.delta_t
+    }
+
+    pub fn h(&self) -> T {
+        self.h
+    }
+
+    /// Writes the fields, the time, the step count and the parameters of the
+    /// equation and the boundary conditions, along with the previous time
+    /// level of second order equations, so that the run can be resumed
+    /// bit-identically with `load_checkpoint`. The states of the built-in
+    /// equations and boundary conditions start with an identifier, which gets
+    /// checked when they are loaded again. The time integration scheme, the
+    /// time step control, the observers and the sources aren't part of a
+    /// checkpoint. Neither is the mask, so it has to be set again before a
+    /// checkpoint gets loaded into a new solver.
+    pub fn save_checkpoint<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let writer: &mut dyn Write = writer;
+        let state = SolverState {
+            current: self.current_field,
+            steps: self.steps,
+            time: self.time,
+            trial_delta_t: self.trial_delta_t,
+            delta_t: self.delta_t,
+            h: self.h,
+            states: self.save_states()?,
+        };
+        state.write_to(writer, "Solver")?;
+
+        for field in &self.fields {
+            field.write_to(writer)?;
+        }
+
+        match self.history {
+            Some((ref previous, previous_delta_t)) => {
+                write_u64(writer, 1)?;
+                previous_delta_t.write_to(writer)?;
+                previous.write_to(writer)?;
+            }
+            None => write_u64(writer, 0)?,
+        }
+        match self.initial_velocity {
+            Some(ref velocity) => {
+                write_u64(writer, 1)?;
+                velocity.write_to(writer)?;
+            }
+            None => write_u64(writer, 0)?,
+        }
+        Ok(())
+    }
+
+    /// Restores a checkpoint written by `save_checkpoint`. It has to be
+    /// written by a solver with the same equation, boundary conditions and
+    /// dimensions. If an error occurs, the solver is left unchanged.
+    pub fn load_checkpoint<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let reader: &mut dyn Read = reader;
+        self.read_checkpoint(reader).map_err(checkpoint::map_truncated)
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut dyn Read) -> io::Result<()> {
+        let state = SolverState::read_from(reader, "Solver", 5)?;
+
+        let template = &self.fields[0];
+        if let Some(ref mask) = self.mask {
+            if mask.dimensions != template.dimensions {
+                return Err(invalid_data("The mask doesn't have the dimensions of the field"
+                                            .to_string()));
+            }
+        }
+        let first = checkpoint::read_matching_field(reader, template)?;
+        let second = checkpoint::read_matching_field(reader, template)?;
+        let history = if read_u64(reader)? != 0 {
+            let previous_delta_t = T::read_from(reader)?;
+            let previous = checkpoint::read_matching_field(reader, template)?;
+            Some((previous, previous_delta_t))
+        } else {
+            None
+        };
+        let initial_velocity = if read_u64(reader)? != 0 {
+            Some(checkpoint::read_matching_field(reader, template)?)
+        } else {
+            None
+        };
+
+        // Everything got read, so only the states can still fail to restore
+        let backup = self.save_states()?;
+        checkpoint::restore_states(&state.states, &backup, |readers| self.load_states(readers))?;
+
+        self.fields = [first, second];
+        self.history = history;
+        self.initial_velocity = initial_velocity;
+        self.current_field = state.current;
+        self.steps = state.steps;
+        self.time = state.time;
+        self.trial_delta_t = state.trial_delta_t;
+        self.delta_t = state.delta_t;
+        self.h = state.h;
+        Ok(())
+    }
+
+    /// Returns the states of the equation and the boundary conditions.
+    fn save_states(&self) -> io::Result<Vec<Vec<u8>>> {
+        let mut states = vec![Vec::new(); 5];
+        self.differential_equation.save_state(&mut states[0])?;
+        self.boundary_conditions.up.save_state(&mut states[1])?;
+        self.boundary_conditions.down.save_state(&mut states[2])?;
+        self.boundary_conditions.left.save_state(&mut states[3])?;
+        self.boundary_conditions.right.save_state(&mut states[4])?;
+        Ok(states)
+    }
+
+    /// Restores the states returned by `save_states`.
+    fn load_states(&mut self, readers: &mut [&[u8]]) -> io::Result<()> {
+        self.differential_equation.load_state(&mut readers[0])?;
+        self.boundary_conditions.up.load_state(&mut readers[1])?;
+        self.boundary_conditions.down.load_state(&mut readers[2])?;
+        self.boundary_conditions.left.load_state(&mut readers[3])?;
+        self.boundary_conditions.right.load_state(&mut readers[4])
+    }
+
+    /// Solves a step into the target field. Returns the largest error of the
+    /// step relative to the tolerances, if they are given and the scheme
+    /// contains an embedded pair.
+    fn step(&mut self, delta_t: T, tolerances: Option<(T, T)>) -> T {
+        if let Some(ref mask) = self.mask {
+            let field = &mut self.fields[self.current_field];
+            assert!(mask.dimensions == field.dimensions,
+                    "The mask needs to have the dimensions of the field");
+            mask.apply_fixed_values(field);
+        }
+
+        if self.time_integration.is_second_order() {
+            self.solve_leapfrog(delta_t);
+            T::zero()
+        } else if let Some((theta, linear_solver)) = self.time_integration.implicitness() {
+            self.solve_implicit(theta, linear_solver, delta_t);
+            T::zero()
+        } else if let Some(tableau) = self.time_integration.runge_kutta_tableau() {
+            self.solve_runge_kutta(&tableau, delta_t, tolerances)
+        } else {
+            self.solve_explicit(delta_t);
+            T::zero()
+        }
+    }
+
+    fn solve_explicit(&mut self, delta_t: T) {
         // Prepare solving
         let (field_a, field_b) = self.fields.split_at_mut(1);
         let (current_field, target_field) = if self.current_field == 0 {
@@ -85,34 +429,486 @@ impl<D, BU, BD, BL, BR> Solver<D, BU, BD, BL, BR>
             (&mut field_b[0], &mut field_a[0])
         };
         let equation = &self.differential_equation;
-        let delta_t = self.delta_t;
+        let mask = self.mask.as_ref();
         let h = self.h;
         let thread_count = self.thread_pool.thread_count() as usize;
+        let sources = sum_source_rates(&self.sources,
+                                       &mut self.source_rates,
+                                       current_field,
+                                       self.time,
+                                       h);
 
         // Execute Boundary Conditions
-        self.boundary_conditions.calculate_boundaries(current_field);
+        self.boundary_conditions.calculate_boundaries(current_field, self.time, h);
 
         // Solve the differential equation for the whole field
         self.thread_pool.scoped(|scope| {
             let current_field = &current_field;
             for chunk in target_field.chunks_inner_mut(thread_count) {
                 scope.execute(move || {
+                    let solve = |field: &DataField<T>, coord| {
+                        equation.solve(field, coord, delta_t, h)
+                    };
+                    let mut patch = DataField::new((1, 1));
                     for (coord, cell) in chunk {
-                        *cell = equation.solve(current_field, coord, delta_t, h);
+                        *cell = evaluate(mask, current_field, coord, &mut patch, solve)
+                                    .map(|value| value + delta_t * source_rate(sources, coord))
+                                    .unwrap_or(current_field[coord]);
                     }
                 });
             }
         });
+    }
 
-        // Swap the fields
-        self.current_field ^= 1;
+    fn solve_leapfrog(&mut self, delta_t: T) {
+        // Prepare solving
+        let (field_a, field_b) = self.fields.split_at_mut(1);
+        let (current_field, target_field) = if self.current_field == 0 {
+            (&mut field_a[0], &mut field_b[0])
+        } else {
+            (&mut field_b[0], &mut field_a[0])
+        };
+        let equation = &self.differential_equation;
+        let mask = self.mask.as_ref();
+        let h = self.h;
+        let thread_count = self.thread_pool.thread_count() as usize;
+        let sources = sum_source_rates(&self.sources,
+                                       &mut self.source_rates,
+                                       current_field,
+                                       self.time,
+                                       h);
+        let initial_velocity = self.initial_velocity.take();
+
+        // Execute Boundary Conditions
+        match self.history {
+            Some((ref previous, previous_delta_t)) => {
+                self.boundary_conditions
+                    .calculate_boundaries_with_history(current_field,
+                                                       previous,
+                                                       previous_delta_t,
+                                                       self.time,
+                                                       h)
+            }
+            None => self.boundary_conditions.calculate_boundaries(current_field, self.time, h),
+        }
+
+        // Solve the differential equation for the whole field
+        let history = self.history.as_ref();
+        let initial_velocity = initial_velocity.as_ref();
+        self.thread_pool.scoped(|scope| {
+            let current_field = &current_field;
+            for chunk in target_field.chunks_inner_mut(thread_count) {
+                scope.execute(move || {
+                    let acceleration = |field: &DataField<T>, coord| {
+                        equation.right_hand_side(field, coord, h)
+                    };
+                    let mut patch = DataField::new((1, 1));
+                    for (coord, cell) in chunk {
+                        let current = current_field[coord];
+                        // The first step is a Taylor expansion with the
+                        // initial velocity
+                        let (change, previous_delta_t) = match history {
+                            Some(&(ref previous, previous_delta_t)) => {
+                                (delta_t / previous_delta_t * (current - previous[coord]),
+                                 previous_delta_t)
+                            }
+                            None => {
+                                (initial_velocity.map_or(T::zero(), |v| delta_t * v[coord]),
+                                 T::zero())
+                            }
+                        };
+                        let weight = delta_t * (delta_t + previous_delta_t) / T::from_f64(2.0);
+                        *cell = evaluate(mask, current_field, coord, &mut patch, acceleration)
+                                    .map(|value| {
+                                        let value = value + source_rate(sources, coord);
+                                        current + change + weight * value
+                                    })
+                                    .unwrap_or(current);
+                    }
+                });
+            }
+        });
+
+        // The current field becomes the previous time level
+        let mut previous = match self.history.take() {
+            Some((previous, _)) => previous,
+            None => {
+                DataField::with_ghost_layers(current_field.dimensions,
+                                             current_field.ghost_layers())
+            }
+        };
+        mem::swap(&mut previous, current_field);
+        self.history = Some((previous, delta_t));
+    }
+
+    fn solve_runge_kutta(&mut self,
+                         tableau: &ButcherTableau,
+                         delta_t: T,
+                         tolerances: Option<(T, T)>)
+                         -> T {
+        let (field_a, field_b) = self.fields.split_at_mut(1);
+        let (current_field, target_field) = if self.current_field == 0 {
+            (&mut field_a[0], &mut field_b[0])
+        } else {
+            (&mut field_b[0], &mut field_a[0])
+        };
+        let mut system = Rates {
+            equation: &self.differential_equation,
+            boundary_conditions: &self.boundary_conditions,
+            mask: self.mask.as_ref(),
+            sources: &self.sources,
+            source_rates: &mut self.source_rates,
+            h: self.h,
+        };
+        let step = Step {
+            time: self.time,
+            delta_t,
+            tolerances,
+        };
+        time_integration::runge_kutta_step(&mut system,
+                                           tableau,
+                                           step,
+                                           slice::from_mut(current_field),
+                                           slice::from_mut(target_field),
+                                           &mut self.scratch_fields,
+                                           &mut self.thread_pool)
     }
 
-    pub fn get_field(&self) -> &DataField {
+    fn solve_implicit(&mut self, theta: T, linear_solver: LinearSolver<T>, delta_t: T) {
+        // Prepare solving
+        let (field_a, field_b) = self.fields.split_at_mut(1);
+        let (current_field, target_field) = if self.current_field == 0 {
+            (&mut field_a[0], &mut field_b[0])
+        } else {
+            (&mut field_b[0], &mut field_a[0])
+        };
+        let equation = &self.differential_equation;
+        let diffusion = equation.diffusion_coefficient().unwrap();
+        let mask = self.mask.as_ref();
+        let h = self.h;
+        let thread_count = self.thread_pool.thread_count() as usize;
+
+        reserve_fields(&mut self.scratch_fields, 1, current_field);
+        let right_hand_side = &mut self.scratch_fields[0];
+        // The sources are taken at the time the implicit part refers to
+        let sources = sum_source_rates(&self.sources,
+                                       &mut self.source_rates,
+                                       current_field,
+                                       self.time + theta * delta_t,
+                                       h);
+
+        // Execute Boundary Conditions
+        self.boundary_conditions.calculate_boundaries(current_field, self.time, h);
+
+        // Everything but the implicit part of the diffusion term is explicit
+        self.thread_pool.scoped(|scope| {
+            let current_field = &current_field;
+            for chunk in right_hand_side.chunks_inner_mut(thread_count) {
+                scope.execute(move || {
+                    let explicit_part = |field: &DataField<T>, (x, y): (usize, usize)| {
+                        let center = field[(x, y)];
+                        let rate = equation.right_hand_side(field, (x, y), h);
+                        let neighbours = field[(x - 1, y)] + field[(x + 1, y)] +
+                                         field[(x, y - 1)] + field[(x, y + 1)];
+                        let laplacian = (neighbours - T::from_f64(4.0) * center) / (h * h);
+                        center + delta_t * (rate - theta * diffusion * laplacian)
+                    };
+                    let mut patch = DataField::new((1, 1));
+                    for (coord, cell) in chunk {
+                        *cell = evaluate(mask, current_field, coord, &mut patch, explicit_part)
+                                    .map(|value| value + delta_t * source_rate(sources, coord))
+                                    .unwrap_or(current_field[coord]);
+                    }
+                });
+            }
+        });
+
+        // Solve the linear system for the diffusion term
+        let boundary_conditions = &self.boundary_conditions;
+        let time = self.time + delta_t;
+        let alpha = theta * delta_t * diffusion / (h * h);
+        target_field.copy_from(current_field);
+        let system = LinearSystem {
+            b: right_hand_side,
+            alpha,
+            mask,
+        };
+        linear_solver.solve(target_field,
+                            system,
+                            |field| boundary_conditions.calculate_boundaries(field, time, h),
+                            &mut self.linear_solver_workspace,
+                            &mut self.thread_pool);
+    }
+
+    pub fn get_field(&self) -> &DataField<T> {
         &self.fields[self.current_field]
     }
 
-    pub fn get_field_mut(&mut self) -> &mut DataField {
+    pub fn get_field_mut(&mut self) -> &mut DataField<T> {
         &mut self.fields[self.current_field]
     }
 }
+
+impl<T, D, BU, BD, BL, BR> Stepper<T> for Solver<T, D, BU, BD, BL, BR>
+    where T: Float,
+          D: DifferentialEquation<T> + Sync + Send,
+          BU: BoundaryCondition<T> + Sync + Send,
+          BD: BoundaryCondition<T> + Sync + Send,
+          BL: BoundaryCondition<T> + Sync + Send,
+          BR: BoundaryCondition<T> + Sync + Send
+{
+    fn step(&mut self, delta_t: T, tolerances: Option<(T, T)>) -> T {
+        Solver::step(self, delta_t, tolerances)
+    }
+
+    fn accept(&mut self, delta_t: T) {
+        // Swap the fields
+        self.current_field ^= 1;
+        self.time = self.time + delta_t;
+    }
+
+    fn trial_delta_t(&mut self) -> &mut T {
+        &mut self.trial_delta_t
+    }
+}
+
+/// The right-hand side of the equation on the fluid cells of the mask, plus
+/// the rates of the sources.
+struct Rates<'a, T, D, BU, BD, BL, BR>
+    where T: Float + 'a,
+          D: 'a,
+          BU: BoundaryCondition<T> + 'a,
+          BD: BoundaryCondition<T> + 'a,
+          BL: BoundaryCondition<T> + 'a,
+          BR: BoundaryCondition<T> + 'a
+{
+    equation: &'a D,
+    boundary_conditions: &'a BoundaryConditions<T, BU, BD, BL, BR>,
+    mask: Option<&'a Mask<T>>,
+    sources: &'a Sources<T>,
+    source_rates: &'a mut Vec<DataField<T>>,
+    h: T,
+}
+
+impl<'a, T, D, BU, BD, BL, BR> System<T> for Rates<'a, T, D, BU, BD, BL, BR>
+    where T: Float,
+          D: DifferentialEquation<T> + Sync,
+          BU: BoundaryCondition<T> + Sync,
+          BD: BoundaryCondition<T> + Sync,
+          BL: BoundaryCondition<T> + Sync,
+          BR: BoundaryCondition<T> + Sync
+{
+    fn calculate_boundaries(&self, fields: &mut [DataField<T>], time: T) {
+        self.boundary_conditions.calculate_boundaries(&mut fields[0], time, self.h);
+    }
+
+    fn prepare(&mut self, fields: &[DataField<T>], time: T) {
+        sum_source_rates(self.sources, self.source_rates, &fields[0], time, self.h);
+    }
+
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       _: usize,
+                       coord: (usize, usize),
+                       patch: &mut DataField<T>)
+                       -> T {
+        let h = self.h;
+        let equation = self.equation;
+        let rate = |field: &DataField<T>, coord| equation.right_hand_side(field, coord, h);
+        let sources = if self.sources.is_empty() {
+            None
+        } else {
+            Some(&self.source_rates[0])
+        };
+        // Cells that aren't fluid don't change
+        evaluate(self.mask, &fields[0], coord, patch, rate)
+            .map(|rate| rate + source_rate(sources, coord))
+            .unwrap_or(T::zero())
+    }
+}
+
+/// Evaluates `stencil` at a cell with respect to the mask. Returns `None` for
+/// cells that aren't fluid, as they don't get solved.
+fn evaluate<T, F>(mask: Option<&Mask<T>>,
+                  field: &DataField<T>,
+                  coord: (usize, usize),
+                  patch: &mut DataField<T>,
+                  stencil: F)
+                  -> Option<T>
+    where T: Float,
+          F: FnOnce(&DataField<T>, (usize, usize)) -> T
+{
+    match mask {
+        None => Some(stencil(field, coord)),
+        Some(mask) if mask.is_fluid(coord) => Some(mask.evaluate(field, coord, patch, stencil)),
+        Some(_) => None,
+    }
+}
+
+/// Sums up the rates of the sources at `time` in the first of `rates`.
+/// Returns `None` if there aren't any sources.
+fn sum_source_rates<'a, T>(sources: &Sources<T>,
+                           rates: &'a mut Vec<DataField<T>>,
+                           template: &DataField<T>,
+                           time: T,
+                           h: T)
+                           -> Option<&'a DataField<T>>
+    where T: Float
+{
+    if sources.is_empty() {
+        return None;
+    }
+
+    reserve_fields(rates, 1, template);
+    let rates = &mut rates[0];
+    for (_, rate) in rates.iter_mut() {
+        *rate = T::zero();
+    }
+    for source in sources {
+        source.add_rates(rates, time, h);
+    }
+    Some(rates)
+}
+
+fn source_rate<T>(sources: Option<&DataField<T>>, coord: (usize, usize)) -> T
+    where T: Float
+{
+    match sources {
+        Some(rates) => rates[coord],
+        None => T::zero(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use boundary_condition::{BoundaryPoint, Neumann, Profile};
+    use differential_equation::ThermalConduction;
+    use geometry::{CellType, Mask, Rectangle};
+    use BoundaryConditions;
+    use LinearSolver;
+    use LinearSolverMethod;
+    use Solver;
+    use TimeIntegration;
+
+    type TestSolver = Solver<f64, ThermalConduction, Neumann, Neumann, Neumann, Neumann>;
+
+    fn time_integrations() -> Vec<TimeIntegration<f64>> {
+        let mut linear_solver = LinearSolver::new(LinearSolverMethod::ConjugateGradient);
+        linear_solver.tolerance = 1e-12;
+        linear_solver.max_iterations = 10000;
+        vec![TimeIntegration::ForwardEuler,
+             TimeIntegration::RungeKutta4,
+             TimeIntegration::BackwardEuler(linear_solver),
+             TimeIntegration::CrankNicolson(linear_solver)]
+    }
+
+    /// A solver with a hot stripe on the left of an obstacle in the middle
+    /// of the field.
+    fn solver(time_integration: TimeIntegration<f64>, obstacle: CellType<f64>) -> TestSolver {
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver = Solver::with_time_integration(ThermalConduction,
+                                                       boundary_conditions,
+                                                       (16, 12),
+                                                       0.2,
+                                                       1.0,
+                                                       time_integration);
+        let mut mask = Mask::new((16, 12));
+        mask.fill(&Rectangle {
+                      position: (6, 3),
+                      size: (3, 6),
+                  },
+                  obstacle);
+        solver.mask = Some(mask);
+        for ((x, _), cell) in solver.get_field_mut().iter_mut() {
+            *cell = if x < 4 { 1.0 } else { 0.0 };
+        }
+        solver
+    }
+
+    fn fluid_heat(solver: &TestSolver) -> f64 {
+        let field = solver.get_field();
+        let mask = solver.mask.as_ref().unwrap();
+        let (nx, ny) = field.dimensions;
+        let mut heat = 0.0;
+        for y in 1..ny - 1 {
+            for x in 1..nx - 1 {
+                if mask.is_fluid((x, y)) {
+                    heat += field[(x, y)];
+                }
+            }
+        }
+        heat
+    }
+
+    #[test]
+    fn run_until_stops_at_the_time() {
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver = Solver::new(ThermalConduction, boundary_conditions, (6, 6), 0.3, 1.0);
+        solver.run_until(1.0);
+        assert_eq!(solver.steps(), 4);
+        assert!((solver.time() - 1.0f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn run_until_stops_once_the_time_stops_increasing() {
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver = Solver::new(ThermalConduction, boundary_conditions, (6, 6), 0.25, 1.0);
+        solver.time = 1e8f32;
+        solver.run_until(2e8);
+        assert_eq!(solver.time(), 1e8);
+        assert_eq!(solver.steps(), 1);
+    }
+
+    #[test]
+    fn evaluates_the_boundaries_at_the_time_of_the_step() {
+        let times = Mutex::new(Vec::new());
+        {
+            let profile = Profile(|point: BoundaryPoint<f64>| {
+                let mut times = times.lock().unwrap();
+                if times.last() != Some(&point.time) {
+                    times.push(point.time);
+                }
+                1.0
+            });
+            let boundary_conditions = BoundaryConditions::new(profile, Neumann, Neumann, Neumann);
+            let mut solver = Solver::new(ThermalConduction, boundary_conditions, (6, 6), 0.25, 1.0);
+            solver.run_steps(3);
+        }
+        assert_eq!(times.into_inner().unwrap(), vec![0.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn insulated_obstacles_conserve_heat() {
+        for time_integration in time_integrations() {
+            let mut solver = solver(time_integration, CellType::Insulated);
+            let initial_heat = fluid_heat(&solver);
+            solver.run_steps(100);
+
+            let heat = fluid_heat(&solver);
+            assert!((heat - initial_heat).abs() < 1e-8,
+                    "{:?} changed the heat from {} to {}",
+                    time_integration,
+                    initial_heat,
+                    heat);
+            // The heat has to have spread past the obstacle
+            assert!(solver[(12, 6)] > 0.01);
+            assert_eq!(solver[(7, 6)], 0.0);
+        }
+    }
+
+    #[test]
+    fn fixed_values_stay_fixed() {
+        for time_integration in time_integrations() {
+            let mut solver = solver(time_integration, CellType::FixedValue(0.25));
+            for _ in 0..20 {
+                solver.solve_next_frame();
+                for y in 3..9 {
+                    for x in 6..9 {
+                        assert_eq!(solver[(x, y)], 0.25, "{:?}", time_integration);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::ops::Index;
+use BoundaryCondition;
+use BoundaryConditions3D;
+use DataField3D;
+use DifferentialEquation3D;
+use Float;
+use PreCondition3D;
+use scoped_threadpool::Pool;
+use num_cpus;
+
+pub struct Solver3D<T, D, BU, BD, BL, BR, BF, BB>
+    where T: Float,
+          D: DifferentialEquation3D<T>,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>,
+          BF: BoundaryCondition<T>,
+          BB: BoundaryCondition<T>
+{
+    differential_equation: D,
+    pub boundary_conditions: BoundaryConditions3D<T, BU, BD, BL, BR, BF, BB>,
+    delta_t: T,
+    h: T,
+    time: T,
+    fields: [DataField3D<T>; 2],
+    current_field: usize,
+    thread_pool: Pool,
+}
+
+impl<T, D, BU, BD, BL, BR, BF, BB> Index<(usize, usize, usize)>
+    for Solver3D<T, D, BU, BD, BL, BR, BF, BB>
+    where T: Float,
+          D: DifferentialEquation3D<T>,
+          BU: BoundaryCondition<T>,
+          BD: BoundaryCondition<T>,
+          BL: BoundaryCondition<T>,
+          BR: BoundaryCondition<T>,
+          BF: BoundaryCondition<T>,
+          BB: BoundaryCondition<T>
+{
+    type Output = T;
+
+    fn index(&self, index: (usize, usize, usize)) -> &T {
+        &self.fields[self.current_field][index]
+    }
+}
+
+impl<T, D, BU, BD, BL, BR, BF, BB> Solver3D<T, D, BU, BD, BL, BR, BF, BB>
+    where T: Float,
+          D: DifferentialEquation3D<T> + Sync + Send,
+          BU: BoundaryCondition<T> + Sync + Send,
+          BD: BoundaryCondition<T> + Sync + Send,
+          BL: BoundaryCondition<T> + Sync + Send,
+          BR: BoundaryCondition<T> + Sync + Send,
+          BF: BoundaryCondition<T> + Sync + Send,
+          BB: BoundaryCondition<T> + Sync + Send
+{
+    pub fn new(equation: D,
+               boundary_conditions: BoundaryConditions3D<T, BU, BD, BL, BR, BF, BB>,
+               dimensions: (usize, usize, usize),
+               delta_t: T,
+               h: T)
+               -> Self {
+        Solver3D {
+            differential_equation: equation,
+            boundary_conditions,
+            delta_t,
+            h,
+            time: T::zero(),
+            fields: [DataField3D::new(dimensions), DataField3D::new(dimensions)],
+            current_field: 0,
+            thread_pool: Pool::new(num_cpus::get() as u32),
+        }
+    }
+
+    pub fn execute_precondition<P: PreCondition3D<T> + ?Sized + Sync>(&mut self, precondition: &P) {
+        let current_field = &mut self.fields[self.current_field];
+        let thread_count = self.thread_pool.thread_count() as usize;
+
+        self.thread_pool.scoped(|scope| {
+            for chunk in current_field.chunks_mut(thread_count) {
+                scope.execute(move || {
+                    for (coord, cell) in chunk {
+                        *cell = precondition.precondition(coord);
+                    }
+                });
+            }
+        });
+    }
+
+    pub fn solve_next_frame(&mut self) {
+        // Prepare solving
+        let (field_a, field_b) = self.fields.split_at_mut(1);
+        let (current_field, target_field) = if self.current_field == 0 {
+            (&mut field_a[0], &mut field_b[0])
+        } else {
+            (&mut field_b[0], &mut field_a[0])
+        };
+        let equation = &self.differential_equation;
+        let delta_t = self.delta_t;
+        let h = self.h;
+        let thread_count = self.thread_pool.thread_count() as usize;
+
+        // Execute Boundary Conditions
+        self.boundary_conditions.calculate_boundaries(current_field, self.time, h);
+
+        // Solve the differential equation for the whole volume
+        self.thread_pool.scoped(|scope| {
+            let current_field = &current_field;
+            for chunk in target_field.chunks_inner_mut(thread_count) {
+                scope.execute(move || {
+                    for (coord, cell) in chunk {
+                        *cell = equation.solve(current_field, coord, delta_t, h);
+                    }
+                });
+            }
+        });
+
+        // Swap the fields
+        self.current_field ^= 1;
+        self.time = self.time + delta_t;
+    }
+
+    pub fn time(&self) -> T {
+        self.time
+    }
+
+    pub fn get_field(&self) -> &DataField3D<T> {
+        &self.fields[self.current_field]
+    }
+
+    pub fn get_field_mut(&mut self) -> &mut DataField3D<T> {
+        &mut self.fields[self.current_field]
+    }
+}
@@ -0,0 +1,134 @@
+//! Sources and sinks that add to the rate of change of a field, like heaters
+//! in a thermal conduction. A negative strength turns a source into a sink.
+
+mod point_source;
+mod region_source;
+mod volumetric_source;
+
+pub use self::point_source::PointSource;
+pub use self::region_source::RegionSource;
+pub use self::volumetric_source::VolumetricSource;
+
+use DataField;
+use Float;
+
+pub trait Source<T>
+    where T: Float
+{
+    /// Adds the rate of change the source causes at `time` to the cells of
+    /// `rates`, which is in units of the field per unit time. For equations
+    /// that are second order in time, the rates act as accelerations in units
+    /// of the field per unit time squared.
+    fn add_rates(&self, rates: &mut DataField<T>, time: T, h: T);
+}
+
+/// The strength of a source at a given time. Plain values are constant.
+pub trait Strength<T>
+    where T: Float
+{
+    fn at(&self, time: T) -> T;
+}
+
+impl<T> Strength<T> for T
+    where T: Float
+{
+    fn at(&self, _: T) -> T {
+        *self
+    }
+}
+
+/// A strength that changes over time, like a heater that gets switched on
+/// and off.
+pub struct Varying<F>(pub F);
+
+impl<T, F> Strength<T> for Varying<F>
+    where T: Float,
+          F: Fn(T) -> T
+{
+    fn at(&self, time: T) -> T {
+        (self.0)(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boundary_condition::Neumann;
+    use differential_equation::ThermalConduction;
+    use geometry::Rectangle;
+    use BoundaryConditions;
+    use Solver;
+    use super::*;
+
+    type TestSolver = Solver<f64, ThermalConduction, Neumann, Neumann, Neumann, Neumann>;
+
+    fn insulated_solver(h: f64) -> TestSolver {
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        Solver::new(ThermalConduction, boundary_conditions, (10, 8), 0.1, h)
+    }
+
+    /// The heat in the inner cells, which an insulated field conserves.
+    fn heat(solver: &TestSolver) -> f64 {
+        let mut heat = 0.0;
+        for y in 1..7 {
+            for x in 1..9 {
+                heat += solver[(x, y)] * solver.h() * solver.h();
+            }
+        }
+        heat
+    }
+
+    /// Checks that every step adds `injected` to the heat of the field.
+    fn assert_injects(mut solver: TestSolver, injected: f64) {
+        for step in 1..6 {
+            solver.solve_next_frame();
+            let expected = step as f64 * injected;
+            assert!((heat(&solver) - expected).abs() < 1e-12,
+                    "Injected {} after {} steps, expected {}",
+                    heat(&solver),
+                    step,
+                    expected);
+        }
+    }
+
+    #[test]
+    fn point_sources_inject_their_strength() {
+        let mut solver = insulated_solver(0.5);
+        solver.add_source(PointSource {
+            coord: (3, 4),
+            strength: 2.0,
+        });
+        assert_injects(solver, 2.0 * 0.1);
+    }
+
+    #[test]
+    fn point_sources_outside_of_the_field_are_ignored() {
+        let mut solver = insulated_solver(1.0);
+        for &coord in &[(0, 4), (9, 4), (3, 7), (100, 100)] {
+            solver.add_source(PointSource {
+                coord,
+                strength: 2.0,
+            });
+        }
+        assert_injects(solver, 0.0);
+    }
+
+    #[test]
+    fn region_sources_inject_their_strength_into_every_cell() {
+        let mut solver = insulated_solver(1.0);
+        solver.add_source(RegionSource {
+            shape: Rectangle {
+                position: (2, 2),
+                size: (3, 2),
+            },
+            strength: Varying(|time: f64| if time < 1.0 { 1.5 } else { 0.0 }),
+        });
+        assert_injects(solver, 6.0 * 1.5 * 0.1);
+    }
+
+    #[test]
+    fn volumetric_sources_inject_their_strength_into_every_cell() {
+        let mut solver = insulated_solver(1.0);
+        solver.add_source(VolumetricSource(|(x, _), _| if x == 1 { 0.5 } else { 0.0 }));
+        assert_injects(solver, 6.0 * 0.5 * 0.1);
+    }
+}
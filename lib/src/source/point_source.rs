@@ -0,0 +1,22 @@
+use DataField;
+use Float;
+use source::{Source, Strength};
+
+/// Adds `strength` per unit time to a single cell. As the cell covers an
+/// area of `h * h`, its value changes by `strength / (h * h)` per unit time.
+/// A coordinate outside of the inner cells of the field gets ignored.
+pub struct PointSource<S> {
+    pub coord: (usize, usize),
+    pub strength: S,
+}
+
+impl<T, S> Source<T> for PointSource<S>
+    where T: Float,
+          S: Strength<T>
+{
+    fn add_rates(&self, rates: &mut DataField<T>, time: T, h: T) {
+        if rates.is_inner(self.coord) {
+            rates[self.coord] = rates[self.coord] + self.strength.at(time) / (h * h);
+        }
+    }
+}
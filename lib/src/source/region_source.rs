@@ -0,0 +1,26 @@
+use DataField;
+use Float;
+use geometry::Shape;
+use source::{Source, Strength};
+
+/// Changes the value of every cell inside of the shape by `strength` per
+/// unit time.
+pub struct RegionSource<R, S> {
+    pub shape: R,
+    pub strength: S,
+}
+
+impl<T, R, S> Source<T> for RegionSource<R, S>
+    where T: Float,
+          R: Shape,
+          S: Strength<T>
+{
+    fn add_rates(&self, rates: &mut DataField<T>, time: T, _: T) {
+        let strength = self.strength.at(time);
+        for (coord, rate) in rates.iter_inner_mut() {
+            if self.shape.contains(coord) {
+                *rate = *rate + strength;
+            }
+        }
+    }
+}
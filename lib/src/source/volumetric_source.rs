@@ -0,0 +1,18 @@
+use DataField;
+use Float;
+use source::Source;
+
+/// A source whose strength per unit time is given for every cell and time,
+/// like the heat produced by an electric current.
+pub struct VolumetricSource<F>(pub F);
+
+impl<T, F> Source<T> for VolumetricSource<F>
+    where T: Float,
+          F: Fn((usize, usize), T) -> T
+{
+    fn add_rates(&self, rates: &mut DataField<T>, time: T, _: T) {
+        for (coord, rate) in rates.iter_inner_mut() {
+            *rate = *rate + (self.0)(coord, time);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use DataField;
+use data_field::reserve_fields;
+use Float;
+use LinearSolver;
+use scoped_threadpool::Pool;
+
+pub struct ButcherTableau {
+    pub a: &'static [&'static [f64]],
+    pub b: &'static [f64],
+    /// Difference between the weights of the solution and the weights of the
+    /// embedded lower order solution, along with the order of the latter.
+    pub error: Option<(&'static [f64], u32)>,
+}
+
+const HEUN: ButcherTableau = ButcherTableau {
+    a: &[&[], &[1.0]],
+    b: &[0.5, 0.5],
+    error: None,
+};
+
+const MIDPOINT: ButcherTableau = ButcherTableau {
+    a: &[&[], &[0.5]],
+    b: &[0.0, 1.0],
+    error: None,
+};
+
+const RUNGE_KUTTA_4: ButcherTableau = ButcherTableau {
+    a: &[&[], &[0.5], &[0.0, 0.5], &[0.0, 0.0, 1.0]],
+    b: &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+    error: None,
+};
+
+const HEUN_EULER: ButcherTableau = ButcherTableau {
+    a: &[&[], &[1.0]],
+    b: &[0.5, 0.5],
+    error: Some((&[-0.5, 0.5], 1)),
+};
+
+const BOGACKI_SHAMPINE: ButcherTableau = ButcherTableau {
+    a: &[&[], &[0.5], &[0.0, 0.75], &[2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0]],
+    b: &[2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
+    error: Some((&[-5.0 / 72.0, 1.0 / 12.0, 1.0 / 9.0, -1.0 / 8.0], 2)),
+};
+
+/// Time stepping scheme used by the `Solver`. The implicit schemes only treat
+/// the diffusion term of an equation implicitly, while all remaining terms
+/// stay explicit, which makes them semi-implicit for reaction-diffusion
+/// equations. `Leapfrog` is the only scheme for equations that are second
+/// order in time.
+#[derive(Clone, Copy, Debug)]
+pub enum TimeIntegration<T>
+    where T: Float
+{
+    ForwardEuler,
+    Heun,
+    Midpoint,
+    RungeKutta4,
+    HeunEuler,
+    BogackiShampine,
+    BackwardEuler(LinearSolver<T>),
+    CrankNicolson(LinearSolver<T>),
+    /// The central difference `u(t + dt) = 2 u(t) - u(t - dt) + dt^2 u''(t)`,
+    /// which keeps the previous time level around. Varying step sizes are
+    /// taken into account.
+    Leapfrog,
+}
+
+impl<T> TimeIntegration<T>
+    where T: Float
+{
+    /// Returns the Butcher tableau of the multi-stage explicit schemes.
+    pub fn runge_kutta_tableau(&self) -> Option<ButcherTableau> {
+        match *self {
+            TimeIntegration::Heun => Some(HEUN),
+            TimeIntegration::Midpoint => Some(MIDPOINT),
+            TimeIntegration::RungeKutta4 => Some(RUNGE_KUTTA_4),
+            TimeIntegration::HeunEuler => Some(HEUN_EULER),
+            TimeIntegration::BogackiShampine => Some(BOGACKI_SHAMPINE),
+            _ => None,
+        }
+    }
+
+    /// Returns the weight of the implicit part of the diffusion term together
+    /// with the linear solver to use, or `None` for explicit schemes.
+    pub fn implicitness(&self) -> Option<(T, LinearSolver<T>)> {
+        match *self {
+            TimeIntegration::BackwardEuler(linear_solver) => Some((T::one(), linear_solver)),
+            TimeIntegration::CrankNicolson(linear_solver) => {
+                Some((T::from_f64(0.5), linear_solver))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns how much larger than the stable forward Euler step the steps of
+    /// this scheme may be, or `None` if the scheme has no such limit.
+    pub fn stability_factor(&self) -> Option<T> {
+        match *self {
+            TimeIntegration::RungeKutta4 => Some(T::from_f64(1.39)),
+            TimeIntegration::BogackiShampine => Some(T::from_f64(1.25)),
+            TimeIntegration::BackwardEuler(_) |
+            TimeIntegration::CrankNicolson(_) => None,
+            _ => Some(T::one()),
+        }
+    }
+
+    /// Returns whether the scheme integrates equations that are second order
+    /// in time.
+    pub fn is_second_order(&self) -> bool {
+        matches!(*self, TimeIntegration::Leapfrog)
+    }
+}
+
+/// Determines how the `Solver` subdivides a frame of length `delta_t` into
+/// individual time steps.
+#[derive(Clone, Copy, Debug)]
+pub enum TimeStepControl<T>
+    where T: Float
+{
+    /// Every frame is a single step of length `delta_t`.
+    Fixed,
+    /// Frames are split into as many equally long steps as necessary to stay
+    /// below the stable time step reported by the equation, scaled by the
+    /// safety factor.
+    Stability { safety_factor: T },
+    /// Frames are split into steps whose length is adapted to keep the error
+    /// estimate of an embedded Runge-Kutta pair below the tolerances.
+    ErrorControl {
+        absolute_tolerance: T,
+        relative_tolerance: T,
+    },
+}
+
+/// A solver whose frames get split into steps by `solve_frame`.
+pub trait Stepper<T>
+    where T: Float
+{
+    /// Solves a step into the target fields. Returns the largest error of the
+    /// step relative to the tolerances, if they are given and the scheme
+    /// contains an embedded pair.
+    fn step(&mut self, delta_t: T, tolerances: Option<(T, T)>) -> T;
+
+    /// Makes the target fields of the last step the current ones and
+    /// advances the time.
+    fn accept(&mut self, delta_t: T);
+
+    /// The length of the step the error control tries next.
+    fn trial_delta_t(&mut self) -> &mut T;
+}
+
+/// Solves a frame of length `delta_t` in the steps the time step control
+/// asks for.
+pub fn solve_frame<T, S>(stepper: &mut S,
+                         time_integration: &TimeIntegration<T>,
+                         time_step_control: TimeStepControl<T>,
+                         stable_time_step: Option<T>,
+                         delta_t: T)
+    where T: Float,
+          S: Stepper<T>
+{
+    match time_step_control {
+        TimeStepControl::Fixed => advance(stepper, delta_t),
+        TimeStepControl::Stability { safety_factor } => {
+            let steps = match stable_time_step {
+                Some(limit) => {
+                    let steps = delta_t.to_f64() / (safety_factor * limit).to_f64();
+                    steps.ceil().max(1.0) as usize
+                }
+                None => 1,
+            };
+            let step = delta_t / T::from_f64(steps as f64);
+            for _ in 0..steps {
+                advance(stepper, step);
+            }
+        }
+        TimeStepControl::ErrorControl { absolute_tolerance, relative_tolerance } => {
+            solve_error_controlled(stepper,
+                                   time_integration,
+                                   delta_t,
+                                   (absolute_tolerance, relative_tolerance));
+        }
+    }
+}
+
+fn advance<T, S>(stepper: &mut S, delta_t: T)
+    where T: Float,
+          S: Stepper<T>
+{
+    stepper.step(delta_t, None);
+    stepper.accept(delta_t);
+}
+
+fn solve_error_controlled<T, S>(stepper: &mut S,
+                                time_integration: &TimeIntegration<T>,
+                                delta_t: T,
+                                tolerances: (T, T))
+    where T: Float,
+          S: Stepper<T>
+{
+    let exponent = match time_integration.runge_kutta_tableau().and_then(|t| t.error) {
+        Some((_, order)) => 1.0 / (order as f64 + 1.0),
+        None => panic!("Error control requires an embedded Runge-Kutta pair"),
+    };
+
+    let mut remaining = delta_t;
+    while remaining > T::zero() {
+        let trial_delta_t = *stepper.trial_delta_t();
+        let clipped = trial_delta_t >= remaining;
+        let delta_t = if clipped {
+            remaining
+        } else {
+            trial_delta_t
+        };
+
+        let error = stepper.step(delta_t, Some(tolerances)).to_f64();
+        let factor = if error == 0.0 {
+            5.0
+        } else if error.is_finite() {
+            (0.9 * error.powf(-exponent)).clamp(0.2, 5.0)
+        } else {
+            0.2
+        };
+        let next_delta_t = delta_t * T::from_f64(factor);
+
+        if error <= 1.0 {
+            stepper.accept(delta_t);
+            remaining = remaining - delta_t;
+            if !clipped || next_delta_t > trial_delta_t {
+                *stepper.trial_delta_t() = next_delta_t;
+            }
+        } else {
+            assert!(next_delta_t > T::zero(), "Time step underflow");
+            *stepper.trial_delta_t() = next_delta_t;
+        }
+    }
+}
+
+/// The right-hand sides of a system of fields, which `runge_kutta_step`
+/// integrates.
+pub trait System<T>: Sync
+    where T: Float
+{
+    fn calculate_boundaries(&self, fields: &mut [DataField<T>], time: T);
+
+    /// Gets called before the right-hand sides of the fields get evaluated
+    /// at `time`.
+    fn prepare(&mut self, _fields: &[DataField<T>], _time: T) {}
+
+    /// The patch is scratch space of the calling thread.
+    fn right_hand_side(&self,
+                       fields: &[DataField<T>],
+                       index: usize,
+                       coord: (usize, usize),
+                       patch: &mut DataField<T>)
+                       -> T;
+}
+
+/// A step of `delta_t` from `time`, along with the tolerances of the error
+/// estimation.
+#[derive(Clone, Copy)]
+pub struct Step<T>
+    where T: Float
+{
+    pub time: T,
+    pub delta_t: T,
+    pub tolerances: Option<(T, T)>,
+}
+
+/// Solves a step of an explicit Runge-Kutta scheme from the current fields
+/// into the target fields. Returns the largest error of the step relative to
+/// the tolerances, if they are given and the tableau contains an embedded
+/// pair.
+pub fn runge_kutta_step<T, S>(system: &mut S,
+                              tableau: &ButcherTableau,
+                              step: Step<T>,
+                              current_fields: &mut [DataField<T>],
+                              target_fields: &mut [DataField<T>],
+                              scratch_fields: &mut Vec<DataField<T>>,
+                              thread_pool: &mut Pool)
+                              -> T
+    where T: Float,
+          S: System<T>
+{
+    let Step { time, delta_t, tolerances } = step;
+    let thread_count = thread_pool.thread_count() as usize;
+    let field_count = current_fields.len();
+    let b = tableau.b;
+    let error_estimation = match (tableau.error, tolerances) {
+        (Some((weights, _)), Some(tolerances)) => Some((weights, tolerances)),
+        _ => None,
+    };
+
+    // The first fields of the scratch hold the intermediate stage values,
+    // the others the right-hand sides of the stages, one field after another
+    // for every stage
+    reserve_fields(scratch_fields, (b.len() + 1) * field_count, &current_fields[0]);
+    let (stage_fields, stage_rates) = scratch_fields.split_at_mut(field_count);
+
+    // Execute Boundary Conditions
+    system.calculate_boundaries(current_fields, time);
+
+    for (stage, weights) in tableau.a.iter().enumerate() {
+        let (previous_rates, rates) = stage_rates.split_at_mut(stage * field_count);
+        let stage_time = time + delta_t * T::from_f64(weights.iter().sum());
+
+        let stage_fields = if stage == 0 {
+            &current_fields[..]
+        } else {
+            // Determine the intermediate values from the previous stages
+            thread_pool.scoped(|scope| {
+                let current_fields = &current_fields;
+                let previous_rates = &previous_rates;
+                for (index, stage_field) in stage_fields.iter_mut().enumerate() {
+                    for chunk in stage_field.chunks_inner_mut(thread_count) {
+                        scope.execute(move || {
+                            for (coord, cell) in chunk {
+                                let mut increment = T::zero();
+                                for (&weight, rates) in
+                                    weights.iter().zip(previous_rates.chunks(field_count)) {
+                                    increment = increment +
+                                                T::from_f64(weight) * rates[index][coord];
+                                }
+                                *cell = current_fields[index][coord] + delta_t * increment;
+                            }
+                        });
+                    }
+                }
+            });
+            system.calculate_boundaries(stage_fields, stage_time);
+            &stage_fields[..]
+        };
+
+        // Evaluate the right-hand sides for this stage
+        system.prepare(stage_fields, stage_time);
+        let system = &*system;
+        thread_pool.scoped(|scope| {
+            for (index, rate) in rates[..field_count].iter_mut().enumerate() {
+                for chunk in rate.chunks_inner_mut(thread_count) {
+                    scope.execute(move || {
+                        let mut patch = DataField::new((1, 1));
+                        for (coord, cell) in chunk {
+                            *cell = system.right_hand_side(stage_fields, index, coord, &mut patch);
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    // Combine the stages
+    let mut errors = vec![T::zero(); field_count * thread_count];
+    thread_pool.scoped(|scope| {
+        let current_fields = &current_fields;
+        let stage_rates = &stage_rates;
+        let mut errors = errors.iter_mut();
+        for (index, target_field) in target_fields.iter_mut().enumerate() {
+            for (chunk, error) in target_field.chunks_inner_mut(thread_count).zip(&mut errors) {
+                scope.execute(move || {
+                    for (coord, cell) in chunk {
+                        let mut increment = T::zero();
+                        for (&weight, rates) in b.iter().zip(stage_rates.chunks(field_count)) {
+                            increment = increment + T::from_f64(weight) * rates[index][coord];
+                        }
+                        let current = current_fields[index][coord];
+                        *cell = current + delta_t * increment;
+
+                        if let Some((weights, (absolute, relative))) = error_estimation {
+                            let mut difference = T::zero();
+                            for (&weight, rates) in
+                                weights.iter().zip(stage_rates.chunks(field_count)) {
+                                difference = difference +
+                                             T::from_f64(weight) * rates[index][coord];
+                            }
+                            let magnitude = if current.abs() > cell.abs() {
+                                current.abs()
+                            } else {
+                                cell.abs()
+                            };
+                            let cell_error = (delta_t * difference).abs() /
+                                             (absolute + relative * magnitude);
+                            // NaN has to propagate to reject the step
+                            if cell_error > *error || cell_error.is_nan() {
+                                *error = cell_error;
+                            }
+                        }
+                    }
+                });
+            }
+        }
+    });
+
+    errors.into_iter().fold(T::zero(), |a, b| if b > a || b.is_nan() { b } else { a })
+}
+
+#[cfg(test)]
+mod tests {
+    use boundary_condition::Neumann;
+    use differential_equation::ThermalConduction;
+    use precondition::ConstantPreCondition;
+    use BoundaryConditions;
+    use DataField;
+    use DifferentialEquation;
+    use Solver;
+    use TimeIntegration;
+    use TimeStepControl;
+
+    /// The logistic growth `du/dt = u (1 - u)` in every cell.
+    struct Logistic;
+
+    impl DifferentialEquation<f64> for Logistic {
+        fn right_hand_side(&self, field: &DataField<f64>, coord: (usize, usize), _: f64) -> f64 {
+            field[coord] * (1.0 - field[coord])
+        }
+    }
+
+    type LogisticSolver = Solver<f64, Logistic, Neumann, Neumann, Neumann, Neumann>;
+
+    fn logistic_solver(time_integration: TimeIntegration<f64>, delta_t: f64) -> LogisticSolver {
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver = Solver::with_time_integration(Logistic,
+                                                       boundary_conditions,
+                                                       (5, 5),
+                                                       delta_t,
+                                                       1.0,
+                                                       time_integration);
+        solver.execute_precondition(&ConstantPreCondition(0.2));
+        solver
+    }
+
+    /// The distance of a logistic growth that started at 0.2 from the exact
+    /// solution.
+    fn logistic_error(solver: &LogisticSolver) -> f64 {
+        let exact = 1.0 / (1.0 + 4.0 * (-solver.time()).exp());
+        (solver[(2, 2)] - exact).abs()
+    }
+
+    /// The error at the time 1 after the given number of steps.
+    fn error(time_integration: TimeIntegration<f64>, steps: usize) -> f64 {
+        let mut solver = logistic_solver(time_integration, 1.0 / steps as f64);
+        for _ in 0..steps {
+            solver.solve_next_frame();
+        }
+        logistic_error(&solver)
+    }
+
+    #[test]
+    fn explicit_schemes_converge_with_their_order() {
+        let schemes = [(TimeIntegration::ForwardEuler, 1.0),
+                       (TimeIntegration::Heun, 2.0),
+                       (TimeIntegration::Midpoint, 2.0),
+                       (TimeIntegration::RungeKutta4, 4.0),
+                       (TimeIntegration::HeunEuler, 2.0),
+                       (TimeIntegration::BogackiShampine, 3.0)];
+        for &(time_integration, order) in &schemes {
+            let coarse = error(time_integration, 10);
+            let fine = error(time_integration, 20);
+            let observed = (coarse / fine).log2();
+            assert!((observed - order).abs() < 0.2,
+                    "{:?} converged with order {} instead of {}",
+                    time_integration,
+                    observed,
+                    order);
+        }
+    }
+
+    type HeatSolver = Solver<f64, ThermalConduction, Neumann, Neumann, Neumann, Neumann>;
+
+    /// A hot square in the corner of a cold, insulated field.
+    fn heat_solver(delta_t: f64) -> HeatSolver {
+        let boundary_conditions = BoundaryConditions::new(Neumann, Neumann, Neumann, Neumann);
+        let mut solver =
+            Solver::new(ThermalConduction, boundary_conditions, (12, 12), delta_t, 1.0);
+        for ((x, y), cell) in solver.get_field_mut().iter_mut() {
+            *cell = if x < 5 && y < 5 { 1.0 } else { 0.0 };
+        }
+        solver
+    }
+
+    fn largest_value(solver: &HeatSolver) -> f64 {
+        let mut largest = 0.0f64;
+        for y in 1..11 {
+            for x in 1..11 {
+                largest = largest.max(solver[(x, y)].abs());
+            }
+        }
+        largest
+    }
+
+    #[test]
+    fn stability_control_splits_frames_into_stable_steps() {
+        // The stable step is 0.25, so that a frame of 1 takes 5 steps of 0.2
+        let mut controlled = heat_solver(1.0);
+        controlled.time_step_control = TimeStepControl::Stability { safety_factor: 0.9 };
+        let mut reference = heat_solver(0.2);
+        let mut unstable = heat_solver(1.0);
+        for _ in 0..10 {
+            controlled.solve_next_frame();
+            for _ in 0..5 {
+                reference.solve_next_frame();
+            }
+            unstable.solve_next_frame();
+        }
+
+        assert_eq!(controlled.time(), reference.time());
+        for y in 0..12 {
+            for x in 0..12 {
+                assert_eq!(controlled[(x, y)], reference[(x, y)]);
+            }
+        }
+        assert!(largest_value(&controlled) <= 1.0);
+        assert!(largest_value(&unstable) > 1.0);
+    }
+
+    #[test]
+    fn error_control_stays_within_the_tolerance() {
+        for &time_integration in &[TimeIntegration::HeunEuler, TimeIntegration::BogackiShampine] {
+            for &tolerance in &[1e-4, 1e-7] {
+                let mut solver = logistic_solver(time_integration, 0.1);
+                solver.time_step_control = TimeStepControl::ErrorControl {
+                    absolute_tolerance: tolerance,
+                    relative_tolerance: tolerance,
+                };
+                for _ in 0..30 {
+                    solver.solve_next_frame();
+                    // The tolerances bound the error of every step, so that
+                    // the accumulated error may exceed them slightly
+                    let error = logistic_error(&solver);
+                    assert!(error < 5.0 * tolerance,
+                            "{:?} exceeded the tolerance {} with an error of {}",
+                            time_integration,
+                            tolerance,
+                            error);
+                }
+                assert!((solver.time() - 3.0).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Prescribed velocities of a flow that carries the solved quantity along,
+//! as used by the `AdvectionDiffusionEquation`.
+
+use std::sync::Arc;
+use DataField;
+use Float;
+
+pub trait Velocity<T>
+    where T: Float
+{
+    /// The velocity at the cell in units of length per unit time.
+    fn at(&self, coord: (usize, usize)) -> (T, T);
+
+    /// The largest absolute velocity along each axis, which limits the
+    /// stable time step. `None` if it isn't known.
+    fn max_speed(&self) -> Option<(T, T)> {
+        None
+    }
+}
+
+/// A uniform flow.
+impl<T> Velocity<T> for (T, T)
+    where T: Float
+{
+    fn at(&self, _: (usize, usize)) -> (T, T) {
+        *self
+    }
+
+    fn max_speed(&self) -> Option<(T, T)> {
+        Some((self.0.abs(), self.1.abs()))
+    }
+}
+
+/// Lets the equation and the boundary conditions share a velocity.
+impl<T, V> Velocity<T> for Arc<V>
+    where T: Float,
+          V: Velocity<T> + ?Sized
+{
+    fn at(&self, coord: (usize, usize)) -> (T, T) {
+        (**self).at(coord)
+    }
+
+    fn max_speed(&self) -> Option<(T, T)> {
+        (**self).max_speed()
+    }
+}
+
+/// A velocity given for every cell by its components, like the result of a
+/// flow simulation. Its largest speed gets determined once on creation.
+pub struct VelocityField<T>
+    where T: Float
+{
+    x: DataField<T>,
+    y: DataField<T>,
+    max_speed: (T, T),
+}
+
+impl<T> VelocityField<T>
+    where T: Float
+{
+    /// Both fields need the dimensions of the solved field, including its
+    /// ghost layers.
+    pub fn new(x: DataField<T>, y: DataField<T>) -> Self {
+        assert!(x.dimensions == y.dimensions,
+                "Both components of the velocity need the same dimensions");
+        let (nx, ny) = x.dimensions;
+        let mut max_speed = (T::zero(), T::zero());
+        for cy in 0..ny {
+            for cx in 0..nx {
+                let (u, v) = (x[(cx, cy)].abs(), y[(cx, cy)].abs());
+                if u > max_speed.0 {
+                    max_speed.0 = u;
+                }
+                if v > max_speed.1 {
+                    max_speed.1 = v;
+                }
+            }
+        }
+
+        VelocityField {
+            x,
+            y,
+            max_speed,
+        }
+    }
+
+    pub fn x(&self) -> &DataField<T> {
+        &self.x
+    }
+
+    pub fn y(&self) -> &DataField<T> {
+        &self.y
+    }
+}
+
+impl<T> Velocity<T> for VelocityField<T>
+    where T: Float
+{
+    fn at(&self, coord: (usize, usize)) -> (T, T) {
+        (self.x[coord], self.y[coord])
+    }
+
+    fn max_speed(&self) -> Option<(T, T)> {
+        Some(self.max_speed)
+    }
+}
+
+/// A velocity calculated from the coordinate of the cell, like a vortex or
+/// the parabolic profile of a channel flow.
+pub struct AnalyticVelocity<T, F>
+    where T: Float
+{
+    pub velocity: F,
+    /// The largest absolute velocity along each axis. Without it, the stable
+    /// time step only accounts for the diffusion, so the advection has to be
+    /// kept stable by choosing the time step by hand.
+    pub max_speed: Option<(T, T)>,
+}
+
+impl<T, F> Velocity<T> for AnalyticVelocity<T, F>
+    where T: Float,
+          F: Fn((usize, usize)) -> (T, T)
+{
+    fn at(&self, coord: (usize, usize)) -> (T, T) {
+        (self.velocity)(coord)
+    }
+
+    fn max_speed(&self) -> Option<(T, T)> {
+        self.max_speed
+    }
+}
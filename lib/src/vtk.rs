@@ -0,0 +1,301 @@
+//! Export of fields as VTK image data, which can be opened in ParaView. Every
+//! cell of a field, including the boundary, becomes a point of the image with
+//! the grid spacing `h` between neighbouring points.
+
+use std::io::{self, Write};
+use std::mem;
+use DataField;
+use DataField3D;
+use Float;
+
+/// A field that can be exported as VTK image data.
+pub trait ImageData<T>
+    where T: Float
+{
+    fn extent(&self) -> (usize, usize, usize);
+    fn value(&self, coord: (usize, usize, usize)) -> T;
+}
+
+impl<T> ImageData<T> for DataField<T>
+    where T: Float
+{
+    fn extent(&self) -> (usize, usize, usize) {
+        let (nx, ny) = self.dimensions;
+        (nx, ny, 1)
+    }
+
+    fn value(&self, (x, y, _): (usize, usize, usize)) -> T {
+        self[(x, y)]
+    }
+}
+
+impl<T> ImageData<T> for DataField3D<T>
+    where T: Float
+{
+    fn extent(&self) -> (usize, usize, usize) {
+        self.dimensions
+    }
+
+    fn value(&self, coord: (usize, usize, usize)) -> T {
+        self[coord]
+    }
+}
+
+/// Writes the named fields in the legacy ASCII VTK format.
+pub fn write_legacy<T, F, W>(writer: &mut W,
+                             fields: &[(&str, &F)],
+                             h: T,
+                             time: T)
+                             -> io::Result<()>
+    where T: Float,
+          F: ImageData<T>,
+          W: Write
+{
+    let (nx, ny, nz) = common_extent(fields)?;
+    for &(name, _) in fields {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("Invalid field name \"{}\"", name)));
+        }
+    }
+
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "rusty_solver")?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET STRUCTURED_POINTS")?;
+    writeln!(writer, "DIMENSIONS {} {} {}", nx, ny, nz)?;
+    writeln!(writer, "ORIGIN 0 0 0")?;
+    writeln!(writer, "SPACING {:?} {:?} {:?}", h, h, h)?;
+    writeln!(writer, "FIELD FieldData 1")?;
+    writeln!(writer, "TIME 1 1 double")?;
+    writeln!(writer, "{:?}", time.to_f64())?;
+    writeln!(writer, "POINT_DATA {}", nx * ny * nz)?;
+
+    let type_name = if mem::size_of::<T>() == 4 {
+        "float"
+    } else {
+        "double"
+    };
+
+    for &(name, field) in fields {
+        writeln!(writer, "SCALARS {} {} 1", name, type_name)?;
+        writeln!(writer, "LOOKUP_TABLE default")?;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    writeln!(writer, "{:?}", field.value((x, y, z)))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the named fields in the XML image data format (`.vti`). The values
+/// are stored as raw binary appended data, so they are exported losslessly.
+pub fn write_vti<T, F, W>(writer: &mut W,
+                          fields: &[(&str, &F)],
+                          h: T,
+                          time: T)
+                          -> io::Result<()>
+    where T: Float,
+          F: ImageData<T>,
+          W: Write
+{
+    let (nx, ny, nz) = common_extent(fields)?;
+    let extent = format!("0 {} 0 {} 0 {}", nx - 1, ny - 1, nz - 1);
+    let type_name = if mem::size_of::<T>() == 4 {
+        "Float32"
+    } else {
+        "Float64"
+    };
+    let bytes = (nx * ny * nz * mem::size_of::<T>()) as u64;
+
+    writeln!(writer, "<?xml version=\"1.0\"?>")?;
+    writeln!(writer,
+             "<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" \
+              header_type=\"UInt64\">")?;
+    writeln!(writer,
+             "  <ImageData WholeExtent=\"{}\" Origin=\"0 0 0\" Spacing=\"{:?} {:?} {:?}\">",
+             extent,
+             h,
+             h,
+             h)?;
+    writeln!(writer, "    <FieldData>")?;
+    writeln!(writer,
+             "      <DataArray type=\"Float64\" Name=\"TimeValue\" NumberOfTuples=\"1\" \
+              format=\"ascii\">{:?}</DataArray>",
+             time.to_f64())?;
+    writeln!(writer, "    </FieldData>")?;
+    writeln!(writer, "    <Piece Extent=\"{}\">", extent)?;
+    writeln!(writer, "      <PointData Scalars=\"{}\">", escape(fields[0].0))?;
+    for (index, &(name, _)) in fields.iter().enumerate() {
+        // Every array is preceded by its size in bytes
+        let offset = index as u64 * (bytes + 8);
+        writeln!(writer,
+                 "        <DataArray type=\"{}\" Name=\"{}\" format=\"appended\" \
+                  offset=\"{}\"/>",
+                 type_name,
+                 escape(name),
+                 offset)?;
+    }
+    writeln!(writer, "      </PointData>")?;
+    writeln!(writer, "    </Piece>")?;
+    writeln!(writer, "  </ImageData>")?;
+    writeln!(writer, "  <AppendedData encoding=\"raw\">")?;
+    write!(writer, "_")?;
+    for &(_, field) in fields {
+        writer.write_all(&bytes.to_le_bytes())?;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    field.value((x, y, z)).write_to(writer)?;
+                }
+            }
+        }
+    }
+    writeln!(writer)?;
+    writeln!(writer, "  </AppendedData>")?;
+    writeln!(writer, "</VTKFile>")
+}
+
+/// An index of snapshots written at different simulation times, which lets
+/// ParaView load them as a single time series (`.pvd`).
+#[derive(Clone, Debug, Default)]
+pub struct TimeSeries {
+    data_sets: Vec<(f64, String)>,
+}
+
+impl TimeSeries {
+    pub fn new() -> Self {
+        TimeSeries { data_sets: Vec::new() }
+    }
+
+    /// Adds a snapshot. The file name is relative to the `.pvd` file.
+    pub fn add<T: Float>(&mut self, time: T, file: &str) {
+        self.data_sets.push((time.to_f64(), file.to_string()));
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "<?xml version=\"1.0\"?>")?;
+        writeln!(writer, "<VTKFile type=\"Collection\" version=\"0.1\">")?;
+        writeln!(writer, "  <Collection>")?;
+        for &(time, ref file) in &self.data_sets {
+            writeln!(writer,
+                     "    <DataSet timestep=\"{:?}\" group=\"\" part=\"0\" file=\"{}\"/>",
+                     time,
+                     escape(file))?;
+        }
+        writeln!(writer, "  </Collection>")?;
+        writeln!(writer, "</VTKFile>")
+    }
+}
+
+fn common_extent<T, F>(fields: &[(&str, &F)]) -> io::Result<(usize, usize, usize)>
+    where T: Float,
+          F: ImageData<T>
+{
+    let extent = match fields.first() {
+        Some(&(_, field)) => field.extent(),
+        None => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "At least one field is needed"))
+        }
+    };
+    if fields.iter().any(|&(_, field)| field.extent() != extent) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "All fields need to have the same dimensions"));
+    }
+    Ok(extent)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_legacy, write_vti, TimeSeries};
+    use checkpoint::read_u64;
+    use DataField;
+    use Float;
+
+    fn field(offset: f64) -> DataField<f64> {
+        let mut field = DataField::new((4, 3));
+        for ((x, y), cell) in field.iter_mut() {
+            *cell = offset + x as f64 + 0.1 * y as f64;
+        }
+        field
+    }
+
+    #[test]
+    fn writes_the_legacy_format() {
+        let mut field = DataField::new((3, 3));
+        field[(1, 1)] = 0.5f32;
+        let mut bytes = Vec::new();
+        write_legacy(&mut bytes, &[("phase", &field)], 2.0, 1.5).unwrap();
+
+        let expected = "# vtk DataFile Version 3.0\nrusty_solver\nASCII\n\
+                        DATASET STRUCTURED_POINTS\nDIMENSIONS 3 3 1\nORIGIN 0 0 0\n\
+                        SPACING 2.0 2.0 2.0\nFIELD FieldData 1\nTIME 1 1 double\n1.5\n\
+                        POINT_DATA 9\nSCALARS phase float 1\nLOOKUP_TABLE default\n\
+                        0.0\n0.0\n0.0\n0.0\n0.5\n0.0\n0.0\n0.0\n0.0\n";
+        assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn vti_data_round_trips() {
+        let (first, second) = (field(0.0), field(1.0 / 3.0));
+        let mut bytes = Vec::new();
+        write_vti(&mut bytes, &[("a", &first), ("b", &second)], 1.0, 0.25).unwrap();
+
+        let marker = b"<AppendedData encoding=\"raw\">\n_";
+        let start = bytes.windows(marker.len()).position(|window| window == marker).unwrap() +
+                    marker.len();
+        let mut data = &bytes[start..];
+        for field in &[first, second] {
+            assert_eq!(read_u64(&mut data).unwrap(), 12 * 8);
+            for y in 0..3 {
+                for x in 0..4 {
+                    assert_eq!(f64::read_from(&mut data).unwrap().to_bits(),
+                               field[(x, y)].to_bits());
+                }
+            }
+        }
+        assert_eq!(data, &b"\n  </AppendedData>\n</VTKFile>\n"[..]);
+
+        let header = String::from_utf8_lossy(&bytes[..start]);
+        assert!(header.contains("WholeExtent=\"0 3 0 2 0 0\""));
+        assert!(header.contains("Name=\"b\" format=\"appended\" offset=\"104\""));
+        assert!(header.contains(">0.25</DataArray>"));
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let (small, large) = (field(0.0), DataField::new((5, 3)));
+        let mut bytes = Vec::new();
+        assert!(write_vti(&mut bytes, &[("a", &small), ("b", &large)], 1.0, 0.0).is_err());
+        assert!(write_legacy(&mut bytes, &[("two words", &small)], 1.0, 0.0).is_err());
+        assert!(write_legacy::<f64, DataField<f64>, _>(&mut bytes, &[], 1.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn writes_the_time_series() {
+        let mut series = TimeSeries::new();
+        series.add(0.0, "a_0.vti");
+        series.add(0.5f32, "a&b_1.vti");
+        let mut bytes = Vec::new();
+        series.write_to(&mut bytes).unwrap();
+
+        let expected = "<?xml version=\"1.0\"?>\n\
+                        <VTKFile type=\"Collection\" version=\"0.1\">\n  <Collection>\n    \
+                        <DataSet timestep=\"0.0\" group=\"\" part=\"0\" file=\"a_0.vti\"/>\n    \
+                        <DataSet timestep=\"0.5\" group=\"\" part=\"0\" \
+                        file=\"a&amp;b_1.vti\"/>\n  </Collection>\n</VTKFile>\n";
+        assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+    }
+}